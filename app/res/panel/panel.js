@@ -0,0 +1,193 @@
+"use strict";
+
+const $ = (id) => document.getElementById(id);
+
+let status = null;
+let builtinEffects = [];
+let token = new URLSearchParams(location.search).get("token") || localStorage.getItem("token") || "";
+if (token) {
+  localStorage.setItem("token", token);
+}
+
+function effectName(effect) {
+  return typeof effect === "string" ? effect : Object.keys(effect)[0];
+}
+
+function toHex(rgb) {
+  return "#" + rgb.map((value) => value.toString(16).padStart(2, "0")).join("");
+}
+
+function fromHex(hex) {
+  return [1, 3, 5].map((i) => parseInt(hex.slice(i, i + 2), 16));
+}
+
+function showError(message) {
+  $("error").textContent = message;
+  $("error").hidden = !message;
+}
+
+async function api(method, path, body) {
+  const headers = {};
+  if (token) {
+    headers.Authorization = `Bearer ${token}`;
+  }
+  if (body !== undefined) {
+    headers["Content-Type"] = "application/json";
+  }
+
+  const response = await fetch(path, { method, headers, body: body === undefined ? undefined : JSON.stringify(body) });
+  if (response.status === 401) {
+    const entered = prompt("Token");
+    if (entered === null) {
+      showError("A token is needed");
+      throw new Error("A token is needed");
+    }
+    token = entered;
+    localStorage.setItem("token", token);
+    return api(method, path, body);
+  }
+  if (!response.ok) {
+    const error = await response.json().catch(() => ({ error: response.statusText }));
+    showError(error.error);
+    throw new Error(error.error);
+  }
+
+  showError("");
+  return response.status === 204 ? null : response.json();
+}
+
+/** Show the current profile with a change made to it */
+function editProfile(edit) {
+  if (!status) {
+    return;
+  }
+  const profile = structuredClone(status.profile);
+  edit(profile);
+  api("PUT", "/api/profile", profile);
+}
+
+function fillList(list, items, active, onClick) {
+  list.replaceChildren(
+    ...items
+      .filter((item) => item.name)
+      .map((item) => {
+        const button = document.createElement("button");
+        button.textContent = item.name;
+        button.classList.toggle("active", item.name === active);
+        button.addEventListener("click", () => onClick(item.name));
+        const entry = document.createElement("li");
+        entry.append(button);
+        return entry;
+      })
+  );
+}
+
+let profiles = [];
+let customEffects = [];
+
+function showLists() {
+  fillList($("profiles"), profiles, status && !status.custom_effect ? status.profile.name : null, (name) =>
+    api("POST", `/api/profiles/${encodeURIComponent(name)}/apply`)
+  );
+  fillList($("effects"), customEffects, status && status.custom_effect, (name) => api("POST", `/api/effects/${encodeURIComponent(name)}/play`));
+}
+
+async function loadLists() {
+  [profiles, customEffects, builtinEffects] = await Promise.all([api("GET", "/api/profiles"), api("GET", "/api/effects"), api("GET", "/api/builtin-effects")]);
+
+  $("effect").replaceChildren(
+    ...builtinEffects.map((effect) => {
+      const option = document.createElement("option");
+      option.textContent = effect.name;
+      return option;
+    })
+  );
+
+  showLists();
+  if (status) {
+    showStatus(status);
+  }
+}
+
+function showStatus(next) {
+  status = next;
+  const profile = status.profile;
+
+  $("effect").value = effectName(profile.effect);
+  $("brightness").checked = profile.brightness === "High";
+  document.querySelectorAll(".colors input").forEach((input) => {
+    input.value = toHex(profile.rgb_zones[input.dataset.zone].rgb);
+  });
+
+  $("lights").textContent = status.lights_on ? "Lights off" : "Lights on";
+  $("pause").textContent = status.paused ? "Resume" : "Pause";
+  $("showing").textContent = status.custom_effect ? `Playing ${status.custom_effect}` : `Showing ${profile.name || "an unsaved profile"}`;
+
+  showLists();
+}
+
+function showFrame(colors) {
+  document.querySelectorAll(".zone").forEach((zone, i) => {
+    zone.style.background = toHex(colors.slice(i * 3, i * 3 + 3));
+  });
+}
+
+function connect() {
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  const socket = new WebSocket(`${scheme}://${location.host}/api/stream?token=${encodeURIComponent(token)}`);
+
+  socket.addEventListener("open", () => {
+    $("connection").textContent = "Online";
+    $("connection").className = "online";
+    loadLists().catch(() => {});
+  });
+  socket.addEventListener("message", (message) => {
+    const event = JSON.parse(message.data);
+    if (event.type === "status") {
+      showStatus(event.status);
+    } else if (event.type === "frame") {
+      showFrame(event.colors);
+    }
+  });
+  socket.addEventListener("close", () => {
+    $("connection").textContent = "Offline";
+    $("connection").className = "offline";
+    setTimeout(connect, 2000);
+  });
+}
+
+$("effect").addEventListener("change", () => {
+  const picked = builtinEffects.find((effect) => effect.name === $("effect").value);
+  editProfile((profile) => {
+    profile.effect = picked.effect;
+  });
+});
+
+$("brightness").addEventListener("change", () => {
+  editProfile((profile) => {
+    profile.brightness = $("brightness").checked ? "High" : "Low";
+  });
+});
+
+document.querySelectorAll(".colors input").forEach((input) => {
+  input.addEventListener("change", () => {
+    editProfile((profile) => {
+      profile.rgb_zones[input.dataset.zone].rgb = fromHex(input.value);
+    });
+  });
+});
+
+for (const [id, action] of [
+  ["previous", "PreviousProfile"],
+  ["next", "NextProfile"],
+  ["lights", "ToggleLights"],
+  ["pause", "PauseEffect"],
+]) {
+  $(id).addEventListener("click", () => api("POST", "/api/actions", action));
+}
+
+// Make sure there is a token before the stream is opened, if one is needed
+api("GET", "/api/status")
+  .then(showStatus)
+  .catch(() => {})
+  .finally(connect);
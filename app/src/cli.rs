@@ -1,18 +1,24 @@
 use std::{convert::TryInto, path::PathBuf, str::FromStr};
 
 use clap::{arg, command, Parser, Subcommand};
-use error_stack::{Result, ResultExt};
+use error_stack::{Report, Result, ResultExt};
 use strum::IntoEnumIterator;
 use thiserror::Error;
 
 use crate::{
-    enums::{Brightness, Direction, Effects},
+    daemon,
+    enums::{AudioSource, Brightness, Direction, Effects, RenderMode},
+    ipc::{Client, Request, Response},
     manager::{
         self,
         custom_effect::CustomEffect,
-        profile::{self, Profile},
+        key_zones,
+        palette::{ColorStop, Palette},
+        profile::{self, AudioSettings, BatterySettings, ProceduralSettings, Profile, TemperatureSettings},
+        transition::Transition,
         ManagerCreationError,
     },
+    persist::Settings,
     DENY_HIDING,
 };
 
@@ -44,6 +50,10 @@ struct Cli {
     /// Do not show the window when launching (use along the --gui flag)
     #[arg(short = 'w', long, default_value_t = false)]
     hide_window: bool,
+
+    /// Run in the background without a window, taking commands from other invocations of the program
+    #[arg(long, default_value_t = false)]
+    daemon: bool,
 }
 
 #[derive(Subcommand)]
@@ -62,6 +72,34 @@ enum Commands {
         #[arg(short, long, default_value = "0,0,0,0,0,0,0,0,0,0,0,0", value_parser = parse_colors)]
         colors: Option<[u8; 12]>,
 
+        /// Color stops for the gradient effects, as position:R,G,B separated by semicolons. Example: 0:255,0,0;0.5:0,255,0;1:0,0,255
+        #[arg(short, long, value_parser = parse_palette)]
+        palette: Option<Palette>,
+
+        /// Seed for the effects that rely on randomness (Fire, Twinkle, Comet and Plasma)
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// How saturated the colors are, from 0 to 1 (Rainbow only)
+        #[arg(long, default_value_t = 1.0)]
+        saturation: f32,
+
+        /// Where the sound comes from (AudioVisualizer only) [possible values: Output, Input, File, Pipe]
+        #[arg(long, default_value = "Output")]
+        audio_source: AudioSource,
+
+        /// The WAV file or raw PCM pipe to read from, "-" reading the PCM from stdin (AudioVisualizer only)
+        #[arg(long)]
+        audio_path: Option<String>,
+
+        /// The sensor label or hwmon path to read, picking the CPU automatically if left out (Temperature only)
+        #[arg(long)]
+        sensor: Option<String>,
+
+        /// Pulse red over the effect when the battery runs low
+        #[arg(long, default_value_t = false)]
+        battery_overlay: bool,
+
         /// The brightness of the effect [possible values: Low, High]
         #[arg(short, long, default_value = "Low", value_parser)]
         brightness: Brightness,
@@ -77,6 +115,10 @@ enum Commands {
         /// A filename to save the effect at
         #[arg(long, value_enum)]
         save: Option<PathBuf>,
+
+        /// Emulate the effect instead of letting the keyboard run it (Breath, Smooth and Wave only)
+        #[arg(long, default_value_t = false)]
+        software: bool,
     },
 
     /// List all the available effects
@@ -93,6 +135,12 @@ enum Commands {
         #[arg(short, long)]
         path: PathBuf,
     },
+
+    /// Print what the running instance is showing, as JSON
+    Status,
+
+    /// Print what the running instance is showing every time it changes, one JSON object per line
+    Watch,
 }
 
 fn parse_colors(arg: &str) -> std::result::Result<[u8; 12], String> {
@@ -113,12 +161,42 @@ fn parse_colors(arg: &str) -> std::result::Result<[u8; 12], String> {
     }
 }
 
+fn parse_palette(arg: &str) -> std::result::Result<Palette, String> {
+    fn input_err<E>(_e: E) -> String {
+        "Invalid input, please check you used the correct format for the palette".to_string()
+    }
+
+    let stops: std::result::Result<Vec<ColorStop>, String> = arg
+        .split(';')
+        .map(|stop| {
+            let (position, rgb) = stop.split_once(':').ok_or_else(|| input_err(()))?;
+            let position = position.trim().parse::<f32>().map_err(input_err)?;
+            let rgb: Vec<u8> = rgb.split(',').map(|channel| channel.trim().parse::<u8>()).collect::<std::result::Result<_, _>>().map_err(input_err)?;
+
+            Ok(ColorStop {
+                position,
+                rgb: rgb.try_into().map_err(input_err)?,
+            })
+        })
+        .collect();
+
+    let stops = stops?;
+    if stops.is_empty() || stops.len() > Palette::MAX_STOPS || stops.iter().any(|stop| !(0.0..=1.0).contains(&stop.position)) {
+        return Err(input_err(()));
+    }
+
+    Ok(Palette { stops })
+}
+
 pub enum CliOutput {
     /// Start the UI
     Gui { hide_window: bool, output_type: OutputType },
 
     /// CLI arguments were passed
     Cli(OutputType),
+
+    /// Run without a window
+    Daemon,
 }
 
 pub enum GuiCommand {
@@ -132,7 +210,7 @@ pub enum GuiCommand {
 /// What instruction was received through the CLI
 #[derive(Clone)]
 pub enum OutputType {
-    Profile(Profile),
+    Profile(Box<Profile>),
     Custom(CustomEffect),
     NoArgs,
     Exit,
@@ -146,6 +224,7 @@ pub fn try_cli() -> Result<GuiCommand, CliError> {
     let output_type = parse_cli()?;
 
     match output_type {
+        CliOutput::Gui { output_type, .. } | CliOutput::Cli(output_type) if forward(&output_type) => Ok(GuiCommand::Exit),
         CliOutput::Gui { hide_window, output_type } => {
             if *DENY_HIDING && hide_window {
                 println!("Window hiding is currently not supported. See https://github.com/4JX/L5P-Keyboard-RGB/issues/181");
@@ -153,9 +232,34 @@ pub fn try_cli() -> Result<GuiCommand, CliError> {
             Ok(GuiCommand::Start { hide_window, output_type })
         }
         CliOutput::Cli(output_type) => handle_cli_output(output_type),
+        CliOutput::Daemon => {
+            daemon::run().change_context(CliError)?;
+            Ok(GuiCommand::Exit)
+        }
     }
 }
 
+/// Hand a profile or custom effect to the instance that owns the keyboard, returning whether there was one to take it
+fn forward(output_type: &OutputType) -> bool {
+    let request = match output_type {
+        OutputType::Profile(profile) => Request::SetProfile { profile: profile.clone() },
+        OutputType::Custom(effect) => Request::CustomEffect { effect: effect.clone() },
+        OutputType::NoArgs | OutputType::Exit => return false,
+    };
+
+    let Ok(mut client) = Client::connect() else {
+        return false;
+    };
+
+    match client.request(&request) {
+        Ok(Response::Error { message }) => println!("The running instance could not do that: {message}"),
+        Ok(_) => {}
+        Err(err) => eprintln!("{err:?}"),
+    }
+
+    true
+}
+
 fn handle_cli_output(output_type: OutputType) -> Result<GuiCommand, CliError> {
     let manager_result = manager::EffectManager::new(manager::OperationMode::Cli);
     let instance_not_unique = manager_result.as_ref().err().is_some_and(|err| &ManagerCreationError::InstanceAlreadyRunning == err.current_context());
@@ -166,10 +270,17 @@ fn handle_cli_output(output_type: OutputType) -> Result<GuiCommand, CliError> {
     }
 
     let mut effect_manager = manager_result.change_context(CliError)?;
+    let settings = Settings::load();
+    // There's no reading back what the keyboard showed before this process took it, so nothing to fade from
+    effect_manager.set_transition(Transition {
+        duration_ms: 0,
+        ..settings.transition
+    });
+    key_zones::configure(&settings.key_zones);
 
     let command_result = match output_type {
         OutputType::Profile(profile) => {
-            effect_manager.set_profile(profile);
+            effect_manager.set_profile(*profile);
             Ok(GuiCommand::Exit)
         }
         OutputType::Custom(effect) => {
@@ -187,15 +298,27 @@ fn handle_cli_output(output_type: OutputType) -> Result<GuiCommand, CliError> {
 fn parse_cli() -> Result<CliOutput, CliError> {
     let cli = Cli::parse();
 
+    if cli.daemon {
+        return Ok(CliOutput::Daemon);
+    }
+
     if let Some(subcommand) = cli.command {
         match subcommand {
             Commands::Set {
                 effect,
                 colors,
+                palette,
+                seed,
+                saturation,
+                audio_source,
+                audio_path,
+                sensor,
+                battery_overlay,
                 brightness,
                 speed,
                 direction,
                 save,
+                software,
             } => {
                 let direction = direction.unwrap_or_default();
                 let rgb_array = if effect.takes_color_array() {
@@ -214,8 +337,28 @@ fn parse_cli() -> Result<CliOutput, CliError> {
                     direction,
                     speed,
                     brightness,
+                    palette: palette.unwrap_or_default(),
+                    procedural: ProceduralSettings { seed, saturation },
+                    audio: AudioSettings {
+                        source: audio_source,
+                        path: audio_path.unwrap_or_default(),
+                        ..Default::default()
+                    },
+                    temperature: TemperatureSettings {
+                        sensors: vec![sensor.unwrap_or_default()],
+                        ..Default::default()
+                    },
+                    battery: BatterySettings {
+                        overlay: battery_overlay,
+                        ..Default::default()
+                    },
+                    ..Default::default()
                 };
 
+                if let Some(render_mode) = profile.render_mode_mut().filter(|_| software) {
+                    *render_mode = RenderMode::Software;
+                }
+
                 if let Some(filename) = save {
                     profile.save_profile(&filename).expect("Failed to save.");
                 }
@@ -223,10 +366,10 @@ fn parse_cli() -> Result<CliOutput, CliError> {
                 if cli.gui {
                     return Ok(CliOutput::Gui {
                         hide_window: cli.hide_window,
-                        output_type: OutputType::Profile(profile),
+                        output_type: OutputType::Profile(Box::new(profile)),
                     });
                 } else {
-                    return Ok(CliOutput::Cli(OutputType::Profile(profile)));
+                    return Ok(CliOutput::Cli(OutputType::Profile(Box::new(profile))));
                 }
             }
             Commands::List => {
@@ -241,7 +384,7 @@ fn parse_cli() -> Result<CliOutput, CliError> {
                 let profile = Profile::load_profile(&path).change_context(CliError)?;
                 return Ok(CliOutput::Gui {
                     hide_window: cli.hide_window,
-                    output_type: OutputType::Profile(profile),
+                    output_type: OutputType::Profile(Box::new(profile)),
                 });
             }
 
@@ -252,6 +395,23 @@ fn parse_cli() -> Result<CliOutput, CliError> {
                     output_type: OutputType::Custom(effect),
                 });
             }
+
+            Commands::Status => {
+                let response = Client::connect().and_then(|mut client| client.request(&Request::Status)).change_context(CliError)?;
+                match response {
+                    Response::Status { status } => println!("{}", serde_json::to_string(&status).unwrap()),
+                    response => return Err(Report::new(CliError).attach_printable(format!("Unexpected response: {response:?}"))),
+                }
+                return Ok(CliOutput::Cli(OutputType::Exit));
+            }
+
+            Commands::Watch => {
+                let statuses = Client::connect().and_then(Client::subscribe).change_context(CliError)?;
+                for status in statuses {
+                    println!("{}", serde_json::to_string(&status).unwrap());
+                }
+                return Ok(CliOutput::Cli(OutputType::Exit));
+            }
         }
     }
 
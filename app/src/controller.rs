@@ -0,0 +1,161 @@
+//! What the GUI and the daemon both do with actions, requests from other instances and the schedule
+//!
+//! Each only says how to show, play and save things, so the two can't end up reacting differently.
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    enums::{Action, Brightness},
+    focus,
+    ipc::{self, Request, Response, Status},
+    manager::{custom_effect::CustomEffect, profile::Profile},
+    schedule::{self, ScheduleSettings, Target},
+};
+
+pub trait Controller {
+    fn profiles(&self) -> &[Profile];
+    fn profiles_mut(&mut self) -> &mut Vec<Profile>;
+    fn custom_effects(&self) -> &[CustomEffect];
+    fn custom_effects_mut(&mut self) -> &mut Vec<CustomEffect>;
+    fn schedule(&self) -> &ScheduleSettings;
+    /// Called after a profile or custom effect was saved
+    fn saved(&mut self) {}
+
+    fn current_profile(&self) -> &Profile;
+    /// The custom effect playing, or about to, over the profile
+    fn custom_effect(&self) -> Option<&CustomEffect>;
+    fn status(&self) -> Status;
+
+    fn show_profile(&mut self, profile: Profile);
+    fn play(&mut self, effect: CustomEffect);
+    fn set_lights(&mut self, on: bool);
+    fn pause(&mut self);
+
+    /// Do what a hotkey was bound to
+    fn run_action(&mut self, action: Action) -> Response {
+        match action {
+            Action::NextProfile => self.cycle_profiles(true),
+            Action::PreviousProfile => self.cycle_profiles(false),
+            Action::Profile { name } => match focus::find_profile(self.profiles(), &name) {
+                Some(profile) => self.show_profile(profile.clone()),
+                None => {
+                    return Response::Error {
+                        message: format!("There is no saved profile called \"{name}\""),
+                    }
+                }
+            },
+            Action::BrightnessUp | Action::BrightnessDown => {
+                let brightness = if action == Action::BrightnessUp { Brightness::High } else { Brightness::Low };
+                if self.current_profile().brightness != brightness && self.custom_effect().is_none() {
+                    let mut profile = self.current_profile().clone();
+                    profile.brightness = brightness;
+                    self.show_profile(profile);
+                }
+            }
+            Action::ToggleLights => {
+                let lights_on = self.status().lights_on;
+                self.set_lights(!lights_on);
+            }
+            Action::PauseEffect => {
+                if !self.status().paused {
+                    self.pause();
+                } else if let Some(effect) = self.custom_effect().cloned() {
+                    // Play the custom effect from the start again, if that's what was paused
+                    self.play(effect);
+                } else {
+                    self.show_profile(self.current_profile().clone());
+                }
+            }
+            Action::CustomEffect { name } => match self.custom_effects().iter().find(|effect| effect.name.as_ref() == Some(&name)) {
+                Some(effect) => self.play(effect.clone()),
+                None => {
+                    return Response::Error {
+                        message: format!("There is no saved custom effect called \"{name}\""),
+                    }
+                }
+            },
+        }
+
+        Response::Ok
+    }
+
+    /// Answer another instance, or a script
+    fn handle_request(&mut self, request: Request) -> Response {
+        match request {
+            Request::SetProfile { profile } => self.show_profile(*profile),
+            Request::CustomEffect { effect } => self.play(effect),
+            Request::Action { action } => return self.run_action(action),
+            Request::Status | Request::Subscribe => return Response::Status { status: Box::new(self.status()) },
+            Request::Profiles => return Response::Profiles { profiles: self.profiles().to_vec() },
+            Request::SaveProfile { profile } => {
+                let response = ipc::save_named(self.profiles_mut(), *profile, |profile| profile.name.as_ref());
+                if matches!(response, Response::Ok) {
+                    self.saved();
+                }
+                return response;
+            }
+            Request::CustomEffects => {
+                return Response::CustomEffects {
+                    effects: self.custom_effects().to_vec(),
+                }
+            }
+            Request::SaveCustomEffect { effect } => {
+                let response = ipc::save_named(self.custom_effects_mut(), effect, |effect| effect.name.as_ref());
+                if matches!(response, Response::Ok) {
+                    self.saved();
+                }
+                return response;
+            }
+        }
+
+        Response::Ok
+    }
+
+    fn cycle_profiles(&mut self, forward: bool) {
+        let profiles = self.profiles();
+        let len = profiles.len();
+
+        if let Some(i) = profiles.iter().position(|profile| profile.name == self.current_profile().name) {
+            let next = if forward { (i + 1) % len } else { (i + len - 1) % len };
+            self.show_profile(profiles[next].clone());
+        }
+    }
+
+    /// Do what the last entry that started since the schedule was last gone through says, returning up to when it now has been
+    fn follow_schedule(&mut self, since: NaiveDateTime) -> NaiveDateTime {
+        let now = schedule::now();
+
+        match self.schedule().started(since, now).and_then(|entry| entry.target(self.profiles())) {
+            Some(Target::Profile(profile)) => {
+                if *profile != *self.current_profile() {
+                    self.show_profile(*profile);
+                }
+                // Undo an earlier entry that turned them off
+                if !self.status().lights_on {
+                    self.set_lights(true);
+                }
+            }
+            Some(Target::LightsOff) if self.status().lights_on => self.set_lights(false),
+            Some(Target::LightsOff) | None => {}
+        }
+
+        now
+    }
+
+    /// Do what the entry that started while the program wasn't running says, returning whether that showed a profile
+    ///
+    /// The current profile still has to be shown after an entry that turned the lights off, for when they're turned back on.
+    fn catch_up_on_schedule(&mut self, now: NaiveDateTime) -> bool {
+        match self.schedule().current(now).and_then(|entry| entry.target(self.profiles())) {
+            Some(Target::Profile(profile)) => {
+                self.show_profile(*profile);
+                true
+            }
+            Some(Target::LightsOff) => {
+                self.set_lights(false);
+                false
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Runs the lights without a window, taking requests from other instances and scripts over IPC
+
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDateTime;
+use crossbeam_channel::select;
+use error_stack::{Result, ResultExt};
+use thiserror::Error;
+
+use crate::{
+    controller::Controller,
+    hotkeys,
+    ipc::{Server, Status},
+    manager::{custom_effect::CustomEffect, key_zones, profile::Profile, EffectManager, OperationMode},
+    persist::Settings,
+    schedule::{self, ScheduleSettings},
+};
+
+#[derive(Debug, Error)]
+#[error("Could not start the daemon")]
+pub struct DaemonError;
+
+struct Daemon {
+    manager: EffectManager,
+    server: Server,
+    settings: Settings,
+    custom_effect: Option<CustomEffect>,
+    lights_on: bool,
+    paused: bool,
+    /// Up to when the schedule has been gone through
+    schedule_checked: NaiveDateTime,
+    /// The last status subscribers were told about
+    published: Option<Status>,
+}
+
+/// Take over the keyboard and serve requests until the process is stopped
+pub fn run() -> Result<(), DaemonError> {
+    let manager = EffectManager::new(OperationMode::Daemon).change_context(DaemonError)?;
+    let (mut server, calls) = Server::start().change_context(DaemonError)?;
+
+    let settings = Settings::load();
+    key_zones::configure(&settings.key_zones);
+    manager.set_transition(settings.transition);
+    manager.set_idle(settings.idle);
+    manager.set_session(settings.session.clone());
+    manager.set_power(settings.power.clone());
+
+    // Everything else still works without it
+    if let Err(err) = server.serve_api(&settings.api, manager.previewer()) {
+        eprintln!("{err:?}");
+    }
+    server.serve_mqtt(&settings.mqtt);
+
+    let mut actions = hotkeys::listen(Arc::new(Mutex::new(settings.hotkeys.clone())));
+    let ticks = crossbeam_channel::tick(schedule::CHECK_INTERVAL);
+
+    let mut daemon = Daemon {
+        manager,
+        server,
+        settings,
+        custom_effect: None,
+        lights_on: true,
+        paused: false,
+        schedule_checked: schedule::now(),
+        published: None,
+    };
+
+    if !daemon.catch_up_on_schedule(daemon.schedule_checked) {
+        daemon.show_profile(daemon.settings.current_profile.clone());
+    }
+    daemon.publish();
+
+    loop {
+        select! {
+            recv(calls) -> call => {
+                let Ok((request, reply)) = call else {
+                    break;
+                };
+                let _ = reply.send(daemon.handle_request(request));
+            }
+            recv(actions) -> action => match action {
+                Ok(action) => {
+                    daemon.run_action(action);
+                }
+                // Without the keyboard to read from, there's no point in waiting on it
+                Err(_) => actions = crossbeam_channel::never(),
+            },
+            recv(ticks) -> _ => daemon.schedule_checked = daemon.follow_schedule(daemon.schedule_checked),
+        }
+
+        daemon.publish();
+    }
+
+    daemon.manager.shutdown();
+
+    Ok(())
+}
+
+impl Controller for Daemon {
+    fn profiles(&self) -> &[Profile] {
+        &self.settings.profiles
+    }
+
+    fn profiles_mut(&mut self) -> &mut Vec<Profile> {
+        &mut self.settings.profiles
+    }
+
+    fn custom_effects(&self) -> &[CustomEffect] {
+        &self.settings.effects
+    }
+
+    fn custom_effects_mut(&mut self) -> &mut Vec<CustomEffect> {
+        &mut self.settings.effects
+    }
+
+    fn schedule(&self) -> &ScheduleSettings {
+        &self.settings.schedule
+    }
+
+    fn saved(&mut self) {
+        self.settings.save();
+    }
+
+    fn current_profile(&self) -> &Profile {
+        &self.settings.current_profile
+    }
+
+    fn custom_effect(&self) -> Option<&CustomEffect> {
+        self.custom_effect.as_ref()
+    }
+
+    fn status(&self) -> Status {
+        Status {
+            profile: self.settings.current_profile.clone(),
+            custom_effect: self.custom_effect.as_ref().and_then(|effect| effect.name.clone()),
+            lights_on: self.lights_on,
+            paused: self.paused,
+        }
+    }
+
+    fn show_profile(&mut self, profile: Profile) {
+        self.settings.current_profile = profile.clone();
+        self.custom_effect = None;
+        self.paused = false;
+        self.manager.set_profile(profile);
+    }
+
+    fn play(&mut self, effect: CustomEffect) {
+        self.custom_effect = Some(effect.clone());
+        self.paused = false;
+        self.manager.custom_effect(effect);
+    }
+
+    fn set_lights(&mut self, on: bool) {
+        self.lights_on = on;
+        self.manager.set_lights(on);
+    }
+
+    fn pause(&mut self) {
+        self.manager.pause();
+        self.paused = true;
+    }
+}
+
+impl Daemon {
+    /// Tell subscribers about any change, keeping the profile for the next time the program starts
+    fn publish(&mut self) {
+        let status = self.status();
+        if self.published.as_ref() == Some(&status) {
+            return;
+        }
+
+        if self.published.as_ref().is_none_or(|published| published.profile != status.profile) {
+            self.settings.save();
+        }
+
+        self.server.broadcast(&status);
+        self.published = Some(status);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::manager::{custom_effect::CustomEffect, profile::Profile};
+use crate::manager::{custom_effect::CustomEffect, profile::Profile, session::SessionEvent};
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumIter, EnumString, IntoStaticStr};
 
@@ -14,6 +14,9 @@ pub enum Effects {
         fps: u8,
         saturation_boost: f32,
     },
+    AudioVisualizer {
+        mode: VisualizerMode,
+    },
     SmoothWave {
         mode: SwipeMode,
         clean_with_black: bool,
@@ -26,7 +29,25 @@ pub enum Effects {
     Christmas,
     Fade,
     Temperature,
+    LoadMeter {
+        metric: LoadMetric,
+        style: MeterStyle,
+    },
+    Battery,
     Ripple,
+    Reactive,
+    TypingSpeed {
+        style: MeterStyle,
+    },
+    Heatmap,
+    Gradient {
+        mode: GradientMode,
+    },
+    Fire,
+    Twinkle,
+    Comet,
+    Plasma,
+    Rainbow,
 }
 
 #[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq)]
@@ -36,6 +57,136 @@ pub enum SwipeMode {
     Fill,
 }
 
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq)]
+pub enum GradientMode {
+    #[default]
+    Static,
+    Scrolling,
+    RotatingHue,
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq)]
+pub enum VisualizerMode {
+    /// Bass on one side of the keyboard through treble on the other
+    #[default]
+    Spectrum,
+    /// The overall volume, filling the keyboard like a VU meter
+    Meter,
+}
+
+/// What the load meter shows
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq, Eq)]
+pub enum LoadMetric {
+    #[default]
+    Cpu,
+    Memory,
+    Gpu,
+    Disk,
+    Network,
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, PartialEq, Eq)]
+pub enum MeterStyle {
+    /// Fill the keyboard from one side
+    #[default]
+    Bar,
+    /// Color the whole keyboard
+    Gauge,
+}
+
+/// How a zone of the reactive effect fades after a key press
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum DecayCurve {
+    /// Fade at a steady rate
+    Linear,
+    /// Drop quickly at first, then linger
+    #[default]
+    Quadratic,
+    /// A short flash with a long dim tail
+    Exponential,
+}
+
+impl DecayCurve {
+    /// Turn how much of the decay is left, from `1.0` right after a press down to `0.0`, into an intensity
+    pub fn apply(self, remaining: f32) -> f32 {
+        let remaining = remaining.clamp(0.0, 1.0);
+
+        match self {
+            Self::Linear => remaining,
+            Self::Quadratic => remaining * remaining,
+            Self::Exponential => remaining.mul_add(10.0, -10.0).exp2() * remaining,
+        }
+    }
+}
+
+/// What part of the focused window a focus rule looks at
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum WindowField {
+    /// The `WM_CLASS` on X11, or the app id on Wayland
+    #[default]
+    Class,
+    Title,
+    /// The name of the process that owns the window
+    Process,
+}
+
+/// Something a hotkey can do, whatever is showing the lights
+#[derive(Default, Debug, Clone, Serialize, Deserialize, EnumIter, IntoStaticStr, PartialEq, Eq)]
+pub enum Action {
+    #[default]
+    NextProfile,
+    PreviousProfile,
+    /// Switch to the saved profile with this name
+    Profile {
+        name: String,
+    },
+    BrightnessUp,
+    BrightnessDown,
+    /// Turn the lights off, or back on
+    ToggleLights,
+    /// Leave the running effect on its current frame, or start it again
+    PauseEffect,
+    /// Play the saved custom effect with this name
+    CustomEffect {
+        name: String,
+    },
+}
+
+/// Where the audio visualizer gets its sound from
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum AudioSource {
+    /// Whatever is playing on the default output device
+    #[default]
+    Output,
+    /// The default input device, usually a microphone
+    Input,
+    /// A WAV file, played back in a loop
+    File,
+    /// Raw signed 16-bit little endian PCM read from a file or named pipe, `-` standing for stdin
+    Pipe,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumIter, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum LockKey {
+    #[strum(serialize = "Caps Lock")]
+    Caps,
+    #[strum(serialize = "Num Lock")]
+    Num,
+    #[strum(serialize = "Scroll Lock")]
+    Scroll,
+}
+
+impl LockKey {
+    /// The name the kernel gives the LED of the key
+    pub fn led_name(self) -> &'static str {
+        match self {
+            Self::Caps => "capslock",
+            Self::Num => "numlock",
+            Self::Scroll => "scrolllock",
+        }
+    }
+}
+
 impl PartialEq for Effects {
     fn eq(&self, other: &Self) -> bool {
         core::mem::discriminant(self) == core::mem::discriminant(other)
@@ -45,25 +196,72 @@ impl PartialEq for Effects {
 #[allow(dead_code)]
 impl Effects {
     pub fn takes_color_array(self) -> bool {
-        matches!(self, Self::Static | Self::Breath | Self::Lightning | Self::Swipe { .. } | Self::Fade | Self::Ripple)
+        matches!(self, Self::Static | Self::Breath | Self::Lightning | Self::Swipe { .. } | Self::Fade | Self::Ripple | Self::Reactive)
     }
 
     pub fn takes_direction(self) -> bool {
-        matches!(self, Self::Wave | Self::SmoothWave { .. } | Self::Swipe { .. })
+        matches!(
+            self,
+            Self::Wave
+                | Self::AudioVisualizer { .. }
+                | Self::LoadMeter { .. }
+                | Self::Battery
+                | Self::TypingSpeed { .. }
+                | Self::SmoothWave { .. }
+                | Self::Swipe { .. }
+                | Self::Gradient { .. }
+                | Self::Fire
+                | Self::Comet
+                | Self::Plasma
+                | Self::Rainbow
+        )
     }
 
     pub fn takes_speed(self) -> bool {
         matches!(
             self,
-            Self::Breath | Self::Smooth | Self::Wave | Self::Lightning | Self::SmoothWave { .. } | Self::Swipe { .. } | Self::Disco | Self::Fade | Self::Ripple
+            Self::Breath
+                | Self::Smooth
+                | Self::Wave
+                | Self::Lightning
+                | Self::SmoothWave { .. }
+                | Self::Swipe { .. }
+                | Self::Disco
+                | Self::Fade
+                | Self::Ripple
+                | Self::Gradient { .. }
+                | Self::Fire
+                | Self::Twinkle
+                | Self::Comet
+                | Self::Plasma
+                | Self::Rainbow
         )
     }
 
+    pub fn takes_palette(self) -> bool {
+        matches!(
+            self,
+            Self::AudioVisualizer { .. } | Self::SmoothWave { .. } | Self::Gradient { .. } | Self::Twinkle | Self::Comet | Self::Plasma
+        )
+    }
+
+    pub fn takes_seed(self) -> bool {
+        matches!(self, Self::Fire | Self::Twinkle | Self::Comet | Self::Plasma)
+    }
+
     pub fn is_built_in(self) -> bool {
         matches!(self, Self::Static | Self::Breath | Self::Smooth | Self::Wave)
     }
 }
 
+/// Whether an effect the keyboard can run by itself is left to the firmware or emulated by the program
+#[derive(Clone, Copy, EnumString, Serialize, Deserialize, Debug, EnumIter, IntoStaticStr, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    #[default]
+    Hardware,
+    Software,
+}
+
 #[derive(Clone, Copy, EnumString, Serialize, Deserialize, Debug, EnumIter, IntoStaticStr, PartialEq, Eq, Default)]
 pub enum Direction {
     #[default]
@@ -80,7 +278,16 @@ pub enum Brightness {
 
 #[derive(Debug)]
 pub enum Message {
-    CustomEffect { effect: CustomEffect },
-    Profile { profile: Profile },
+    CustomEffect {
+        effect: CustomEffect,
+    },
+    Profile {
+        profile: Box<Profile>,
+    },
+    Session {
+        event: SessionEvent,
+    },
+    /// The power source changed, which the effect thread reads back itself
+    Power,
     Exit,
 }
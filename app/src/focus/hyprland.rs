@@ -0,0 +1,86 @@
+use std::{
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crossbeam_channel::Sender;
+use error_stack::{Result, ResultExt};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{process_name, FocusError, FocusedWindow};
+
+/// Events after which the focused window is asked for again
+const EVENTS: [&str; 3] = ["activewindow", "windowtitle", "fullscreen"];
+
+#[derive(Deserialize)]
+struct ActiveWindow {
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    pid: Option<u32>,
+    /// A boolean in older versions, a mode where `0` means windowed in newer ones
+    #[serde(default)]
+    fullscreen: Value,
+}
+
+/// Follow the event socket of Hyprland, asking it for the active window whenever it might have changed
+pub fn start(tx: Sender<FocusedWindow>) -> Result<(), FocusError> {
+    let directory = socket_directory().ok_or(FocusError::NoBackend)?;
+    let events = UnixStream::connect(directory.join(".socket2.sock")).change_context(FocusError::Connect)?;
+
+    thread::spawn(move || {
+        if let Some(window) = active_window(&directory) {
+            let _ = tx.send(window);
+        }
+
+        for line in BufReader::new(events).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let event = line.split_once(">>").map_or(line.as_str(), |(event, _)| event);
+            if !EVENTS.contains(&event) {
+                continue;
+            }
+
+            if tx.send(active_window(&directory).unwrap_or_default()).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Newer versions keep their sockets in the runtime directory, older ones in `/tmp`
+fn socket_directory() -> Option<PathBuf> {
+    let signature = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(|runtime| PathBuf::from(runtime).join("hypr").join(&signature))
+        .filter(|directory| directory.exists())
+        .or_else(|| Some(PathBuf::from("/tmp/hypr").join(&signature)))
+}
+
+fn active_window(directory: &Path) -> Option<FocusedWindow> {
+    let mut stream = UnixStream::connect(directory.join(".socket.sock")).ok()?;
+    stream.write_all(b"j/activewindow").ok()?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).ok()?;
+
+    let window: ActiveWindow = serde_json::from_str(&reply).ok()?;
+
+    Some(FocusedWindow {
+        class: window.class,
+        title: window.title,
+        process: window.pid.map(process_name).unwrap_or_default(),
+        fullscreen: window.fullscreen.as_bool().unwrap_or_else(|| window.fullscreen.as_u64().is_some_and(|mode| mode != 0)),
+    })
+}
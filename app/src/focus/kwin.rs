@@ -0,0 +1,117 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    thread,
+};
+
+use crossbeam_channel::Sender;
+use error_stack::{Report, Result, ResultExt};
+use zbus::{blocking::connection, interface, proxy};
+
+use super::{process_name, FocusError, FocusedWindow};
+
+const OBJECT_PATH: &str = "/FocusedWindow";
+const PLUGIN_NAME: &str = "legion-kb-rgb-focus";
+
+/// Reports the active window back over D-Bus, in both KWin 5 and 6 where the signals and types were renamed
+const SCRIPT: &str = r#"
+function report(window) {
+    if (!window) {
+        callDBus(SERVICE, PATH, "io.github._4JX.L5PKeyboardRGB.FocusedWindow", "Changed", "", "", "", false);
+        return;
+    }
+    callDBus(SERVICE, PATH, "io.github._4JX.L5PKeyboardRGB.FocusedWindow", "Changed", window.resourceClass || "", window.caption || "", String(window.pid || ""), !!window.fullScreen);
+}
+
+const followed = [];
+
+function follow(window) {
+    report(window);
+    if (window && followed.indexOf(window) < 0) {
+        followed.push(window);
+        window.captionChanged.connect(function () { if (window.active) report(window); });
+        window.fullScreenChanged.connect(function () { if (window.active) report(window); });
+    }
+}
+
+(workspace.windowActivated || workspace.clientActivated).connect(follow);
+follow(workspace.activeWindow || workspace.activeClient);
+"#;
+
+#[proxy(interface = "org.kde.kwin.Scripting", default_service = "org.kde.KWin", default_path = "/Scripting")]
+trait Scripting {
+    fn load_script(&self, file_path: &str, plugin_name: &str) -> zbus::Result<i32>;
+    fn unload_script(&self, plugin_name: &str) -> zbus::Result<bool>;
+    fn start(&self) -> zbus::Result<()>;
+}
+
+/// Where the script's calls end up
+struct Listener {
+    tx: Sender<FocusedWindow>,
+    /// Told once nobody is receiving windows anymore, so the script can be unloaded
+    stop: Sender<()>,
+}
+
+#[interface(name = "io.github._4JX.L5PKeyboardRGB.FocusedWindow")]
+impl Listener {
+    /// The process id comes as a string, as numbers from scripts don't reliably map onto one D-Bus type
+    fn changed(&self, class: String, title: String, pid: String, fullscreen: bool) {
+        let process = pid.parse().map(process_name).unwrap_or_default();
+        if self.tx.send(FocusedWindow { class, title, process, fullscreen }).is_err() {
+            let _ = self.stop.send(());
+        }
+    }
+}
+
+/// Load a script into KWin that calls back whenever the active window changes
+pub fn start(tx: Sender<FocusedWindow>) -> Result<(), FocusError> {
+    let (stop_tx, stop_rx) = crossbeam_channel::bounded(1);
+    let connection = connection::Builder::session()
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, Listener { tx, stop: stop_tx }))
+        .and_then(|builder| builder.build())
+        .change_context(FocusError::Connect)?;
+
+    let service = connection.unique_name().ok_or(FocusError::Connect)?.to_string();
+    let script = format!("const SERVICE = \"{service}\";\nconst PATH = \"{OBJECT_PATH}\";\n{SCRIPT}");
+    let path = write_script(&script)?;
+
+    let scripting = ScriptingProxyBlocking::new(&connection).change_context(FocusError::Connect)?;
+    // A script left over from an earlier run would call back to a connection that is gone
+    let _ = scripting.unload_script(PLUGIN_NAME);
+    let loaded = scripting.load_script(&path.to_string_lossy(), PLUGIN_NAME).and_then(|_| scripting.start());
+    if let Err(err) = loaded {
+        let _ = fs::remove_file(&path);
+        return Err(Report::new(err).change_context(FocusError::Connect));
+    }
+
+    // The connection has to stay around to answer the script, until nobody is receiving windows
+    thread::spawn(move || {
+        let _ = stop_rx.recv();
+        let _ = ScriptingProxyBlocking::new(&connection).and_then(|scripting| scripting.unload_script(PLUGIN_NAME));
+        let _ = fs::remove_file(&path);
+    });
+
+    Ok(())
+}
+
+/// Write the script where only this user can get at it, since KWin runs whatever it's pointed at
+fn write_script(script: &str) -> Result<PathBuf, FocusError> {
+    let runtime = env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| Report::new(FocusError::Connect).attach_printable("XDG_RUNTIME_DIR is not set"))?;
+    let path = PathBuf::from(runtime).join(format!("{PLUGIN_NAME}-{}.js", std::process::id()));
+
+    // Nobody else can write to the runtime directory, so anything already there is from an earlier run
+    let _ = fs::remove_file(&path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .change_context(FocusError::Connect)
+        .attach_printable_lazy(|| format!("Could not write the KWin script to {}", path.display()))?;
+    file.write_all(script.as_bytes()).change_context(FocusError::Connect)?;
+
+    Ok(path)
+}
@@ -0,0 +1,119 @@
+//! The focused window, and the rules that pick a profile for it
+//!
+//! Windows are followed through the IPC of Hyprland or Sway when running under them, through a KWin script under
+//! Plasma on Wayland, and through EWMH everywhere else on X11.
+
+use crossbeam_channel::Receiver;
+use error_stack::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{enums::WindowField, manager::profile::Profile};
+
+#[cfg(target_os = "linux")]
+mod hyprland;
+#[cfg(target_os = "linux")]
+mod kwin;
+#[cfg(target_os = "linux")]
+mod sway;
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FocusedWindow {
+    pub class: String,
+    pub title: String,
+    pub process: String,
+    pub fullscreen: bool,
+}
+
+impl FocusedWindow {
+    pub fn field(&self, field: WindowField) -> &str {
+        match field {
+            WindowField::Class => &self.class,
+            WindowField::Title => &self.title,
+            WindowField::Process => &self.process,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FocusError {
+    #[error("Could not find a way of telling which window is focused")]
+    NoBackend,
+    #[error("Could not connect to the window manager")]
+    Connect,
+}
+
+/// Receive the focused window every time it or its title changes, or it goes in or out of fullscreen
+#[cfg(target_os = "linux")]
+pub fn watch() -> Result<Receiver<FocusedWindow>, FocusError> {
+    use std::env;
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        hyprland::start(tx)?;
+    } else if env::var_os("SWAYSOCK").is_some() || env::var_os("I3SOCK").is_some() {
+        sway::start(tx)?;
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() && env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| desktop.contains("KDE")) {
+        kwin::start(tx)?;
+    } else if env::var_os("DISPLAY").is_some() {
+        x11::start(tx)?;
+    } else {
+        return Err(FocusError::NoBackend.into());
+    }
+
+    Ok(rx)
+}
+
+/// The name of a running process, empty if it's gone or there is no telling
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> String {
+    std::fs::read_to_string(format!("/proc/{pid}/comm")).map(|name| name.trim().to_string()).unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch() -> Result<Receiver<FocusedWindow>, FocusError> {
+    Err(FocusError::NoBackend.into())
+}
+
+/// Switches to a saved profile when a matching window is focused
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct FocusSettings {
+    pub enabled: bool,
+    /// Checked in order, the first one that matches wins
+    pub rules: Vec<FocusRule>,
+    /// The saved profile shown when no rule matches, instead of going back to what was shown before one did
+    pub fallback: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct FocusRule {
+    pub field: WindowField,
+    /// Looked for anywhere in the field, ignoring case
+    pub pattern: String,
+    pub fullscreen_only: bool,
+    /// The name of the saved profile to switch to
+    pub profile: String,
+}
+
+impl FocusRule {
+    pub fn matches(&self, window: &FocusedWindow) -> bool {
+        !self.pattern.is_empty() && (window.fullscreen || !self.fullscreen_only) && window.field(self.field).to_lowercase().contains(&self.pattern.to_lowercase())
+    }
+}
+
+impl FocusSettings {
+    /// The name of the profile a window asks for, if any rule matches it
+    pub fn profile_for(&self, window: &FocusedWindow) -> Option<&str> {
+        self.rules.iter().find(|rule| rule.matches(window)).map(|rule| rule.profile.as_str())
+    }
+}
+
+/// Look a saved profile up by name
+pub fn find_profile<'a>(profiles: &'a [Profile], name: &str) -> Option<&'a Profile> {
+    profiles.iter().find(|profile| profile.name.as_deref() == Some(name))
+}
@@ -0,0 +1,101 @@
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+};
+
+use crossbeam_channel::Sender;
+use error_stack::{Result, ResultExt};
+use serde::Deserialize;
+
+use super::{process_name, FocusError, FocusedWindow};
+
+const MAGIC: &[u8] = b"i3-ipc";
+const SUBSCRIBE: u32 = 2;
+
+#[derive(Deserialize)]
+struct WindowEvent {
+    change: String,
+    container: Container,
+}
+
+#[derive(Deserialize)]
+struct Container {
+    #[serde(default)]
+    name: Option<String>,
+    /// Set for Wayland windows
+    #[serde(default)]
+    app_id: Option<String>,
+    #[serde(default)]
+    pid: Option<u32>,
+    #[serde(default)]
+    fullscreen_mode: u8,
+    #[serde(default)]
+    focused: bool,
+    /// Set for XWayland windows
+    #[serde(default)]
+    window_properties: Option<WindowProperties>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    #[serde(default)]
+    class: Option<String>,
+}
+
+/// Follow the window events of Sway, or i3 which speaks the same protocol
+pub fn start(tx: Sender<FocusedWindow>) -> Result<(), FocusError> {
+    let path = env::var_os("SWAYSOCK").or_else(|| env::var_os("I3SOCK")).ok_or(FocusError::NoBackend)?;
+    let mut stream = UnixStream::connect(path).change_context(FocusError::Connect)?;
+
+    send(&mut stream, SUBSCRIBE, br#"["window"]"#).change_context(FocusError::Connect)?;
+    // The reply to subscribing, which only says whether it worked
+    receive(&mut stream).change_context(FocusError::Connect)?;
+
+    thread::spawn(move || {
+        while let Ok(payload) = receive(&mut stream) {
+            let Ok(event) = serde_json::from_slice::<WindowEvent>(&payload) else {
+                continue;
+            };
+
+            if !matches!(event.change.as_str(), "focus" | "title" | "fullscreen_mode") || !event.container.focused {
+                continue;
+            }
+
+            let container = event.container;
+            let window = FocusedWindow {
+                class: container.app_id.or_else(|| container.window_properties.and_then(|properties| properties.class)).unwrap_or_default(),
+                title: container.name.unwrap_or_default(),
+                process: container.pid.map(process_name).unwrap_or_default(),
+                fullscreen: container.fullscreen_mode != 0,
+            };
+
+            if tx.send(window).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn send(stream: &mut UnixStream, kind: u32, payload: &[u8]) -> std::io::Result<()> {
+    let mut message = MAGIC.to_vec();
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+/// The payload of the next message, be it a reply or an event
+fn receive(stream: &mut UnixStream) -> std::io::Result<Vec<u8>> {
+    let mut header = [0; MAGIC.len() + 8];
+    stream.read_exact(&mut header)?;
+
+    let length = u32::from_ne_bytes(header[MAGIC.len()..MAGIC.len() + 4].try_into().unwrap());
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+
+    Ok(payload)
+}
@@ -0,0 +1,113 @@
+use std::thread;
+
+use crossbeam_channel::Sender;
+use error_stack::{Result, ResultExt};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window},
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{process_name, FocusError, FocusedWindow};
+
+struct Atoms {
+    active_window: Atom,
+    name: Atom,
+    utf8_string: Atom,
+    pid: Atom,
+    state: Atom,
+    fullscreen: Atom,
+}
+
+impl Atoms {
+    fn new(connection: &RustConnection) -> Option<Self> {
+        let intern = |name: &[u8]| Some(connection.intern_atom(false, name).ok()?.reply().ok()?.atom);
+
+        Some(Self {
+            active_window: intern(b"_NET_ACTIVE_WINDOW")?,
+            name: intern(b"_NET_WM_NAME")?,
+            utf8_string: intern(b"UTF8_STRING")?,
+            pid: intern(b"_NET_WM_PID")?,
+            state: intern(b"_NET_WM_STATE")?,
+            fullscreen: intern(b"_NET_WM_STATE_FULLSCREEN")?,
+        })
+    }
+}
+
+/// Follow `_NET_ACTIVE_WINDOW` on the root window, and the properties of whatever window it points to
+pub fn start(tx: Sender<FocusedWindow>) -> Result<(), FocusError> {
+    let (connection, screen) = x11rb::connect(None).change_context(FocusError::Connect)?;
+    let root = connection.setup().roots[screen].root;
+    let atoms = Atoms::new(&connection).ok_or(FocusError::Connect)?;
+
+    watch_properties(&connection, root).ok_or(FocusError::Connect)?;
+
+    thread::spawn(move || {
+        let mut active = active_window(&connection, root, &atoms);
+        if let Some(window) = active {
+            watch_properties(&connection, window);
+            let _ = tx.send(describe(&connection, window, &atoms));
+        }
+
+        while let Ok(event) = connection.wait_for_event() {
+            let Event::PropertyNotify(event) = event else {
+                continue;
+            };
+
+            if event.window == root && event.atom == atoms.active_window {
+                active = active_window(&connection, root, &atoms);
+                if let Some(window) = active {
+                    watch_properties(&connection, window);
+                }
+            } else if Some(event.window) != active || ![atoms.name, atoms.state, AtomEnum::WM_NAME.into()].contains(&event.atom) {
+                continue;
+            }
+
+            let window = active.map(|window| describe(&connection, window, &atoms)).unwrap_or_default();
+            if tx.send(window).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn watch_properties(connection: &RustConnection, window: Window) -> Option<()> {
+    let attributes = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+    connection.change_window_attributes(window, &attributes).ok()?;
+    connection.flush().ok()
+}
+
+fn active_window(connection: &RustConnection, root: Window, atoms: &Atoms) -> Option<Window> {
+    let reply = connection.get_property(false, root, atoms.active_window, AtomEnum::WINDOW, 0, 1).ok()?.reply().ok()?;
+    let window = reply.value32()?.next();
+    window.filter(|window| *window != 0)
+}
+
+fn describe(connection: &RustConnection, window: Window, atoms: &Atoms) -> FocusedWindow {
+    let property = |property: Atom, kind: Atom| connection.get_property(false, window, property, kind, 0, u32::MAX).ok()?.reply().ok();
+
+    // The instance name comes first, then the class
+    let class = property(AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())
+        .and_then(|reply| reply.value.split(|byte| *byte == 0).nth(1).map(|class| String::from_utf8_lossy(class).into_owned()))
+        .unwrap_or_default();
+
+    let title = property(atoms.name, atoms.utf8_string)
+        .filter(|reply| !reply.value.is_empty())
+        .or_else(|| property(AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))
+        .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+        .unwrap_or_default();
+
+    let process = property(atoms.pid, AtomEnum::CARDINAL.into())
+        .and_then(|reply| reply.value32()?.next())
+        .map(process_name)
+        .unwrap_or_default();
+
+    let fullscreen = property(atoms.state, AtomEnum::ATOM.into()).is_some_and(|reply| reply.value32().is_some_and(|mut states| states.any(|state| state == atoms.fullscreen)));
+
+    FocusedWindow { class, title, process, fullscreen }
+}
@@ -0,0 +1,117 @@
+use std::{mem, time::Duration};
+
+use crossbeam_channel::Receiver;
+use eframe::egui::{self, ComboBox};
+use strum::IntoEnumIterator;
+
+use crate::{
+    enums::Action,
+    hotkeys::{Binding, HotkeySettings},
+    input::{self, Key, KeyEvent},
+    manager::{custom_effect::CustomEffect, profile::Profile},
+};
+
+/// Lets the user bind actions to key combinations, recorded by pressing them
+#[derive(Default)]
+pub struct HotkeyEditor {
+    /// The binding being recorded, the keys pressed for it so far and where the presses come from
+    recording: Option<(usize, Vec<Key>, Receiver<KeyEvent>)>,
+}
+
+impl HotkeyEditor {
+    pub fn show(&mut self, ui: &mut egui::Ui, settings: &mut HotkeySettings, profiles: &[Profile], effects: &[CustomEffect]) {
+        self.record(ui, settings);
+
+        let profile_names: Vec<&str> = profiles.iter().filter_map(|profile| profile.name.as_deref()).collect();
+        let effect_names: Vec<&str> = effects.iter().filter_map(|effect| effect.name.as_deref()).collect();
+
+        let mut removed = None;
+        for (i, binding) in settings.bindings.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let recording = self.recording.as_ref().filter(|(recording, ..)| *recording == i);
+                let text = match recording {
+                    Some((_, keys, _)) if keys.is_empty() => "Press keys...".to_string(),
+                    Some((_, keys, _)) => keys.iter().map(ToString::to_string).collect::<Vec<_>>().join(" + "),
+                    None if binding.keys.is_empty() => "Unset".to_string(),
+                    None => binding.keys.join(" + "),
+                };
+                if ui.selectable_label(recording.is_some(), text).clicked() {
+                    self.recording = if recording.is_some() { None } else { Some((i, Vec::new(), input::subscribe())) };
+                }
+
+                ComboBox::from_id_salt(("hotkey_action", i))
+                    .selected_text({
+                        let text: &'static str = (&binding.action).into();
+                        text
+                    })
+                    .show_ui(ui, |ui| {
+                        for action in Action::iter() {
+                            let text: &'static str = (&action).into();
+                            let selected = mem::discriminant(&binding.action) == mem::discriminant(&action);
+                            if ui.selectable_label(selected, text).clicked() && !selected {
+                                binding.action = action;
+                            }
+                        }
+                    });
+
+                match &mut binding.action {
+                    Action::Profile { name } => pick_name(ui, ("hotkey_profile", i), name, &profile_names),
+                    Action::CustomEffect { name } => pick_name(ui, ("hotkey_effect", i), name, &effect_names),
+                    _ => {}
+                }
+
+                if ui.small_button("🗑").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = removed {
+            settings.bindings.remove(i);
+            self.recording = None;
+        }
+
+        if ui.button("+").clicked() {
+            settings.bindings.push(Binding::default());
+        }
+    }
+
+    /// Collect the keys pressed for the binding being recorded, until they are all let go
+    fn record(&mut self, ui: &egui::Ui, settings: &mut HotkeySettings) {
+        let Some((i, keys, events)) = &mut self.recording else {
+            return;
+        };
+
+        let mut done = false;
+        for event in events.try_iter() {
+            match event {
+                KeyEvent::Press(key) if !keys.contains(&key) => keys.push(key),
+                KeyEvent::Release(_) if !keys.is_empty() => done = true,
+                _ => {}
+            }
+        }
+
+        if done {
+            if let Some(binding) = settings.bindings.get_mut(*i) {
+                binding.keys = keys.iter().map(ToString::to_string).collect();
+            }
+            self.recording = None;
+        } else {
+            // Presses don't go through egui, so keep checking for them
+            ui.ctx().request_repaint_after(Duration::from_millis(50));
+        }
+    }
+
+    /// Stop listening for presses, such as once the menu is closed
+    pub fn stop(&mut self) {
+        self.recording = None;
+    }
+}
+
+fn pick_name(ui: &mut egui::Ui, id_salt: impl std::hash::Hash, picked: &mut String, names: &[&str]) {
+    ComboBox::from_id_salt(id_salt).selected_text(picked.as_str()).show_ui(ui, |ui| {
+        for name in names {
+            ui.selectable_value(picked, name.to_string(), *name);
+        }
+    });
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crossbeam_channel::Receiver;
+use eframe::egui::{self, ScrollArea};
+
+use crate::{
+    input::{self, KeyEvent},
+    manager::key_zones::{self, KeyZoneSettings},
+};
+
+/// Lets the user pick a keyboard and layout, and move single keys by pressing them
+#[derive(Default)]
+pub struct KeyZoneEditor {
+    /// The zone pressed keys are moved to, and where the presses come from
+    assigning: Option<(usize, Receiver<KeyEvent>)>,
+}
+
+impl KeyZoneEditor {
+    pub fn show(&mut self, ui: &mut egui::Ui, settings: &mut KeyZoneSettings) {
+        let mut changed = false;
+
+        ui.label("Keyboard");
+        for (id, model) in key_zones::models() {
+            if ui.radio(settings.model == *id, &model.name).clicked() {
+                settings.model = id.to_string();
+                changed = true;
+            }
+        }
+        ui.separator();
+
+        ui.label("Layout").on_hover_text("Only matters where keys are reported by what they type, such as on Windows");
+        for (id, layout) in key_zones::layouts() {
+            if ui.radio(settings.layout == *id, &layout.name).clicked() {
+                settings.layout = id.to_string();
+                changed = true;
+            }
+        }
+        ui.separator();
+
+        ui.label("Press keys to move them to a zone");
+        ui.horizontal(|ui| {
+            for zone in 0..4 {
+                let selected = self.assigning.as_ref().is_some_and(|(assigning, _)| *assigning == zone);
+                if ui.selectable_label(selected, format!("Zone {}", zone + 1)).clicked() {
+                    self.assigning = if selected { None } else { Some((zone, input::subscribe())) };
+                }
+            }
+        });
+
+        if let Some((zone, events)) = &self.assigning {
+            for event in events.try_iter() {
+                if let KeyEvent::Press(key) = event {
+                    settings.assign(key, *zone);
+                    changed = true;
+                }
+            }
+
+            // Presses don't go through egui, so keep checking for them
+            ui.ctx().request_repaint_after(Duration::from_millis(50));
+        }
+
+        ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+            let mut removed = None;
+            for (key, zone) in &settings.overrides {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{key}: Zone {}", zone + 1));
+                    if ui.small_button("🗑").clicked() {
+                        removed = Some(key.clone());
+                    }
+                });
+            }
+
+            if let Some(key) = removed {
+                settings.overrides.remove(&key);
+                changed = true;
+            }
+        });
+
+        if !settings.overrides.is_empty() && ui.button("Reset").clicked() {
+            settings.overrides.clear();
+            changed = true;
+        }
+
+        if changed {
+            key_zones::configure(settings);
+        }
+    }
+
+    /// Stop listening for presses, such as once the menu is closed
+    pub fn stop(&mut self) {
+        self.assigning = None;
+    }
+}
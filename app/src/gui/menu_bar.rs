@@ -1,25 +1,43 @@
 use crossbeam_channel::Sender;
 use eframe::{
-    egui::{self, Context},
+    egui::{self, Button, Context, DragValue, Slider, TextEdit},
     epaint::Vec2,
 };
 use egui_file::FileDialog;
 use egui_notify::Toasts;
 use std::{path::PathBuf, time::Duration};
+use strum::IntoEnumIterator;
 
 use crate::{
     gui::modals,
-    manager::{custom_effect::CustomEffect, profile::Profile},
+    hotkeys::HotkeySettings,
+    ipc::{ApiSettings, MqttSettings},
+    manager::{
+        custom_effect::CustomEffect,
+        idle::IdleSettings,
+        key_zones::KeyZoneSettings,
+        power::{PowerRule, PowerSettings},
+        profile::Profile,
+        session::SessionSettings,
+        show_battery_warning, show_lock_key_overlay,
+        transition::{Easing, Transition},
+    },
     DENY_HIDING,
 };
 
-use super::{GuiMessage, LoadedEffect};
+use super::{hotkey_editor::HotkeyEditor, key_zone_editor::KeyZoneEditor, GuiMessage, LoadedEffect};
 
 pub struct MenuBarState {
     gui_sender: Sender<GuiMessage>,
     load_profile_dialog: FileDialog,
     load_effect_dialog: FileDialog,
     save_profile_dialog: FileDialog,
+    key_zone_editor: KeyZoneEditor,
+    hotkey_editor: HotkeyEditor,
+    /// The HTTP API settings being edited, which only take effect once applied
+    api_draft: Option<ApiSettings>,
+    /// The same for the MQTT settings, so the broker isn't reconnected to on every key press
+    mqtt_draft: Option<MqttSettings>,
 }
 
 impl MenuBarState {
@@ -29,11 +47,36 @@ impl MenuBarState {
             load_profile_dialog: FileDialog::open_file(None).default_size(Vec2::splat(300.0)),
             load_effect_dialog: FileDialog::open_file(None).default_size(Vec2::splat(300.0)),
             save_profile_dialog: FileDialog::save_file(None).default_size(Vec2::splat(300.0)),
+            key_zone_editor: KeyZoneEditor::default(),
+            hotkey_editor: HotkeyEditor::default(),
+            api_draft: None,
+            mqtt_draft: None,
         }
     }
 
-    pub fn show(&mut self, ctx: &Context, ui: &mut egui::Ui, current_profile: &mut Profile, current_effect: &mut LoadedEffect, changed: &mut bool, toasts: &mut Toasts) {
-        self.show_menu(ctx, ui, toasts);
+    #[allow(clippy::too_many_arguments)]
+    pub fn show(
+        &mut self, ctx: &Context, ui: &mut egui::Ui, current_profile: &mut Profile, current_effect: &mut LoadedEffect, transition: &mut Transition, key_zones: &mut KeyZoneSettings,
+        idle: &mut IdleSettings, session: &mut SessionSettings, power: &mut PowerSettings, hotkeys: &mut HotkeySettings, api: &mut ApiSettings, mqtt: &mut MqttSettings, profiles: &[Profile],
+        effects: &[CustomEffect], changed: &mut bool, toasts: &mut Toasts,
+    ) {
+        self.show_menu(
+            ctx,
+            ui,
+            current_profile,
+            transition,
+            key_zones,
+            idle,
+            session,
+            power,
+            hotkeys,
+            api,
+            mqtt,
+            profiles,
+            effects,
+            changed,
+            toasts,
+        );
         self.handle_load_profile(ctx, current_profile, changed, toasts);
         self.handle_save_profile(ctx, current_profile, toasts);
         self.handle_load_effect(ctx, current_effect, changed, toasts);
@@ -102,10 +145,19 @@ impl MenuBarState {
         }
     }
 
-    #[allow(unused_variables)]
-    fn show_menu(&mut self, ctx: &Context, ui: &mut egui::Ui, toasts: &mut Toasts) {
+    #[allow(unused_variables, clippy::too_many_arguments)]
+    fn show_menu(
+        &mut self, ctx: &Context, ui: &mut egui::Ui, current_profile: &mut Profile, transition: &mut Transition, key_zones: &mut KeyZoneSettings, idle: &mut IdleSettings,
+        session: &mut SessionSettings, power: &mut PowerSettings, hotkeys: &mut HotkeySettings, api: &mut ApiSettings, mqtt: &mut MqttSettings, profiles: &[Profile], effects: &[CustomEffect],
+        changed: &mut bool, toasts: &mut Toasts,
+    ) {
         use egui::menu;
 
+        let mut editing_key_zones = false;
+        let mut editing_hotkeys = false;
+        let mut editing_api = false;
+        let mut editing_mqtt = false;
+
         menu::bar(ui, |ui| {
             ui.menu_button("Profile", |ui| {
                 if ui.button("Open").clicked() {
@@ -122,6 +174,78 @@ impl MenuBarState {
                 }
             });
 
+            ui.menu_button("Settings", |ui| {
+                ui.menu_button("Transition", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(Slider::new(&mut transition.duration_ms, 0..=Transition::MAX_DURATION_MS).suffix(" ms"));
+                        ui.label("Duration");
+                    });
+                    ui.separator();
+                    for easing in Easing::iter() {
+                        let text: &'static str = easing.into();
+                        ui.radio_value(&mut transition.easing, easing, text);
+                    }
+                });
+                ui.menu_button("Idle", |ui| {
+                    ui.checkbox(&mut idle.enabled, "Dim when idle");
+                    ui.add_enabled_ui(idle.enabled, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(Slider::new(&mut idle.timeout_secs, 5..=IdleSettings::MAX_TIMEOUT_SECS).suffix(" s"));
+                            ui.label("After");
+                        });
+
+                        let mut on_battery = idle.battery_timeout_secs.is_some();
+                        if ui.checkbox(&mut on_battery, "Sooner on battery").changed() {
+                            idle.battery_timeout_secs = on_battery.then_some((idle.timeout_secs / 2).max(5));
+                        }
+                        if let Some(battery_timeout_secs) = &mut idle.battery_timeout_secs {
+                            ui.horizontal(|ui| {
+                                ui.add(Slider::new(battery_timeout_secs, 5..=IdleSettings::MAX_TIMEOUT_SECS).suffix(" s"));
+                                ui.label("On battery");
+                            });
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.add(Slider::new(&mut idle.level, 0..=100).suffix("%"));
+                            ui.label("Brightness").on_hover_text("0% turns the lights off");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add(Slider::new(&mut idle.restore_ms, 0..=IdleSettings::MAX_RESTORE_MS).suffix(" ms"));
+                            ui.label("Fade back in");
+                        });
+                    });
+                });
+                #[cfg(target_os = "linux")]
+                ui.menu_button("Session", |ui| show_session_settings(ui, session, profiles));
+                #[cfg(target_os = "linux")]
+                ui.menu_button("Power", |ui| show_power_settings(ui, power, profiles));
+                ui.menu_button("Overlays", |ui| {
+                    ui.label("Battery warning");
+                    show_battery_warning(ui, &mut current_profile.battery, changed);
+                    ui.separator();
+                    ui.label("Lock keys");
+                    show_lock_key_overlay(ui, &mut current_profile.lock_keys, changed);
+                });
+                ui.menu_button("Key zones", |ui| {
+                    editing_key_zones = true;
+                    self.key_zone_editor.show(ui, key_zones);
+                });
+                ui.menu_button("Hotkeys", |ui| {
+                    editing_hotkeys = true;
+                    self.hotkey_editor.show(ui, hotkeys, profiles, effects);
+                });
+                #[cfg(target_os = "linux")]
+                ui.menu_button("HTTP API", |ui| {
+                    editing_api = true;
+                    show_api_settings(ui, api, &mut self.api_draft);
+                });
+                #[cfg(target_os = "linux")]
+                ui.menu_button("Home Assistant", |ui| {
+                    editing_mqtt = true;
+                    show_mqtt_settings(ui, mqtt, &mut self.mqtt_draft);
+                });
+            });
+
             let about_modal = modals::about(ctx);
             if ui.button("About").clicked() {
                 about_modal.open();
@@ -149,5 +273,146 @@ impl MenuBarState {
                 });
             }
         });
+
+        if !editing_key_zones {
+            self.key_zone_editor.stop();
+        }
+
+        if !editing_hotkeys {
+            self.hotkey_editor.stop();
+        }
+
+        if !editing_api {
+            self.api_draft = None;
+        }
+
+        if !editing_mqtt {
+            self.mqtt_draft = None;
+        }
+    }
+}
+
+/// How to reach the MQTT broker Home Assistant listens to, which is only connected to again once applied
+#[cfg(target_os = "linux")]
+fn show_mqtt_settings(ui: &mut egui::Ui, mqtt: &mut MqttSettings, draft: &mut Option<MqttSettings>) {
+    let edited = draft.get_or_insert_with(|| mqtt.clone());
+
+    ui.checkbox(&mut edited.enabled, "Show up in Home Assistant");
+    ui.add_enabled_ui(edited.enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.host).desired_width(150.0));
+            ui.label("Broker");
+        });
+        ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut edited.port));
+            ui.label("Port");
+        });
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.username).desired_width(150.0));
+            ui.label("Username");
+        });
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.password).password(true).desired_width(150.0));
+            ui.label("Password").on_hover_text("Sent unencrypted, as connecting over TLS isn't supported");
+        });
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.discovery_prefix).desired_width(150.0));
+            ui.label("Discovery prefix");
+        });
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.base_topic).desired_width(150.0));
+            ui.label("Topic");
+        });
+        ui.checkbox(&mut edited.retain, "Keep the state on the broker");
+    });
+    ui.separator();
+
+    if ui.add_enabled(edited != mqtt, Button::new("Apply")).clicked() {
+        *mqtt = edited.clone();
+    }
+}
+
+/// What to do on suspend, when the lid is closed and while the screen is locked
+#[cfg(target_os = "linux")]
+fn show_session_settings(ui: &mut egui::Ui, session: &mut SessionSettings, profiles: &[Profile]) {
+    ui.checkbox(&mut session.off_on_sleep, "Turn off when suspending");
+    ui.checkbox(&mut session.off_on_lid_closed, "Turn off while the lid is closed");
+    ui.separator();
+
+    ui.menu_button("While locked", |ui| {
+        pick_profile(ui, &mut session.lock_profile, "Keep the current profile", profiles);
+    });
+    ui.menu_button("Once unlocked", |ui| {
+        pick_profile(ui, &mut session.unlock_profile, "Go back to what was shown", profiles);
+    });
+}
+
+/// Where the HTTP API listens, which is only restarted once applied rather than on every key press
+#[cfg(target_os = "linux")]
+fn show_api_settings(ui: &mut egui::Ui, api: &mut ApiSettings, draft: &mut Option<ApiSettings>) {
+    let edited = draft.get_or_insert_with(|| api.clone());
+
+    ui.checkbox(&mut edited.enabled, "Serve the HTTP API");
+    ui.add_enabled_ui(edited.enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.address).desired_width(150.0));
+            ui.label("Address");
+        });
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut edited.token).password(true).desired_width(150.0));
+            ui.label("Token").on_hover_text("Needed on every request when set, and to listen on addresses other machines can reach");
+        });
+    });
+    ui.separator();
+
+    if ui.add_enabled(edited != api, Button::new("Apply")).clicked() {
+        *api = edited.clone();
+    }
+}
+
+/// What changes while running on battery, and once it runs low
+#[cfg(target_os = "linux")]
+fn show_power_settings(ui: &mut egui::Ui, power: &mut PowerSettings, profiles: &[Profile]) {
+    ui.checkbox(&mut power.enabled, "Follow the power source");
+    ui.add_enabled_ui(power.enabled, |ui| {
+        ui.menu_button("On battery", |ui| show_power_rule(ui, &mut power.battery, profiles));
+        ui.menu_button("Low battery", |ui| {
+            ui.horizontal(|ui| {
+                ui.add(Slider::new(&mut power.saver_threshold, 5..=50).suffix("%"));
+                ui.label("Under");
+            });
+            ui.separator();
+            show_power_rule(ui, &mut power.saver, profiles);
+        });
+        ui.menu_button("Once plugged in", |ui| {
+            pick_profile(ui, &mut power.ac_profile, "Go back to what was shown", profiles);
+        });
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn show_power_rule(ui: &mut egui::Ui, rule: &mut PowerRule, profiles: &[Profile]) {
+    ui.horizontal(|ui| {
+        ui.add(Slider::new(&mut rule.brightness, PowerSettings::MIN_BRIGHTNESS..=100).suffix("%"));
+        ui.label("Brightness at most");
+    });
+    ui.checkbox(&mut rule.pause_ambient, "Stop capturing the screen for Ambient Light");
+    ui.separator();
+    pick_profile(ui, &mut rule.profile, "Keep the current profile", profiles);
+}
+
+/// Pick a copy of one of the saved profiles, or none at all
+#[cfg(target_os = "linux")]
+fn pick_profile(ui: &mut egui::Ui, picked: &mut Option<Profile>, none_text: &str, profiles: &[Profile]) {
+    if ui.radio(picked.is_none(), none_text).clicked() {
+        *picked = None;
+    }
+
+    for profile in profiles {
+        let name = profile.name.as_deref().unwrap_or("Unnamed");
+        let selected = picked.as_ref().is_some_and(|picked| picked.name == profile.name);
+        if ui.radio(selected, name).clicked() {
+            *picked = Some(profile.clone());
+        }
     }
 }
@@ -2,34 +2,52 @@ use std::{process, thread, time::Duration};
 
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
 };
 
-use device_query::{DeviceQuery, Keycode};
 #[cfg(debug_assertions)]
 use eframe::egui::style::DebugOptions;
 use eframe::{
-    egui::{CentralPanel, Context, CornerRadius, Frame, Layout, ScrollArea, Style, TopBottomPanel, ViewportCommand},
+    egui::{CentralPanel, Context, CornerRadius, Frame, Layout, Rect, ScrollArea, Sense, Style, TopBottomPanel, ViewportCommand},
     emath::Align,
     epaint::{Color32, Vec2},
     CreationContext,
 };
 
+use chrono::NaiveDateTime;
 use egui_notify::Toasts;
 use strum::IntoEnumIterator;
 use tray_icon::menu::MenuEvent;
 
 use crate::{
     cli::OutputType,
-    enums::Effects,
-    manager::{self, custom_effect::CustomEffect, profile::Profile, show_effect_ui, EffectManager, ManagerCreationError},
+    controller::Controller,
+    enums::{Action, Effects},
+    focus::{self, FocusedWindow},
+    hotkeys::{self, HotkeySettings},
+    ipc::{ApiSettings, Call, MqttSettings, Server, Status},
+    manager::{
+        self,
+        custom_effect::CustomEffect,
+        idle::IdleSettings,
+        key_zones::{self, KeyZoneSettings},
+        power::PowerSettings,
+        profile::Profile,
+        session::SessionSettings,
+        show_effect_ui,
+        transition::Transition,
+        EffectManager, ManagerCreationError,
+    },
     persist::Settings,
+    schedule::{self, ScheduleSettings},
     tray::{QUIT_ID, SHOW_ID},
     DENY_HIDING,
 };
 
 use self::{menu_bar::MenuBarState, saved_items::SavedItems, style::Theme};
 
+mod hotkey_editor;
+mod key_zone_editor;
 mod menu_bar;
 mod modals;
 mod saved_items;
@@ -47,16 +65,40 @@ pub struct App {
     state_changed: bool,
     loaded_effect: LoadedEffect,
     current_profile: Profile,
+    transition: Transition,
+    key_zones: KeyZoneSettings,
+    idle: IdleSettings,
+    session: SessionSettings,
+    power: PowerSettings,
+    hotkeys: HotkeySettings,
+    /// The bindings the hotkey listener goes by
+    active_hotkeys: Arc<Mutex<HotkeySettings>>,
+    lights_on: bool,
+    paused: bool,
+    api: ApiSettings,
+    mqtt: MqttSettings,
+    /// Takes requests from other instances, while this one owns the keyboard
+    ipc: Option<Server>,
+    /// The last status subscribers were told about
+    published: Option<Status>,
 
     menu_bar: MenuBarState,
     saved_items: SavedItems,
+    /// What was shown before a focus rule switched profiles
+    before_focus: Option<Profile>,
+    following_focus: bool,
+    /// Up to when the schedule has been gone through
+    schedule_checked: NaiveDateTime,
     global_rgb: [u8; 3],
     theme: Theme,
     toasts: Toasts,
 }
 
 pub enum GuiMessage {
-    CycleProfiles,
+    Action(Action),
+    Focus(FocusedWindow),
+    ScheduleTick,
+    Ipc(Call),
     Quit,
 }
 
@@ -117,7 +159,30 @@ impl App {
         let manager = manager_result.ok();
 
         let settings: Settings = Settings::load();
-        let Settings { current_profile, profiles, effects } = settings;
+        let Settings {
+            current_profile,
+            profiles,
+            effects,
+            transition,
+            key_zones,
+            idle,
+            session,
+            power,
+            hotkeys,
+            focus,
+            schedule,
+            api,
+            mqtt,
+        } = settings;
+
+        key_zones::configure(&key_zones);
+
+        if let Some(manager) = &manager {
+            manager.set_transition(transition);
+            manager.set_idle(idle);
+            manager.set_session(session.clone());
+            manager.set_power(power.clone());
+        }
 
         let gui_tx_c = gui_tx.clone();
         // Default app state
@@ -134,9 +199,25 @@ impl App {
             state_changed: true,
             loaded_effect: LoadedEffect::default(),
             current_profile,
+            transition,
+            key_zones,
+            idle,
+            session,
+            power,
+            active_hotkeys: Arc::new(Mutex::new(hotkeys.clone())),
+            hotkeys,
+            lights_on: true,
+            paused: false,
+            api,
+            mqtt,
+            ipc: None,
+            published: None,
 
             menu_bar: MenuBarState::new(gui_tx_c),
-            saved_items: SavedItems::new(profiles, effects),
+            saved_items: SavedItems::new(profiles, effects, focus, schedule),
+            before_focus: None,
+            following_focus: false,
+            schedule_checked: schedule::now(),
             global_rgb: [0; 3],
             theme: Theme::default(),
             toasts: Toasts::default(),
@@ -144,16 +225,19 @@ impl App {
 
         // Update the state according to the option chosen by the user
         match output {
-            OutputType::Profile(profile) => app.current_profile = profile,
+            OutputType::Profile(profile) => app.current_profile = *profile,
             OutputType::Custom(effect) => app.loaded_effect = LoadedEffect::queued(effect),
-            OutputType::NoArgs => {}
+            OutputType::NoArgs => {
+                // Catch up on whatever entry started while the program wasn't running
+                app.catch_up_on_schedule(app.schedule_checked);
+            }
             OutputType::Exit => unreachable!("Exiting the app supersedes starting the GUI"),
         }
 
         app
     }
 
-    pub fn init(self, cc: &CreationContext<'_>) -> Self {
+    pub fn init(mut self, cc: &CreationContext<'_>) -> Self {
         if !*DENY_HIDING {
             cc.egui_ctx.send_viewport_cmd(ViewportCommand::Visible(self.visible.load(Ordering::SeqCst)));
         }
@@ -181,28 +265,52 @@ impl App {
         let ctx = cc.egui_ctx.clone();
         let gui_tx_c = self.gui_tx.clone();
         if self.manager.is_some() {
+            let actions = hotkeys::listen(self.active_hotkeys.clone());
             thread::spawn(move || {
-                let state = device_query::DeviceState::new();
-                let mut lock_switching = false;
-
-                loop {
-                    let keys = state.get_keys();
+                for action in actions {
+                    if gui_tx_c.send(GuiMessage::Action(action)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
 
-                    if keys.contains(&Keycode::LMeta) && keys.contains(&Keycode::RAlt) {
-                        if !lock_switching {
-                            let _ = gui_tx_c.send(GuiMessage::CycleProfiles);
-                            ctx.request_repaint();
-                            lock_switching = true;
+            match Server::start() {
+                Ok((mut server, calls)) => {
+                    if let Some(manager) = &self.manager {
+                        if let Err(err) = server.serve_api(&self.api, manager.previewer()) {
+                            eprintln!("{err:?}");
+                            self.toasts.error("Could not start the HTTP API.").duration(Some(Duration::from_millis(5000))).closable(true);
                         }
-                    } else {
-                        lock_switching = false;
                     }
-
-                    thread::sleep(Duration::from_millis(50));
+                    server.serve_mqtt(&self.mqtt);
+
+                    let ctx = cc.egui_ctx.clone();
+                    let gui_tx_c = self.gui_tx.clone();
+                    thread::spawn(move || {
+                        for call in calls {
+                            if gui_tx_c.send(GuiMessage::Ipc(call)).is_err() {
+                                break;
+                            }
+                            ctx.request_repaint();
+                        }
+                    });
+                    self.ipc = Some(server);
                 }
-            });
+                Err(err) => eprintln!("{err:?}"),
+            }
         }
 
+        let ctx = cc.egui_ctx.clone();
+        let gui_tx_c = self.gui_tx.clone();
+        thread::spawn(move || loop {
+            thread::sleep(schedule::CHECK_INTERVAL);
+            if gui_tx_c.send(GuiMessage::ScheduleTick).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        });
+
         self.configure_style(&cc.egui_ctx);
 
         self
@@ -211,9 +319,17 @@ impl App {
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
-        if let Ok(message) = self.gui_rx.try_recv() {
+        // Hotkeys, other instances, focus changes and the schedule all share the queue, so everything waiting is handled
+        while let Ok(message) = self.gui_rx.try_recv() {
             match message {
-                GuiMessage::CycleProfiles => self.cycle_profiles(),
+                GuiMessage::Action(action) => {
+                    self.run_action(action);
+                }
+                GuiMessage::Focus(window) => self.apply_focus_rules(&window),
+                GuiMessage::ScheduleTick => self.schedule_checked = self.follow_schedule(self.schedule_checked),
+                GuiMessage::Ipc((request, reply)) => {
+                    let _ = reply.send(self.handle_request(request));
+                }
                 GuiMessage::Quit => self.exit_app(),
             }
         }
@@ -236,11 +352,77 @@ impl eframe::App for App {
             self.exit_app();
         }
 
+        let transition = self.transition;
+        let idle = self.idle;
+        let session = self.session.clone();
+        let power = self.power.clone();
+        let hotkeys = self.hotkeys.clone();
+        let api = self.api.clone();
+        let mqtt = self.mqtt.clone();
         TopBottomPanel::top("top-panel").show(ctx, |ui| {
-            self.menu_bar
-                .show(ctx, ui, &mut self.current_profile, &mut self.loaded_effect, &mut self.state_changed, &mut self.toasts);
+            self.menu_bar.show(
+                ctx,
+                ui,
+                &mut self.current_profile,
+                &mut self.loaded_effect,
+                &mut self.transition,
+                &mut self.key_zones,
+                &mut self.idle,
+                &mut self.session,
+                &mut self.power,
+                &mut self.hotkeys,
+                &mut self.api,
+                &mut self.mqtt,
+                &self.saved_items.profiles,
+                &self.saved_items.custom_effects,
+                &mut self.state_changed,
+                &mut self.toasts,
+            );
         });
 
+        if transition != self.transition {
+            if let Some(manager) = &self.manager {
+                manager.set_transition(self.transition);
+            }
+        }
+
+        if idle != self.idle {
+            if let Some(manager) = &self.manager {
+                manager.set_idle(self.idle);
+            }
+        }
+
+        if session != self.session {
+            if let Some(manager) = &self.manager {
+                manager.set_session(self.session.clone());
+            }
+        }
+
+        if power != self.power {
+            if let Some(manager) = &self.manager {
+                manager.set_power(self.power.clone());
+            }
+        }
+
+        if hotkeys != self.hotkeys {
+            *self.active_hotkeys.lock().unwrap() = self.hotkeys.clone();
+        }
+
+        if api != self.api {
+            if let (Some(server), Some(manager)) = (&mut self.ipc, &self.manager) {
+                if let Err(err) = server.serve_api(&self.api, manager.previewer()) {
+                    eprintln!("{err:?}");
+                    self.toasts.error("Could not start the HTTP API.").duration(Some(Duration::from_millis(5000))).closable(true);
+                }
+            }
+        }
+
+        if mqtt != self.mqtt {
+            if let Some(server) = &mut self.ipc {
+                server.serve_mqtt(&self.mqtt);
+            }
+        }
+
         CentralPanel::default()
             .frame(Frame::new().inner_margin(self.theme.spacing.large).fill(Color32::from_gray(26)))
             .show(ctx, |ui| {
@@ -248,17 +430,43 @@ impl eframe::App for App {
                 self.show_ui_elements(ctx, ui);
             });
 
+        if self.saved_items.focus.enabled && !self.following_focus {
+            self.follow_focus(ctx);
+        }
+
         if self.state_changed {
             self.update_state();
         }
 
+        self.publish_status();
+
         self.handle_close_request(ctx);
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        let SavedItems { profiles, custom_effects, .. } = self.saved_items.clone();
-
-        let mut settings = Settings::new(profiles, custom_effects, self.current_profile.clone());
+        let SavedItems {
+            profiles,
+            custom_effects,
+            focus,
+            schedule,
+            ..
+        } = self.saved_items.clone();
+
+        let mut settings = Settings {
+            profiles,
+            effects: custom_effects,
+            current_profile: self.current_profile.clone(),
+            transition: self.transition,
+            key_zones: self.key_zones.clone(),
+            idle: self.idle,
+            session: self.session.clone(),
+            power: self.power.clone(),
+            hotkeys: self.hotkeys.clone(),
+            focus,
+            schedule,
+            api: self.api.clone(),
+            mqtt: self.mqtt.clone(),
+        };
 
         settings.save();
 
@@ -302,19 +510,67 @@ impl App {
         process::exit(0);
     }
 
-    fn cycle_profiles(&mut self) {
-        let len = self.saved_items.profiles.len();
+    /// Tell subscribers about any change
+    fn publish_status(&mut self) {
+        let Some(server) = &self.ipc else {
+            return;
+        };
 
-        let current_profile_name = &self.current_profile.name;
+        let status = self.status();
+        if self.published.as_ref() != Some(&status) {
+            server.broadcast(&status);
+            self.published = Some(status);
+        }
+    }
 
-        if let Some((i, _)) = self.saved_items.profiles.iter().enumerate().find(|(_, profile)| &profile.name == current_profile_name) {
-            if i == len - 1 && len > 0 {
-                self.current_profile = self.saved_items.profiles[0].clone();
-            } else {
-                self.current_profile = self.saved_items.profiles[i + 1].clone();
+    /// Start forwarding the focused window to the GUI, which only has to happen once
+    fn follow_focus(&mut self, ctx: &Context) {
+        self.following_focus = true;
+
+        match focus::watch() {
+            Ok(windows) => {
+                let ctx = ctx.clone();
+                let gui_tx = self.gui_tx.clone();
+                thread::spawn(move || {
+                    for window in windows {
+                        if gui_tx.send(GuiMessage::Focus(window)).is_err() {
+                            break;
+                        }
+                        ctx.request_repaint();
+                    }
+                });
+            }
+            Err(err) => {
+                eprintln!("{err:?}");
+                self.toasts.error("Could not follow the focused window.").duration(Some(Duration::from_millis(5000))).closable(true);
             }
+        }
+    }
 
-            self.state_changed = true;
+    /// Switch to the profile the focused window asks for, or back to what was shown before
+    fn apply_focus_rules(&mut self, window: &FocusedWindow) {
+        let focus = &self.saved_items.focus;
+        if !focus.enabled {
+            return;
+        }
+
+        let profile = match focus.profile_for(window) {
+            Some(name) => {
+                self.before_focus.get_or_insert_with(|| self.current_profile.clone());
+                focus::find_profile(&self.saved_items.profiles, name).cloned()
+            }
+            None => {
+                let before_focus = self.before_focus.take();
+                focus
+                    .fallback
+                    .as_deref()
+                    .and_then(|name| focus::find_profile(&self.saved_items.profiles, name).cloned())
+                    .or(before_focus)
+            }
+        };
+
+        if let Some(profile) = profile.filter(|profile| *profile != self.current_profile) {
+            self.show_profile(profile);
         }
     }
 
@@ -353,6 +609,8 @@ impl App {
             });
 
             ui.vertical_centered_justified(|ui| {
+                self.show_preview(ui);
+
                 if self.loaded_effect.is_playing() && ui.button("Stop custom effect").clicked() {
                     self.loaded_effect.state = State::None;
                     self.state_changed = true;
@@ -381,6 +639,33 @@ impl App {
         });
     }
 
+    /// Mirror what the keyboard is showing
+    fn show_preview(&self, ui: &mut eframe::egui::Ui) {
+        let Some(manager) = &self.manager else {
+            return;
+        };
+
+        let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 12.0), Sense::hover());
+        let zone_size = Vec2::new(rect.width() / 4.0, rect.height());
+
+        for (i, rgb) in manager.preview().chunks_exact(3).enumerate() {
+            let zone_rect = Rect::from_min_size(rect.min + Vec2::new(zone_size.x * i as f32, 0.0), zone_size).shrink2(Vec2::new(2.0, 0.0));
+            ui.painter().rect_filled(zone_rect, CornerRadius::same(3), Color32::from_rgb(rgb[0], rgb[1], rgb[2]));
+        }
+
+        if let Some(canvas) = manager.preview_canvas() {
+            let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 4.0), Sense::hover());
+            let pixel_size = Vec2::new(rect.width() / canvas.width() as f32, rect.height());
+
+            for (i, rgb) in canvas.to_rgb().iter().enumerate() {
+                let pixel_rect = Rect::from_min_size(rect.min + Vec2::new(pixel_size.x * i as f32, 0.0), pixel_size);
+                ui.painter().rect_filled(pixel_rect, CornerRadius::ZERO, Color32::from_rgb(rgb[0], rgb[1], rgb[2]));
+            }
+        }
+
+        ui.ctx().request_repaint_after(Duration::from_millis(33));
+    }
+
     fn show_effect_ui(&mut self, ui: &mut eframe::egui::Ui) {
         ui.add_enabled_ui(self.loaded_effect.is_none(), |ui| {
             show_effect_ui(ui, &mut self.current_profile, &mut self.state_changed, &self.theme);
@@ -400,6 +685,7 @@ impl App {
         }
 
         self.state_changed = false;
+        self.paused = false;
     }
 
     fn handle_close_request(&mut self, ctx: &Context) {
@@ -413,3 +699,67 @@ impl App {
         }
     }
 }
+
+impl Controller for App {
+    fn profiles(&self) -> &[Profile] {
+        &self.saved_items.profiles
+    }
+
+    fn profiles_mut(&mut self) -> &mut Vec<Profile> {
+        &mut self.saved_items.profiles
+    }
+
+    fn custom_effects(&self) -> &[CustomEffect] {
+        &self.saved_items.custom_effects
+    }
+
+    fn custom_effects_mut(&mut self) -> &mut Vec<CustomEffect> {
+        &mut self.saved_items.custom_effects
+    }
+
+    fn schedule(&self) -> &ScheduleSettings {
+        &self.saved_items.schedule
+    }
+
+    fn current_profile(&self) -> &Profile {
+        &self.current_profile
+    }
+
+    fn custom_effect(&self) -> Option<&CustomEffect> {
+        (!self.loaded_effect.is_none()).then_some(&self.loaded_effect.effect)
+    }
+
+    fn status(&self) -> Status {
+        Status {
+            profile: self.current_profile.clone(),
+            custom_effect: self.custom_effect().and_then(|effect| effect.name.clone()),
+            lights_on: self.lights_on,
+            paused: self.paused,
+        }
+    }
+
+    fn show_profile(&mut self, profile: Profile) {
+        self.current_profile = profile;
+        self.loaded_effect.state = State::None;
+        self.state_changed = true;
+    }
+
+    fn play(&mut self, effect: CustomEffect) {
+        self.loaded_effect = LoadedEffect::queued(effect);
+        self.state_changed = true;
+    }
+
+    fn set_lights(&mut self, on: bool) {
+        self.lights_on = on;
+        if let Some(manager) = &self.manager {
+            manager.set_lights(on);
+        }
+    }
+
+    fn pause(&mut self) {
+        if let Some(manager) = &self.manager {
+            manager.pause();
+            self.paused = true;
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use eframe::egui::{Color32, Context, CornerRadius, Frame, RichText, ScrollArea, Ui};
+use std::mem;
+
+use eframe::egui::{Color32, ComboBox, Context, CornerRadius, DragValue, Frame, RichText, ScrollArea, TextEdit, Ui};
 use egui_modal::Modal;
 
-use crate::manager::{custom_effect::CustomEffect, profile::Profile};
+use strum::IntoEnumIterator;
+
+use crate::{
+    enums::WindowField,
+    focus::{FocusRule, FocusSettings},
+    manager::{custom_effect::CustomEffect, profile::Profile},
+    schedule::{self, ScheduleEntry, ScheduleSettings, Trigger},
+};
 
 use super::{style::SpacingStyle, LoadedEffect, State};
 
@@ -9,6 +18,8 @@ use super::{style::SpacingStyle, LoadedEffect, State};
 pub struct SavedItems {
     pub custom_effects: Vec<CustomEffect>,
     pub profiles: Vec<Profile>,
+    pub focus: FocusSettings,
+    pub schedule: ScheduleSettings,
 
     tab: Tab,
     new_item_name: String,
@@ -18,13 +29,17 @@ pub struct SavedItems {
 pub enum Tab {
     Profiles,
     CustomEffects,
+    Rules,
+    Schedule,
 }
 
 impl SavedItems {
-    pub fn new(profiles: Vec<Profile>, custom_effects: Vec<CustomEffect>) -> Self {
+    pub fn new(profiles: Vec<Profile>, custom_effects: Vec<CustomEffect>, focus: FocusSettings, schedule: ScheduleSettings) -> Self {
         Self {
             profiles,
             custom_effects,
+            focus,
+            schedule,
             tab: Tab::Profiles,
             new_item_name: String::default(),
         }
@@ -95,6 +110,8 @@ impl SavedItems {
     pub fn show_header(&mut self, ctx: &Context, ui: &mut Ui, current_profile: &mut Profile, loaded_effect: &mut LoadedEffect) {
         ui.selectable_value(&mut self.tab, Tab::Profiles, RichText::new("Profiles").heading());
         ui.selectable_value(&mut self.tab, Tab::CustomEffects, RichText::new("Custom Effects").heading());
+        ui.selectable_value(&mut self.tab, Tab::Rules, RichText::new("Rules").heading());
+        ui.selectable_value(&mut self.tab, Tab::Schedule, RichText::new("Schedule").heading());
 
         let profile_modal = self.setup_profile_modal(ctx, current_profile);
         let effect_modal = self.setup_effect_modal(ctx, loaded_effect);
@@ -119,6 +136,24 @@ impl SavedItems {
                     self.custom_effects.retain(|effect| effect != &loaded_effect.effect);
                 }
             }
+            Tab::Rules => {
+                if ui.button("+").clicked() {
+                    self.focus.rules.push(FocusRule {
+                        profile: current_profile.name.clone().unwrap_or_default(),
+                        ..FocusRule::default()
+                    });
+                }
+                ui.checkbox(&mut self.focus.enabled, "Enabled");
+            }
+            Tab::Schedule => {
+                if ui.button("+").clicked() {
+                    self.schedule.entries.push(ScheduleEntry {
+                        profile: current_profile.name.clone(),
+                        ..ScheduleEntry::default()
+                    });
+                }
+                ui.checkbox(&mut self.schedule.enabled, "Enabled");
+            }
         }
     }
 
@@ -170,8 +205,141 @@ impl SavedItems {
                             });
                         }
                     }
+                    Tab::Rules => Self::show_rules(ui, &mut self.focus, &self.profiles),
+                    Tab::Schedule => Self::show_schedule(ui, &mut self.schedule, &self.profiles),
                 });
             });
         });
     }
+
+    /// Which saved profile to switch to when a window is focused
+    fn show_rules(ui: &mut Ui, focus: &mut FocusSettings, profiles: &[Profile]) {
+        let names: Vec<&str> = profiles.iter().filter_map(|profile| profile.name.as_deref()).collect();
+
+        ui.horizontal(|ui| {
+            ComboBox::from_label("Otherwise")
+                .selected_text(focus.fallback.as_deref().unwrap_or("What was shown before"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut focus.fallback, None, "What was shown before");
+                    for name in &names {
+                        ui.selectable_value(&mut focus.fallback, Some(name.to_string()), *name);
+                    }
+                });
+        });
+
+        if focus.rules.is_empty() {
+            ui.centered_and_justified(|ui| ui.label("No rules added"));
+            return;
+        }
+
+        let mut removed = None;
+        for (i, rule) in focus.rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ComboBox::from_id_salt(("rule_field", i))
+                    .width(70.0)
+                    .selected_text({
+                        let text: &'static str = rule.field.into();
+                        text
+                    })
+                    .show_ui(ui, |ui| {
+                        for field in WindowField::iter() {
+                            let text: &'static str = field.into();
+                            ui.selectable_value(&mut rule.field, field, text);
+                        }
+                    });
+
+                ui.add(TextEdit::singleline(&mut rule.pattern).hint_text("contains").desired_width(100.0));
+                ui.checkbox(&mut rule.fullscreen_only, "Fullscreen");
+
+                ComboBox::from_id_salt(("rule_profile", i)).selected_text(&rule.profile).show_ui(ui, |ui| {
+                    for name in &names {
+                        ui.selectable_value(&mut rule.profile, name.to_string(), *name);
+                    }
+                });
+
+                if ui.button("🗑").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = removed {
+            focus.rules.remove(i);
+        }
+    }
+
+    /// Which saved profile to switch to at set times, and when that happens next
+    fn show_schedule(ui: &mut Ui, schedule: &mut ScheduleSettings, profiles: &[Profile]) {
+        let names: Vec<&str> = profiles.iter().filter_map(|profile| profile.name.as_deref()).collect();
+
+        ui.horizontal(|ui| {
+            ui.label("Location");
+            ui.add(DragValue::new(&mut schedule.latitude).range(-90.0..=90.0).speed(0.1).suffix("° N"));
+            ui.add(DragValue::new(&mut schedule.longitude).range(-180.0..=180.0).speed(0.1).suffix("° E"));
+        });
+
+        if let Some((start, entry)) = schedule.next(schedule::now()) {
+            ui.label(format!("Next: {} at {}", entry.profile.as_deref().unwrap_or("Off"), start.format("%a %H:%M")));
+        }
+
+        if schedule.entries.is_empty() {
+            ui.centered_and_justified(|ui| ui.label("No entries added"));
+            return;
+        }
+
+        let mut removed = None;
+        for (i, entry) in schedule.entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 2.0;
+                for (day, name) in entry.days.iter_mut().zip(["M", "T", "W", "T", "F", "S", "S"]) {
+                    ui.toggle_value(day, name);
+                }
+                ui.add_space(6.0);
+
+                ComboBox::from_id_salt(("schedule_trigger", i))
+                    .width(70.0)
+                    .selected_text({
+                        let text: &'static str = entry.trigger.into();
+                        text
+                    })
+                    .show_ui(ui, |ui| {
+                        for trigger in Trigger::iter() {
+                            let text: &'static str = trigger.into();
+                            let selected = mem::discriminant(&entry.trigger) == mem::discriminant(&trigger);
+                            if ui.selectable_label(selected, text).clicked() && !selected {
+                                entry.trigger = trigger;
+                            }
+                        }
+                    });
+
+                match &mut entry.trigger {
+                    Trigger::Time { hour, minute } => {
+                        ui.add(DragValue::new(hour).range(0..=23).custom_formatter(|value, _| format!("{value:02}")));
+                        ui.label(":");
+                        ui.add(DragValue::new(minute).range(0..=59).custom_formatter(|value, _| format!("{value:02}")));
+                    }
+                    Trigger::Sunrise { offset_minutes } | Trigger::Sunset { offset_minutes } => {
+                        ui.add(DragValue::new(offset_minutes).range(-240..=240).suffix(" min"));
+                    }
+                }
+
+                ComboBox::from_id_salt(("schedule_profile", i))
+                    .selected_text(entry.profile.as_deref().unwrap_or("Off"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut entry.profile, None, "Off");
+                        for name in &names {
+                            ui.selectable_value(&mut entry.profile, Some(name.to_string()), *name);
+                        }
+                    });
+
+                if ui.button("🗑").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = removed {
+            schedule.entries.remove(i);
+        }
+    }
 }
@@ -0,0 +1,165 @@
+//! Global shortcuts, read from the keyboard whatever window is focused
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crossbeam_channel::Receiver;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enums::Action,
+    input::{self, Key, KeyEvent, Keycode},
+};
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for HotkeySettings {
+    /// The shortcut there used to be before they could be changed
+    fn default() -> Self {
+        Self {
+            bindings: vec![Binding {
+                keys: vec![Keycode::LMeta.to_string(), Keycode::RAlt.to_string()],
+                action: Action::NextProfile,
+            }],
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Binding {
+    /// The names of the keys that have to be held together
+    pub keys: Vec<String>,
+    pub action: Action,
+}
+
+impl Binding {
+    fn parsed_keys(&self) -> Vec<Key> {
+        self.keys.iter().filter_map(|name| name.parse().ok()).collect()
+    }
+
+    /// Whether pressing `key` completes the combination
+    fn completed_by(&self, key: Key, held: &HashSet<Key>) -> bool {
+        let keys = self.parsed_keys();
+        !keys.is_empty() && keys.contains(&key) && keys.iter().all(|key| held.contains(key))
+    }
+}
+
+/// Receive the action of every binding pressed from now on, until the receiver is dropped
+pub fn listen(settings: Arc<Mutex<HotkeySettings>>) -> Receiver<Action> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+
+    thread::spawn(move || {
+        let mut held = HashSet::new();
+
+        for event in input::subscribe() {
+            let actions = triggered(&settings.lock().unwrap().bindings, &mut held, event);
+
+            for action in actions {
+                if tx.send(action).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+/// The actions of the bindings `event` completes, keeping track of the keys that are `held`
+fn triggered(bindings: &[Binding], held: &mut HashSet<Key>, event: KeyEvent) -> Vec<Action> {
+    match event {
+        KeyEvent::Press(key) => {
+            // Only once per press of the combination, even if a release went missing
+            if !held.insert(key) {
+                return Vec::new();
+            }
+
+            bindings.iter().filter(|binding| binding.completed_by(key, held)).map(|binding| binding.action.clone()).collect()
+        }
+        KeyEvent::Release(key) => {
+            held.remove(&key);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{triggered, Binding};
+    use crate::{
+        enums::Action,
+        input::{
+            Key::{self, Code},
+            KeyEvent::{self, Press, Release},
+            Keycode::{LAlt, LControl, N},
+        },
+    };
+
+    fn fired(bindings: &[Binding], events: &[KeyEvent]) -> Vec<Action> {
+        let mut held = HashSet::new();
+        events.iter().flat_map(|event| triggered(bindings, &mut held, *event)).collect()
+    }
+
+    #[test]
+    fn fires_once_per_press_of_the_combination() {
+        let bindings = [
+            Binding {
+                keys: vec!["LControl".into(), "LAlt".into(), "N".into()],
+                action: Action::NextProfile,
+            },
+            Binding::default(),
+        ];
+
+        let events = [
+            // Only part of the combination
+            Press(Code(LControl)),
+            Press(Code(N)),
+            Release(Code(N)),
+            // The rest of it, then N again as if its release went missing
+            Press(Code(LAlt)),
+            Press(Code(N)),
+            Press(Code(N)),
+            // Pressed again while the modifiers stay held
+            Release(Code(N)),
+            Press(Code(N)),
+            Release(Code(N)),
+            Release(Code(LAlt)),
+            Release(Code(LControl)),
+            // Without the modifiers any more
+            Press(Code(N)),
+            Release(Code(N)),
+        ];
+
+        assert_eq!(fired(&bindings, &events), vec![Action::NextProfile, Action::NextProfile]);
+    }
+
+    #[test]
+    fn unknown_keys_never_complete_a_binding() {
+        let bindings = [
+            Binding {
+                keys: vec!["IntlBackslash".into()],
+                action: Action::NextProfile,
+            },
+            Binding {
+                keys: vec!["NotAKey".into()],
+                action: Action::ToggleLights,
+            },
+            Binding {
+                keys: Vec::new(),
+                action: Action::ToggleLights,
+            },
+        ];
+
+        assert_eq!(fired(&bindings, &[Press(Key::IntlBackslash), Press(Code(N))]), vec![Action::NextProfile]);
+    }
+}
@@ -0,0 +1,20 @@
+use std::{thread, time::Duration};
+
+use device_query::{DeviceEvents, DeviceEventsHandler};
+
+use super::KeyEvent;
+
+pub fn start(publish: fn(KeyEvent)) {
+    thread::spawn(move || {
+        // The handler only proxies a global event loop, so it can be made up if one was already created
+        let event_handler = DeviceEventsHandler::new(Duration::from_millis(10)).unwrap_or(DeviceEventsHandler {});
+
+        let _press_guard = event_handler.on_key_down(move |key| publish(KeyEvent::Press((*key).into())));
+        let _release_guard = event_handler.on_key_up(move |key| publish(KeyEvent::Release((*key).into())));
+
+        // The callbacks stop once the guards are dropped
+        loop {
+            thread::park();
+        }
+    });
+}
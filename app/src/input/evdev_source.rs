@@ -0,0 +1,393 @@
+use std::{env, fs, io, mem::size_of, path::Path, thread, time::Duration};
+
+use device_query::Keycode;
+use error_stack::{Report, Result, ResultExt};
+use evdev::{Device, EventType, KeyCode};
+use thiserror::Error;
+
+use super::{Key, KeyEvent};
+
+const INPUT_PATH: &str = "/dev/input";
+/// Set to a recording made with `cat /dev/input/eventX > file` to play it back instead of reading the keyboard
+const REPLAY_VAR: &str = "LEGION_KEYBOARD_INPUT_REPLAY";
+/// Where the built in keyboard of a laptop is wired, which is the i8042 controller
+const INTERNAL_PHYS: &str = "isa0060/serio0";
+const INTERNAL_NAME: &str = "AT Translated Set 2 keyboard";
+/// Size of a `struct input_event`, a `timeval` made of two longs followed by the type, code and value
+const EVENT_SIZE: usize = 2 * size_of::<isize>() + 8;
+
+#[derive(Debug, Error)]
+pub enum InputError {
+    #[error("No permission to read the keyboard from {INPUT_PATH}")]
+    NoPermission,
+    #[error("Could not find a keyboard in {INPUT_PATH}")]
+    NoKeyboard,
+    #[error("Could not read the recorded input events")]
+    Replay,
+}
+
+/// Publish key events from the keyboard, or from a recording if one is given
+pub fn start(publish: fn(KeyEvent)) -> Result<(), InputError> {
+    if let Some(path) = env::var_os(REPLAY_VAR) {
+        let recording = fs::read(&path).change_context(InputError::Replay).attach_printable_lazy(|| Path::new(&path).display().to_string())?;
+        thread::spawn(move || replay(&recording, publish));
+
+        return Ok(());
+    }
+
+    for mut device in open_keyboards()? {
+        let name = device.name().unwrap_or("the keyboard").to_string();
+
+        thread::spawn(move || loop {
+            match device.fetch_events() {
+                Ok(events) => {
+                    for event in events {
+                        if let Some(event) = translate(event.event_type().0, event.code(), event.value()) {
+                            publish(event);
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Stopped reading {name}: {err}");
+                    break;
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// The built in keyboard if it can be told apart, or every keyboard otherwise
+fn open_keyboards() -> Result<Vec<Device>, InputError> {
+    let entries = fs::read_dir(INPUT_PATH).change_context(InputError::NoKeyboard)?;
+
+    let mut keyboards = Vec::new();
+    let mut denied = false;
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("event")) {
+            continue;
+        }
+
+        match Device::open(&path) {
+            Ok(device) if is_keyboard(&device) => keyboards.push(device),
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => denied = true,
+            Err(_) => {}
+        }
+    }
+
+    // The zones only line up with the keys of the built in keyboard
+    let keyboards = prefer_internal(keyboards, |device| is_internal(device.physical_path(), device.name()));
+
+    if !keyboards.is_empty() {
+        Ok(keyboards)
+    } else if denied {
+        Err(Report::new(InputError::NoPermission)
+            .attach_printable("Add your user to the input group (sudo usermod -aG input $USER) and log back in")
+            .attach_printable("https://github.com/4JX/L5P-Keyboard-RGB#usage"))
+    } else {
+        Err(Report::new(InputError::NoKeyboard))
+    }
+}
+
+fn is_keyboard(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(KeyCode::KEY_A) && keys.contains(KeyCode::KEY_SPACE) && keys.contains(KeyCode::KEY_ENTER))
+}
+
+/// Just the built in keyboard if it's among `keyboards`, or all of them otherwise
+fn prefer_internal<T>(mut keyboards: Vec<T>, is_internal: impl Fn(&T) -> bool) -> Vec<T> {
+    match keyboards.iter().position(is_internal) {
+        Some(i) => vec![keyboards.swap_remove(i)],
+        None => keyboards,
+    }
+}
+
+fn is_internal(phys: Option<&str>, name: Option<&str>) -> bool {
+    phys.is_some_and(|phys| phys.starts_with(INTERNAL_PHYS)) || name == Some(INTERNAL_NAME)
+}
+
+/// Turn a raw evdev event into a key event, leaving out anything that isn't a key going down or up
+pub fn translate(type_: u16, code: u16, value: i32) -> Option<KeyEvent> {
+    if type_ != EventType::KEY.0 {
+        return None;
+    }
+
+    let key = key(KeyCode(code))?;
+
+    // Held keys repeat with a value of 2
+    match value {
+        0 => Some(KeyEvent::Release(key)),
+        1 => Some(KeyEvent::Press(key)),
+        _ => None,
+    }
+}
+
+/// An event as stored in a recording
+#[derive(Clone, Copy, Debug)]
+pub struct RecordedEvent {
+    /// When the event happened, from whatever point the kernel counts from
+    pub time: Duration,
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+/// Split a recording of raw `struct input_event`s into events, ignoring a cut off one at the end
+pub fn decode(recording: &[u8]) -> impl Iterator<Item = RecordedEvent> + '_ {
+    const LONG: usize = size_of::<isize>();
+
+    recording.chunks_exact(EVENT_SIZE).map(|raw| {
+        let seconds = isize::from_ne_bytes(raw[..LONG].try_into().unwrap());
+        let micros = isize::from_ne_bytes(raw[LONG..2 * LONG].try_into().unwrap());
+
+        RecordedEvent {
+            time: Duration::from_secs(seconds.max(0) as u64) + Duration::from_micros(micros.max(0) as u64),
+            type_: u16::from_ne_bytes(raw[2 * LONG..2 * LONG + 2].try_into().unwrap()),
+            code: u16::from_ne_bytes(raw[2 * LONG + 2..2 * LONG + 4].try_into().unwrap()),
+            value: i32::from_ne_bytes(raw[2 * LONG + 4..2 * LONG + 8].try_into().unwrap()),
+        }
+    })
+}
+
+/// Play a recording back with its original timing
+fn replay(recording: &[u8], publish: fn(KeyEvent)) {
+    let mut last: Option<Duration> = None;
+
+    for event in decode(recording) {
+        if let Some(last) = last {
+            thread::sleep(event.time.saturating_sub(last));
+        }
+        last = Some(event.time);
+
+        if let Some(event) = translate(event.type_, event.code, event.value) {
+            publish(event);
+        }
+    }
+}
+
+fn key(code: KeyCode) -> Option<Key> {
+    if code == KeyCode::KEY_102ND {
+        return Some(Key::IntlBackslash);
+    }
+
+    KEYMAP.iter().find(|(evdev, _)| *evdev == code).map(|(_, key)| Key::Code(*key))
+}
+
+/// The keys `device_query` knows about, by their evdev code
+const KEYMAP: &[(KeyCode, Keycode)] = &[
+    (KeyCode::KEY_0, Keycode::Key0),
+    (KeyCode::KEY_1, Keycode::Key1),
+    (KeyCode::KEY_2, Keycode::Key2),
+    (KeyCode::KEY_3, Keycode::Key3),
+    (KeyCode::KEY_4, Keycode::Key4),
+    (KeyCode::KEY_5, Keycode::Key5),
+    (KeyCode::KEY_6, Keycode::Key6),
+    (KeyCode::KEY_7, Keycode::Key7),
+    (KeyCode::KEY_8, Keycode::Key8),
+    (KeyCode::KEY_9, Keycode::Key9),
+    (KeyCode::KEY_A, Keycode::A),
+    (KeyCode::KEY_B, Keycode::B),
+    (KeyCode::KEY_C, Keycode::C),
+    (KeyCode::KEY_D, Keycode::D),
+    (KeyCode::KEY_E, Keycode::E),
+    (KeyCode::KEY_F, Keycode::F),
+    (KeyCode::KEY_G, Keycode::G),
+    (KeyCode::KEY_H, Keycode::H),
+    (KeyCode::KEY_I, Keycode::I),
+    (KeyCode::KEY_J, Keycode::J),
+    (KeyCode::KEY_K, Keycode::K),
+    (KeyCode::KEY_L, Keycode::L),
+    (KeyCode::KEY_M, Keycode::M),
+    (KeyCode::KEY_N, Keycode::N),
+    (KeyCode::KEY_O, Keycode::O),
+    (KeyCode::KEY_P, Keycode::P),
+    (KeyCode::KEY_Q, Keycode::Q),
+    (KeyCode::KEY_R, Keycode::R),
+    (KeyCode::KEY_S, Keycode::S),
+    (KeyCode::KEY_T, Keycode::T),
+    (KeyCode::KEY_U, Keycode::U),
+    (KeyCode::KEY_V, Keycode::V),
+    (KeyCode::KEY_W, Keycode::W),
+    (KeyCode::KEY_X, Keycode::X),
+    (KeyCode::KEY_Y, Keycode::Y),
+    (KeyCode::KEY_Z, Keycode::Z),
+    (KeyCode::KEY_F1, Keycode::F1),
+    (KeyCode::KEY_F2, Keycode::F2),
+    (KeyCode::KEY_F3, Keycode::F3),
+    (KeyCode::KEY_F4, Keycode::F4),
+    (KeyCode::KEY_F5, Keycode::F5),
+    (KeyCode::KEY_F6, Keycode::F6),
+    (KeyCode::KEY_F7, Keycode::F7),
+    (KeyCode::KEY_F8, Keycode::F8),
+    (KeyCode::KEY_F9, Keycode::F9),
+    (KeyCode::KEY_F10, Keycode::F10),
+    (KeyCode::KEY_F11, Keycode::F11),
+    (KeyCode::KEY_F12, Keycode::F12),
+    (KeyCode::KEY_ESC, Keycode::Escape),
+    (KeyCode::KEY_SPACE, Keycode::Space),
+    (KeyCode::KEY_LEFTCTRL, Keycode::LControl),
+    (KeyCode::KEY_RIGHTCTRL, Keycode::RControl),
+    (KeyCode::KEY_LEFTSHIFT, Keycode::LShift),
+    (KeyCode::KEY_RIGHTSHIFT, Keycode::RShift),
+    (KeyCode::KEY_LEFTALT, Keycode::LAlt),
+    (KeyCode::KEY_RIGHTALT, Keycode::RAlt),
+    (KeyCode::KEY_LEFTMETA, Keycode::LMeta),
+    (KeyCode::KEY_RIGHTMETA, Keycode::RMeta),
+    (KeyCode::KEY_ENTER, Keycode::Enter),
+    (KeyCode::KEY_UP, Keycode::Up),
+    (KeyCode::KEY_DOWN, Keycode::Down),
+    (KeyCode::KEY_LEFT, Keycode::Left),
+    (KeyCode::KEY_RIGHT, Keycode::Right),
+    (KeyCode::KEY_BACKSPACE, Keycode::Backspace),
+    (KeyCode::KEY_CAPSLOCK, Keycode::CapsLock),
+    (KeyCode::KEY_TAB, Keycode::Tab),
+    (KeyCode::KEY_HOME, Keycode::Home),
+    (KeyCode::KEY_END, Keycode::End),
+    (KeyCode::KEY_PAGEUP, Keycode::PageUp),
+    (KeyCode::KEY_PAGEDOWN, Keycode::PageDown),
+    (KeyCode::KEY_INSERT, Keycode::Insert),
+    (KeyCode::KEY_DELETE, Keycode::Delete),
+    (KeyCode::KEY_KP0, Keycode::Numpad0),
+    (KeyCode::KEY_KP1, Keycode::Numpad1),
+    (KeyCode::KEY_KP2, Keycode::Numpad2),
+    (KeyCode::KEY_KP3, Keycode::Numpad3),
+    (KeyCode::KEY_KP4, Keycode::Numpad4),
+    (KeyCode::KEY_KP5, Keycode::Numpad5),
+    (KeyCode::KEY_KP6, Keycode::Numpad6),
+    (KeyCode::KEY_KP7, Keycode::Numpad7),
+    (KeyCode::KEY_KP8, Keycode::Numpad8),
+    (KeyCode::KEY_KP9, Keycode::Numpad9),
+    (KeyCode::KEY_KPMINUS, Keycode::NumpadSubtract),
+    (KeyCode::KEY_KPPLUS, Keycode::NumpadAdd),
+    (KeyCode::KEY_KPSLASH, Keycode::NumpadDivide),
+    (KeyCode::KEY_KPASTERISK, Keycode::NumpadMultiply),
+    (KeyCode::KEY_KPEQUAL, Keycode::NumpadEquals),
+    (KeyCode::KEY_KPENTER, Keycode::NumpadEnter),
+    (KeyCode::KEY_KPDOT, Keycode::NumpadDecimal),
+    (KeyCode::KEY_GRAVE, Keycode::Grave),
+    (KeyCode::KEY_MINUS, Keycode::Minus),
+    (KeyCode::KEY_EQUAL, Keycode::Equal),
+    (KeyCode::KEY_LEFTBRACE, Keycode::LeftBracket),
+    (KeyCode::KEY_RIGHTBRACE, Keycode::RightBracket),
+    (KeyCode::KEY_BACKSLASH, Keycode::BackSlash),
+    (KeyCode::KEY_SEMICOLON, Keycode::Semicolon),
+    (KeyCode::KEY_APOSTROPHE, Keycode::Apostrophe),
+    (KeyCode::KEY_COMMA, Keycode::Comma),
+    (KeyCode::KEY_DOT, Keycode::Dot),
+    (KeyCode::KEY_SLASH, Keycode::Slash),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        mem::size_of,
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
+
+    use device_query::Keycode;
+    use evdev::{EventType, KeyCode};
+
+    use super::{decode, is_internal, prefer_internal, replay, translate};
+    use crate::input::{Key, KeyEvent};
+
+    /// A raw `struct input_event`, as the kernel hands it out and recordings store it
+    fn raw_event(micros: isize, type_: EventType, code: u16, value: i32) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(2 * size_of::<isize>() + 8);
+        raw.extend((micros / 1_000_000).to_ne_bytes());
+        raw.extend((micros % 1_000_000).to_ne_bytes());
+        raw.extend(type_.0.to_ne_bytes());
+        raw.extend(code.to_ne_bytes());
+        raw.extend(value.to_ne_bytes());
+        raw
+    }
+
+    /// Key events `step_micros` apart, each followed by a sync as the kernel sends them
+    fn recording(keys: &[(KeyCode, i32)], step_micros: isize) -> Vec<u8> {
+        let mut recording = Vec::new();
+        for (i, (key, value)) in keys.iter().enumerate() {
+            let micros = 1_500_000 + i as isize * step_micros;
+            recording.extend(raw_event(micros, EventType::KEY, key.0, *value));
+            recording.extend(raw_event(micros, EventType::SYNCHRONIZATION, 0, 0));
+        }
+        recording
+    }
+
+    #[test]
+    fn decodes_recordings_and_drops_a_cut_off_event() {
+        let mut recording = recording(&[(KeyCode::KEY_A, 1), (KeyCode::KEY_A, 0)], 250_000);
+        recording.extend(&raw_event(0, EventType::KEY, KeyCode::KEY_B.0, 1)[..10]);
+
+        let events: Vec<_> = decode(&recording).map(|event| (event.time, event.type_, event.code, event.value)).collect();
+        assert_eq!(
+            events,
+            vec![
+                (Duration::from_millis(1500), EventType::KEY.0, KeyCode::KEY_A.0, 1),
+                (Duration::from_millis(1500), EventType::SYNCHRONIZATION.0, 0, 0),
+                (Duration::from_millis(1750), EventType::KEY.0, KeyCode::KEY_A.0, 0),
+                (Duration::from_millis(1750), EventType::SYNCHRONIZATION.0, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn translates_presses_and_releases_only() {
+        let key = EventType::KEY.0;
+
+        assert_eq!(translate(key, KeyCode::KEY_A.0, 1), Some(KeyEvent::Press(Key::Code(Keycode::A))));
+        assert_eq!(translate(key, KeyCode::KEY_A.0, 0), Some(KeyEvent::Release(Key::Code(Keycode::A))));
+        // Held keys repeating
+        assert_eq!(translate(key, KeyCode::KEY_A.0, 2), None);
+        assert_eq!(translate(EventType::SYNCHRONIZATION.0, 0, 0), None);
+        assert_eq!(translate(EventType::MISC.0, KeyCode::KEY_A.0, 1), None);
+        // Keys `device_query` has no name for
+        assert_eq!(translate(key, KeyCode::KEY_MUTE.0, 1), None);
+    }
+
+    #[test]
+    fn tells_the_iso_key_apart_from_backslash() {
+        let key = EventType::KEY.0;
+
+        assert_eq!(translate(key, KeyCode::KEY_BACKSLASH.0, 1), Some(KeyEvent::Press(Key::Code(Keycode::BackSlash))));
+        assert_eq!(translate(key, KeyCode::KEY_102ND.0, 1), Some(KeyEvent::Press(Key::IntlBackslash)));
+    }
+
+    static REPLAYED: Mutex<Vec<KeyEvent>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn replays_recordings_with_their_timing() {
+        let recording = recording(&[(KeyCode::KEY_LEFTSHIFT, 1), (KeyCode::KEY_102ND, 1), (KeyCode::KEY_102ND, 2), (KeyCode::KEY_102ND, 0)], 20_000);
+
+        let start = Instant::now();
+        replay(&recording, |event| REPLAYED.lock().unwrap().push(event));
+
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(
+            *REPLAYED.lock().unwrap(),
+            vec![KeyEvent::Press(Key::Code(Keycode::LShift)), KeyEvent::Press(Key::IntlBackslash), KeyEvent::Release(Key::IntlBackslash)]
+        );
+    }
+
+    #[test]
+    fn recognizes_the_built_in_keyboard() {
+        assert!(is_internal(Some("isa0060/serio0/input0"), Some("AT Translated Set 2 keyboard")));
+        // Either one is enough
+        assert!(is_internal(Some("isa0060/serio0/input0"), None));
+        assert!(is_internal(None, Some("AT Translated Set 2 keyboard")));
+        assert!(!is_internal(Some("usb-0000:00:14.0-2/input0"), Some("Logitech USB Keyboard")));
+        assert!(!is_internal(None, None));
+    }
+
+    #[test]
+    fn prefers_the_built_in_keyboard_over_the_rest() {
+        let internal = |name: &&str| *name == "internal";
+
+        assert_eq!(prefer_internal(vec!["usb", "internal", "bluetooth"], internal), vec!["internal"]);
+        assert_eq!(prefer_internal(vec!["usb", "bluetooth"], internal), vec!["usb", "bluetooth"]);
+        assert!(prefer_internal(Vec::new(), internal).is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Key events from whatever source works on the system, shared by everything that reacts to typing
+//!
+//! On Linux the keyboard is read through evdev, which also works under Wayland. Everywhere else, or when
+//! evdev can't be used, `device_query` is polled instead.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{Mutex, Once},
+};
+
+use crossbeam_channel::{Receiver, Sender};
+pub use device_query::Keycode;
+
+mod device_query_source;
+#[cfg(target_os = "linux")]
+pub mod evdev_source;
+
+/// A key as `device_query` names it, or one it has no name of its own for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Code(Keycode),
+    /// The extra key next to the left shift on ISO keyboards, which only evdev tells apart from the backslash key
+    IntlBackslash,
+}
+
+impl From<Keycode> for Key {
+    fn from(code: Keycode) -> Self {
+        Self::Code(code)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Code(code) => code.fmt(f),
+            Self::IntlBackslash => f.write_str("IntlBackslash"),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = <Keycode as FromStr>::Err;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "IntlBackslash" => Ok(Self::IntlBackslash),
+            _ => name.parse().map(Self::Code),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEvent {
+    Press(Key),
+    Release(Key),
+}
+
+static SUBSCRIBERS: Mutex<Vec<Sender<KeyEvent>>> = Mutex::new(Vec::new());
+static START: Once = Once::new();
+
+/// Receive every key event from now on, until the receiver is dropped
+pub fn subscribe() -> Receiver<KeyEvent> {
+    START.call_once(start);
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+fn publish(event: KeyEvent) {
+    SUBSCRIBERS.lock().unwrap().retain(|tx| tx.send(event).is_ok());
+}
+
+/// Start listening in the background, through the first source that works
+fn start() {
+    #[cfg(target_os = "linux")]
+    match evdev_source::start(publish) {
+        Ok(()) => return,
+        Err(err) => eprintln!("{err:?}\nFalling back to polling the keyboard, which doesn't work under Wayland"),
+    }
+
+    device_query_source::start(publish);
+}
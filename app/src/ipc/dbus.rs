@@ -0,0 +1,449 @@
+//! The same control as the socket, published on the session bus for desktop widgets, extensions and `busctl`
+//!
+//! Setting `DBUS_SESSION_BUS_ADDRESS` to a private `dbus-daemon` keeps it off the desktop's bus.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crossbeam_channel::Sender;
+use error_stack::{Result, ResultExt};
+use zbus::{
+    blocking::{connection, Connection},
+    fdo, interface,
+    names::InterfaceName,
+    object_server::SignalContext,
+    zvariant::Value,
+};
+
+use crate::{
+    enums::{Action, Brightness, Effects},
+    manager::profile::Profile,
+};
+
+use super::{call, Call, IpcError, Request, Response, Status};
+
+// Parts of names on the bus can't start with a digit, hence the underscore
+const SERVICE_NAME: &str = "io.github._4JX.L5PKeyboardRGB";
+const OBJECT_PATH: &str = "/io/github/_4JX/L5PKeyboardRGB";
+const INTERFACE_NAME: &str = "io.github._4JX.L5PKeyboardRGB.Lights";
+
+/// Answers calls on the bus by handing them to the owner of the keyboard
+struct Lights {
+    tx: Sender<Call>,
+    /// What was last published, which the properties are read from
+    ///
+    /// Kept apart from the interface, which stays locked while a call waits on the owner of the keyboard
+    status: Arc<Mutex<Option<Status>>>,
+}
+
+#[interface(name = "io.github._4JX.L5PKeyboardRGB.Lights")]
+impl Lights {
+    /// Switch to a saved profile by its name
+    async fn set_profile(&self, name: String) -> fdo::Result<()> {
+        self.send(Request::Action { action: Action::Profile { name } }).await
+    }
+
+    /// Show a profile given as JSON, in the same format it would be saved in
+    async fn load_profile(&self, profile: String) -> fdo::Result<()> {
+        let profile: Profile = serde_json::from_str(&profile).map_err(|err| fdo::Error::InvalidArgs(err.to_string()))?;
+        self.send(Request::SetProfile { profile: Box::new(profile) }).await
+    }
+
+    /// Change the effect of the current profile, by the same name as on the command line
+    async fn set_effect(&self, effect: String) -> fdo::Result<()> {
+        let effect: Effects = effect.parse().map_err(|_| fdo::Error::InvalidArgs(format!("There is no effect called \"{effect}\"")))?;
+        self.edit_profile(|profile| profile.effect = effect).await
+    }
+
+    /// Change the colors of the current profile, as the red, green and blue of each zone from left to right
+    async fn set_colors(&self, colors: Vec<u8>) -> fdo::Result<()> {
+        if colors.len() != 12 {
+            return Err(fdo::Error::InvalidArgs(format!("Expected 12 values, got {}", colors.len())));
+        }
+
+        self.edit_profile(|profile| {
+            for (zone, rgb) in profile.rgb_zones.iter_mut().zip(colors.chunks_exact(3)) {
+                zone.rgb = [rgb[0], rgb[1], rgb[2]];
+            }
+        })
+        .await
+    }
+
+    /// Change the brightness of the current profile, either `Low` or `High`
+    async fn set_brightness(&self, brightness: String) -> fdo::Result<()> {
+        let brightness: Brightness = brightness.parse().map_err(|_| fdo::Error::InvalidArgs(format!("There is no brightness called \"{brightness}\"")))?;
+        self.edit_profile(|profile| profile.brightness = brightness).await
+    }
+
+    /// Play a saved custom effect by its name
+    async fn play_custom_effect(&self, name: String) -> fdo::Result<()> {
+        self.send(Request::Action {
+            action: Action::CustomEffect { name },
+        })
+        .await
+    }
+
+    async fn next_profile(&self) -> fdo::Result<()> {
+        self.send(Request::Action { action: Action::NextProfile }).await
+    }
+
+    async fn previous_profile(&self) -> fdo::Result<()> {
+        self.send(Request::Action { action: Action::PreviousProfile }).await
+    }
+
+    async fn toggle_lights(&self) -> fdo::Result<()> {
+        self.send(Request::Action { action: Action::ToggleLights }).await
+    }
+
+    /// Freeze the effect on its current frame, or start it again if it already is
+    async fn toggle_pause(&self) -> fdo::Result<()> {
+        self.send(Request::Action { action: Action::PauseEffect }).await
+    }
+
+    /// The name of the current profile, empty if it wasn't saved under one
+    #[zbus(property)]
+    fn profile(&self) -> fdo::Result<String> {
+        Ok(self.shown()?.profile.name.unwrap_or_default())
+    }
+
+    #[zbus(property)]
+    fn effect(&self) -> fdo::Result<String> {
+        Ok(self.shown()?.profile.effect.to_string())
+    }
+
+    #[zbus(property)]
+    fn colors(&self) -> fdo::Result<Vec<u8>> {
+        Ok(colors(&self.shown()?.profile))
+    }
+
+    #[zbus(property)]
+    fn brightness(&self) -> fdo::Result<String> {
+        Ok(self.shown()?.profile.brightness.to_string())
+    }
+
+    /// The name of the custom effect playing, empty if there isn't one
+    #[zbus(property)]
+    fn custom_effect(&self) -> fdo::Result<String> {
+        Ok(self.shown()?.custom_effect.unwrap_or_default())
+    }
+
+    #[zbus(property)]
+    fn lights_on(&self) -> fdo::Result<bool> {
+        Ok(self.shown()?.lights_on)
+    }
+
+    #[zbus(property)]
+    fn paused(&self) -> fdo::Result<bool> {
+        Ok(self.shown()?.paused)
+    }
+
+    /// Everything being shown, as JSON in the same format `legion-kb-rgb status` prints
+    #[zbus(property)]
+    fn status(&self) -> fdo::Result<String> {
+        Ok(serde_json::to_string(&self.shown()?).unwrap())
+    }
+
+    /// Sent along with the properties every time anything being shown changes, carrying the new status as JSON
+    #[zbus(signal)]
+    async fn changed(ctxt: &SignalContext<'_>, status: &str) -> zbus::Result<()>;
+}
+
+impl Lights {
+    fn shown(&self) -> fdo::Result<Status> {
+        self.status.lock().unwrap().clone().ok_or_else(|| fdo::Error::Failed("Nothing is being shown yet".to_string()))
+    }
+
+    /// Hand a request to the owner of the keyboard, waiting for it on a thread of its own so other calls and properties still get answered
+    async fn send(&self, request: Request) -> fdo::Result<()> {
+        let tx = self.tx.clone();
+        match blocking::unblock(move || call(&tx, request)).await {
+            Response::Error { message } => Err(fdo::Error::Failed(message)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Show the current profile with a change made to it
+    async fn edit_profile(&self, edit: impl FnOnce(&mut Profile)) -> fdo::Result<()> {
+        let mut profile = self.shown()?.profile;
+        edit(&mut profile);
+        self.send(Request::SetProfile { profile: Box::new(profile) }).await
+    }
+}
+
+fn colors(profile: &Profile) -> Vec<u8> {
+    profile.rgb_zones.iter().flat_map(|zone| zone.rgb).collect()
+}
+
+/// The properties that follow from `status`, by name
+fn properties(status: &Status) -> HashMap<&'static str, Value<'static>> {
+    HashMap::from([
+        ("Profile", Value::from(status.profile.name.clone().unwrap_or_default())),
+        ("Effect", Value::from(status.profile.effect.to_string())),
+        ("Colors", Value::from(colors(&status.profile))),
+        ("Brightness", Value::from(status.profile.brightness.to_string())),
+        ("CustomEffect", Value::from(status.custom_effect.clone().unwrap_or_default())),
+        ("LightsOn", Value::from(status.lights_on)),
+        ("Paused", Value::from(status.paused)),
+        ("Status", Value::from(serde_json::to_string(status).unwrap())),
+    ])
+}
+
+/// The properties that differ between what was published before, if anything was, and `status`
+fn changed_properties(previous: Option<&Status>, status: &Status) -> HashMap<&'static str, Value<'static>> {
+    let previous = previous.map(properties).unwrap_or_default();
+    let mut changed = properties(status);
+    changed.retain(|name, value| previous.get(name) != Some(value));
+    changed
+}
+
+/// Owns the name on the bus for as long as it's around
+pub struct Service {
+    connection: Connection,
+    status: Arc<Mutex<Option<Status>>>,
+}
+
+impl Service {
+    pub fn start(tx: Sender<Call>) -> Result<Self, IpcError> {
+        let status = Arc::new(Mutex::new(None));
+        let connection = connection::Builder::session()
+            .and_then(|builder| builder.name(SERVICE_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, Lights { tx, status: status.clone() }))
+            .and_then(|builder| builder.build())
+            .change_context(IpcError::Listen)?;
+
+        Ok(Self { connection, status })
+    }
+
+    /// Update the properties, announcing the ones that changed
+    pub fn publish(&self, status: &Status) {
+        let Ok(ctxt) = SignalContext::new(self.connection.inner(), OBJECT_PATH) else {
+            return;
+        };
+
+        let previous = self.status.lock().unwrap().replace(status.clone());
+        let changed = changed_properties(previous.as_ref(), status);
+        if changed.is_empty() {
+            return;
+        }
+        let changed: HashMap<&str, &Value> = changed.iter().map(|(name, value)| (*name, value)).collect();
+
+        let json = serde_json::to_string(status).unwrap();
+        let _ = zbus::block_on(async {
+            fdo::Properties::properties_changed(&ctxt, InterfaceName::from_static_str_unchecked(INTERFACE_NAME), &changed, &[]).await?;
+            Lights::changed(&ctxt, &json).await
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use zbus::{blocking, fdo, zvariant::Value};
+
+    use super::{changed_properties, Lights, Service, INTERFACE_NAME, OBJECT_PATH, SERVICE_NAME};
+    use crate::{
+        enums::{Action, Brightness, Effects},
+        ipc::{Call, Request, Response, Status},
+        manager::profile::Profile,
+    };
+
+    fn status() -> Status {
+        Status {
+            profile: Profile::default(),
+            custom_effect: None,
+            lights_on: true,
+            paused: false,
+        }
+    }
+
+    /// The interface, with a thread standing in for the owner of the keyboard that answers with `respond`
+    fn lights(shown: Option<Status>, respond: fn(&Request) -> Response) -> (Lights, thread::JoinHandle<Vec<Request>>) {
+        let (tx, rx) = crossbeam_channel::unbounded::<Call>();
+        let owner = thread::spawn(move || {
+            rx.iter()
+                .map(|(request, reply)| {
+                    let _ = reply.send(respond(&request));
+                    request
+                })
+                .collect()
+        });
+
+        let lights = Lights {
+            tx,
+            status: Arc::new(Mutex::new(shown)),
+        };
+        (lights, owner)
+    }
+
+    #[test]
+    fn only_what_changed_is_announced() {
+        let before = status();
+        let mut after = status();
+        after.lights_on = false;
+
+        let changed = changed_properties(Some(&before), &after);
+        let mut names: Vec<_> = changed.keys().copied().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["LightsOn", "Status"]);
+        assert_eq!(changed["LightsOn"], Value::from(false));
+
+        assert!(changed_properties(Some(&after), &after).is_empty());
+        // Everything is new the first time around
+        assert_eq!(changed_properties(None, &after).len(), 8);
+    }
+
+    #[test]
+    fn edits_the_profile_being_shown() {
+        let (lights, owner) = lights(Some(status()), |_| Response::Ok);
+
+        zbus::block_on(async {
+            lights.set_colors((0..12).collect()).await.unwrap();
+            lights.set_effect("Breath".to_string()).await.unwrap();
+            lights.set_brightness("High".to_string()).await.unwrap();
+        });
+        drop(lights);
+
+        let profiles: Vec<Profile> = owner
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|request| match request {
+                Request::SetProfile { profile } => *profile,
+                request => panic!("unexpected {request:?}"),
+            })
+            .collect();
+
+        assert_eq!(profiles.len(), 3);
+        assert_eq!(profiles[0].rgb_zones[1].rgb, [3, 4, 5]);
+        // Each edit starts from what is shown, which the owner didn't change here
+        assert!(matches!(profiles[1].effect, Effects::Breath));
+        assert_eq!(profiles[1].rgb_zones, Profile::default().rgb_zones);
+        assert!(matches!(profiles[2].brightness, Brightness::High));
+    }
+
+    #[test]
+    fn rejects_bad_arguments_and_passes_on_failures() {
+        let (lights, owner) = lights(None, |_| Response::Error {
+            message: "No such profile".to_string(),
+        });
+
+        zbus::block_on(async {
+            assert!(matches!(lights.set_colors(vec![0; 11]).await, Err(fdo::Error::InvalidArgs(_))));
+            assert!(matches!(lights.set_effect("Sparkles".to_string()).await, Err(fdo::Error::InvalidArgs(_))));
+            // Nothing to edit until a status was published
+            assert!(matches!(lights.set_effect("Static".to_string()).await, Err(fdo::Error::Failed(_))));
+            assert!(matches!(lights.set_profile("Missing".to_string()).await, Err(fdo::Error::Failed(message)) if message == "No such profile"));
+        });
+        drop(lights);
+
+        assert_eq!(owner.join().unwrap().len(), 1);
+    }
+
+    /// A private session bus, stopped along with the test
+    struct Bus(Child);
+
+    impl Bus {
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon should be installed");
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+            env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim());
+
+            Self(daemon)
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /// Run with `cargo test -- --ignored` where `dbus-daemon` is installed
+    #[test]
+    #[ignore = "needs dbus-daemon to start a private session bus"]
+    fn answers_and_announces_on_the_bus() {
+        let _bus = Bus::start();
+
+        let (tx, rx) = crossbeam_channel::unbounded::<Call>();
+        let (requests_tx, requests) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            for (request, reply) in rx {
+                let _ = reply.send(Response::Ok);
+                let _ = requests_tx.send(request);
+            }
+        });
+        let service = Service::start(tx).unwrap();
+
+        // A desktop widget, as far as the test is concerned
+        let client = blocking::Connection::session().unwrap();
+        let proxy = blocking::Proxy::new(&client, SERVICE_NAME, OBJECT_PATH, INTERFACE_NAME).unwrap();
+        let properties = blocking::fdo::PropertiesProxy::builder(&client)
+            .destination(SERVICE_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (changed_tx, changed) = crossbeam_channel::unbounded();
+        let changed_properties = properties.receive_properties_changed().unwrap();
+        thread::spawn(move || {
+            for signal in changed_properties {
+                let args = signal.args().unwrap();
+                let mut names: Vec<String> = args.changed_properties().keys().map(ToString::to_string).collect();
+                names.sort_unstable();
+                let _ = changed_tx.send((args.interface_name().to_string(), names));
+            }
+        });
+
+        let (announced_tx, announced) = crossbeam_channel::unbounded();
+        let changed_signals = proxy.receive_signal("Changed").unwrap();
+        thread::spawn(move || {
+            for signal in changed_signals {
+                let _ = announced_tx.send(signal.body().deserialize::<String>().unwrap());
+            }
+        });
+
+        // Nothing to read until something is published
+        assert!(proxy.get_property::<bool>("LightsOn").is_err());
+
+        proxy.call::<_, _, ()>("NextProfile", &()).unwrap();
+        assert!(matches!(requests.recv_timeout(Duration::from_secs(5)).unwrap(), Request::Action { action: Action::NextProfile }));
+        let err = proxy.call::<_, _, ()>("SetColors", &(vec![0_u8; 11],)).unwrap_err();
+        assert!(matches!(err, zbus::Error::MethodError(name, _, _) if name.as_str() == "org.freedesktop.DBus.Error.InvalidArgs"));
+
+        let mut shown = status();
+        service.publish(&shown);
+        shown.lights_on = false;
+        service.publish(&shown);
+
+        // Everything the first time around, then only what changed
+        let timeout = Duration::from_secs(5);
+        let (interface, names) = changed.recv_timeout(timeout).unwrap();
+        assert_eq!((interface.as_str(), names.len()), (INTERFACE_NAME, 8));
+        assert_eq!(changed.recv_timeout(timeout).unwrap().1, ["LightsOn", "Status"]);
+        assert_eq!(announced.recv_timeout(timeout).unwrap(), serde_json::to_string(&status()).unwrap());
+        assert_eq!(announced.recv_timeout(timeout).unwrap(), serde_json::to_string(&shown).unwrap());
+
+        assert!(!proxy.get_property::<bool>("LightsOn").unwrap());
+        assert_eq!(proxy.get_property::<String>("Effect").unwrap(), Profile::default().effect.to_string());
+        assert!(requests.is_empty());
+    }
+}
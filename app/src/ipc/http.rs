@@ -0,0 +1,901 @@
+//! An HTTP API for stream decks, home automation and browser tools, with a WebSocket stream of what is being shown
+//!
+//! Only as much of HTTP/1.1 as that needs: one request per connection, with the body sized by `Content-Length`.
+//! A small control panel built on top of the API is served at the root, for phones and other browsers.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{select, Receiver, Sender};
+use error_stack::{Report, Result, ResultExt};
+use serde::{de::DeserializeOwned, Serialize};
+use sha1::{Digest, Sha1};
+use strum::IntoEnumIterator;
+
+use crate::{
+    enums::{Action, Effects},
+    manager::{custom_effect::CustomEffect, profile::Profile, Preview},
+};
+
+use super::{call, ApiSettings, Call, IpcError, Request, Response, Status};
+
+/// How often the stream looks for a new frame
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// How long a client gets to send its request, or to take what is sent to it
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Far more than any profile or custom effect needs
+const MAX_BODY_LEN: usize = 1024 * 1024;
+/// For the request line and headers together, which browsers stay well under
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+const MAX_HEADERS: usize = 64;
+/// Connections served at once, streams included, past which new ones are turned away
+const MAX_CONNECTIONS: usize = 32;
+/// How many statuses a client of the stream can fall behind by before it's given up on
+const STREAM_BACKLOG: usize = 16;
+/// Appended to the key sent when opening a WebSocket, as the protocol asks
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The files of the control panel by path, along with their type
+const PANEL: [(&str, &str, &str); 3] = [
+    ("/", "text/html; charset=utf-8", include_str!("../../res/panel/index.html")),
+    ("/panel.css", "text/css; charset=utf-8", include_str!("../../res/panel/panel.css")),
+    ("/panel.js", "text/javascript; charset=utf-8", include_str!("../../res/panel/panel.js")),
+];
+
+/// What goes down the stream, one JSON text message each
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event<'a> {
+    Status { status: &'a Status },
+    Frame { colors: [u8; 12] },
+}
+
+/// One of the effects a profile can show, with the settings it starts with
+#[derive(Serialize)]
+struct BuiltinEffect {
+    name: String,
+    effect: Effects,
+}
+
+/// Serves the API until dropped
+pub struct Api {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+    statuses: Sender<Status>,
+}
+
+impl Api {
+    pub fn start(settings: &ApiSettings, tx: Sender<Call>, preview: Preview) -> Result<Self, IpcError> {
+        let address = settings
+            .address
+            .to_socket_addrs()
+            .change_context(IpcError::Listen)?
+            .next()
+            .ok_or_else(|| Report::new(IpcError::Listen).attach_printable(format!("{} doesn't point anywhere", settings.address)))?;
+
+        if !address.ip().is_loopback() && settings.token.is_empty() {
+            return Err(Report::new(IpcError::Listen).attach_printable("A token has to be set to listen on addresses other machines can reach"));
+        }
+
+        let listener = TcpListener::bind(address).change_context(IpcError::Listen)?;
+        let address = listener.local_addr().change_context(IpcError::Listen)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (clients_tx, clients_rx) = crossbeam_channel::unbounded();
+        let (statuses_tx, statuses_rx) = crossbeam_channel::unbounded();
+
+        thread::spawn(move || stream(&clients_rx, &statuses_rx, &preview));
+
+        let stop_c = stop.clone();
+        let token = settings.token.clone();
+        let port = address.port();
+        let handle = thread::spawn(move || {
+            let open = Arc::new(AtomicUsize::new(0));
+            for connection in listener.incoming().flatten() {
+                if stop_c.load(Ordering::SeqCst) {
+                    break;
+                }
+                if open.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    continue;
+                }
+
+                open.fetch_add(1, Ordering::SeqCst);
+                let open = open.clone();
+                let tx = tx.clone();
+                let token = token.clone();
+                let clients_tx = clients_tx.clone();
+                thread::spawn(move || {
+                    serve_connection(connection, &tx, &token, port, &clients_tx);
+                    open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        Ok(Self {
+            address,
+            stop,
+            listener: Some(handle),
+            statuses: statuses_tx,
+        })
+    }
+
+    /// Tell every client of the stream what is being shown now
+    pub fn broadcast(&self, status: &Status) {
+        let _ = self.statuses.send(status.clone());
+    }
+}
+
+impl Drop for Api {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        // The listener only notices once something connects, and has to be gone before the address can be taken again
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+
+        if TcpStream::connect_timeout(&address, IO_TIMEOUT).is_ok() {
+            if let Some(handle) = self.listener.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+/// Send the clients of the stream every change to the status and every new frame
+fn stream(clients: &Receiver<(TcpStream, Status)>, statuses: &Receiver<Status>, preview: &Preview) {
+    let ticks = crossbeam_channel::tick(FRAME_INTERVAL);
+    let mut connected: Vec<StreamClient> = Vec::new();
+    let mut last_frame = None;
+
+    loop {
+        select! {
+            recv(clients) -> client => {
+                let Ok((connection, status)) = client else {
+                    break;
+                };
+                let client = StreamClient::spawn(Closing(connection));
+                if !client.send_status(&status) {
+                    continue;
+                }
+
+                // Catch everyone up if the frame changed since the last tick, otherwise only the new client needs it
+                let colors = preview.frame();
+                if last_frame == Some(colors) {
+                    if client.send_frame(colors) {
+                        connected.push(client);
+                    }
+                } else {
+                    last_frame = Some(colors);
+                    connected.push(client);
+                    connected.retain(|client| client.send_frame(colors));
+                }
+            }
+            recv(statuses) -> status => {
+                let Ok(status) = status else {
+                    break;
+                };
+                connected.retain(|client| client.send_status(&status));
+            }
+            recv(ticks) -> _ => {
+                if connected.is_empty() {
+                    continue;
+                }
+
+                let colors = preview.frame();
+                if last_frame != Some(colors) {
+                    last_frame = Some(colors);
+                    connected.retain(|client| client.send_frame(colors));
+                }
+            }
+        }
+    }
+}
+
+/// A client of the stream, written to by a thread of its own so one that stops reading can't hold up the others
+struct StreamClient {
+    statuses: Sender<Vec<u8>>,
+    /// Holds a single frame, as only the newest one is worth sending to a client that fell behind
+    frames: Sender<Vec<u8>>,
+    /// The other end of `frames`, to take back a frame the client didn't get to before a newer one came
+    unsent_frame: Receiver<Vec<u8>>,
+    /// Set once the writer stops, which holding on to `unsent_frame` would otherwise hide
+    gone: Arc<AtomicBool>,
+}
+
+impl StreamClient {
+    fn spawn(mut connection: impl Write + Send + 'static) -> Self {
+        let (statuses, statuses_rx) = crossbeam_channel::bounded::<Vec<u8>>(STREAM_BACKLOG);
+        let (frames, frames_rx) = crossbeam_channel::bounded::<Vec<u8>>(1);
+        let unsent_frame = frames_rx.clone();
+        let gone = Arc::new(AtomicBool::new(false));
+
+        let gone_c = gone.clone();
+        thread::spawn(move || loop {
+            // Statuses first, since a frame is only ever of use next to the status it belongs to
+            let message = statuses_rx.try_recv().or_else(|_| {
+                select! {
+                    recv(statuses_rx) -> message => message,
+                    recv(frames_rx) -> message => message,
+                }
+            });
+
+            if !message.is_ok_and(|message| connection.write_all(&message).is_ok()) {
+                gone_c.store(true, Ordering::SeqCst);
+                break;
+            }
+        });
+
+        Self { statuses, frames, unsent_frame, gone }
+    }
+
+    /// Returns `false` once the client is gone or too far behind to catch up
+    fn send_status(&self, status: &Status) -> bool {
+        self.statuses.try_send(event_frame(&Event::Status { status })).is_ok()
+    }
+
+    /// Returns `false` once the client is gone
+    fn send_frame(&self, colors: [u8; 12]) -> bool {
+        if self.gone.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        // Only this thread fills the slot, so once emptied there is always room
+        let _ = self.unsent_frame.try_recv();
+        let _ = self.frames.try_send(event_frame(&Event::Frame { colors }));
+        true
+    }
+}
+
+/// A connection to a client of the stream, closed for both sides once the writer is done with it
+struct Closing(TcpStream);
+
+impl Write for Closing {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Drop for Closing {
+    fn drop(&mut self) {
+        // Also stops the thread waiting for the client to close the stream
+        let _ = self.0.shutdown(Shutdown::Both);
+    }
+}
+
+fn event_frame(event: &Event) -> Vec<u8> {
+    let message = serde_json::to_string(event).unwrap();
+    websocket_frame(0x1, message.as_bytes())
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: String,
+    /// With the names in lowercase
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn read(connection: impl Read) -> std::result::Result<Self, String> {
+        let mut reader = BufReader::new(connection);
+        let mut head = Head::default();
+
+        let line = head.read_line(&mut reader)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err("Malformed request line".to_string());
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        let mut headers = Vec::new();
+        loop {
+            let line = head.read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err("Too many headers".to_string());
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let mut request = Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers,
+            body: Vec::new(),
+        };
+
+        let len: usize = request.header("content-length").map_or(Ok(0), str::parse).map_err(|_| "Malformed Content-Length".to_string())?;
+        if len > MAX_BODY_LEN {
+            return Err("The body is too large".to_string());
+        }
+        reader.take(len as u64).read_to_end(&mut request.body).map_err(|err| err.to_string())?;
+        if request.body.len() < len {
+            return Err("The request ended early".to_string());
+        }
+
+        Ok(request)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+
+    fn query_param(&self, name: &str) -> Option<String> {
+        self.query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| percent_decode(&value.replace('+', " ")))
+    }
+
+    /// Browsers can't set headers when opening a WebSocket, so the token can come in the query as well
+    fn authorized(&self, token: &str) -> bool {
+        let given = self
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string)
+            .or_else(|| self.query_param("token"));
+        token.is_empty() || given.is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    }
+
+    /// Whether the request was sent to this server by a name no other site can point at it, which rules out DNS rebinding
+    ///
+    /// That leaves `localhost` and addresses written out, which is also how other machines reach it.
+    fn allowed_host(&self, port: u16) -> bool {
+        let Some(host) = self.header("host") else {
+            return false;
+        };
+
+        let (name, host_port) = match host.rsplit_once(':') {
+            Some((name, host_port)) if !host_port.contains(']') => (name, host_port.parse().ok()),
+            _ => (host, Some(80)),
+        };
+        let name = name.strip_prefix('[').and_then(|name| name.strip_suffix(']')).unwrap_or(name);
+
+        host_port == Some(port) && (name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok())
+    }
+
+    /// Whether the request doesn't come from a page on some other site, which could otherwise reach a server on localhost
+    fn same_origin(&self) -> bool {
+        let Some(origin) = self.header("origin") else {
+            // Browsers always send one when opening a WebSocket or changing anything, and pages on other sites can't
+            // add an Authorization header without asking first, so only other programs get here
+            let reads_only = self.method == "GET" && self.header("upgrade").is_none();
+            return reads_only || self.header("authorization").is_some();
+        };
+
+        let origin_host = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://"));
+        origin_host.is_some() && origin_host == self.header("host")
+    }
+
+    fn json<T: DeserializeOwned>(&self) -> std::result::Result<T, HttpResponse> {
+        // Cross-site forms can't send JSON without the browser asking first, which this never agrees to
+        if !self.header("content-type").is_some_and(|value| value.starts_with("application/json")) {
+            return Err(HttpResponse::error(415, "Expected a body of type application/json"));
+        }
+
+        serde_json::from_slice(&self.body).map_err(|err| HttpResponse::error(400, &err.to_string()))
+    }
+}
+
+/// How much of the request line and headers has been read, and for how long, so neither can go on forever
+struct Head {
+    len: u64,
+    started: Instant,
+}
+
+impl Default for Head {
+    fn default() -> Self {
+        Self { len: 0, started: Instant::now() }
+    }
+}
+
+impl Head {
+    fn read_line(&mut self, reader: &mut impl BufRead) -> std::result::Result<String, String> {
+        // Each read has its own timeout, which a client sending a byte at a time would never run into
+        if self.started.elapsed() > IO_TIMEOUT {
+            return Err("The request took too long".to_string());
+        }
+
+        let mut line = String::new();
+        self.len += reader.take(MAX_HEAD_LEN - self.len).read_line(&mut line).map_err(|err| err.to_string())? as u64;
+        if !line.ends_with('\n') {
+            return Err(if self.len == MAX_HEAD_LEN { "The headers are too large" } else { "The request ended early" }.to_string());
+        }
+
+        line.truncate(line.trim_end().len());
+        Ok(line)
+    }
+}
+
+struct HttpResponse {
+    code: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn json<T: Serialize + ?Sized>(value: &T) -> Self {
+        Self {
+            code: 200,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).unwrap(),
+        }
+    }
+
+    fn error(code: u16, message: &str) -> Self {
+        Self {
+            code,
+            ..Self::json(&serde_json::json!({ "error": message }))
+        }
+    }
+
+    /// One of the files of the control panel
+    fn panel(path: &str) -> Option<Self> {
+        PANEL.iter().find(|(asset, ..)| *asset == path).map(|(_, content_type, body)| Self {
+            code: 200,
+            content_type,
+            body: body.as_bytes().to_vec(),
+        })
+    }
+
+    fn no_content() -> Self {
+        Self {
+            code: 204,
+            content_type: "application/json",
+            body: Vec::new(),
+        }
+    }
+
+    fn write(&self, connection: &mut TcpStream) -> io::Result<()> {
+        let reason = match self.code {
+            200 => "OK",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            415 => "Unsupported Media Type",
+            _ => "Error",
+        };
+
+        let head = format!(
+            "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.code,
+            self.content_type,
+            self.body.len()
+        );
+        connection.write_all(head.as_bytes())?;
+        connection.write_all(&self.body)
+    }
+}
+
+fn serve_connection(mut connection: TcpStream, tx: &Sender<Call>, token: &str, port: u16, clients: &Sender<(TcpStream, Status)>) {
+    let _ = connection.set_read_timeout(Some(IO_TIMEOUT));
+    let _ = connection.set_write_timeout(Some(IO_TIMEOUT));
+
+    let request = match HttpRequest::read(&connection) {
+        Ok(request) => request,
+        Err(message) => {
+            let _ = HttpResponse::error(400, &message).write(&mut connection);
+            return;
+        }
+    };
+
+    // The panel asks for the token itself, so it has to load without one
+    let panel = if request.method == "GET" { HttpResponse::panel(&request.path) } else { None };
+
+    let response = if !request.allowed_host(port) {
+        HttpResponse::error(403, "Requests have to be sent to localhost or an address")
+    } else if let Some(panel) = panel {
+        panel
+    } else if !request.same_origin() {
+        HttpResponse::error(403, "Requests from other sites are not allowed")
+    } else if !request.authorized(token) {
+        HttpResponse::error(401, "A valid token is needed")
+    } else if request.method == "GET" && request.path == "/api/stream" {
+        open_stream(&request, connection, tx, clients);
+        return;
+    } else {
+        route(&request, tx)
+    };
+
+    let _ = response.write(&mut connection);
+}
+
+fn route(request: &HttpRequest, tx: &Sender<Call>) -> HttpResponse {
+    let segments: Vec<String> = request.path.trim_matches('/').split('/').map(percent_decode).collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let parsed = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "status"]) => Ok(Request::Status),
+        ("GET", ["api", "builtin-effects"]) => {
+            let effects: Vec<BuiltinEffect> = Effects::iter().map(|effect| BuiltinEffect { name: effect.to_string(), effect }).collect();
+            return HttpResponse::json(&effects);
+        }
+        ("GET", ["api", "profiles"]) => Ok(Request::Profiles),
+        ("POST", ["api", "profiles"]) => request.json().map(|profile: Profile| Request::SaveProfile { profile: Box::new(profile) }),
+        ("POST", ["api", "profiles", name, "apply"]) => Ok(Request::Action {
+            action: Action::Profile { name: (*name).to_string() },
+        }),
+        ("PUT", ["api", "profile"]) => request.json().map(|profile: Profile| Request::SetProfile { profile: Box::new(profile) }),
+        ("GET", ["api", "effects"]) => Ok(Request::CustomEffects),
+        ("POST", ["api", "effects"]) => request.json().map(|effect: CustomEffect| Request::SaveCustomEffect { effect }),
+        ("POST", ["api", "effects", name, "play"]) => Ok(Request::Action {
+            action: Action::CustomEffect { name: (*name).to_string() },
+        }),
+        ("PUT", ["api", "effect"]) => request.json().map(|effect: CustomEffect| Request::CustomEffect { effect }),
+        ("POST", ["api", "actions"]) => request.json().map(|action: Action| Request::Action { action }),
+        _ => Err(HttpResponse::error(404, "There is nothing here")),
+    };
+
+    let request = match parsed {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+
+    match call(tx, request) {
+        Response::Ok => HttpResponse::no_content(),
+        Response::Status { status } => HttpResponse::json(&status),
+        Response::Profiles { profiles } => HttpResponse::json(&profiles),
+        Response::CustomEffects { effects } => HttpResponse::json(&effects),
+        Response::Error { message } => HttpResponse::error(400, &message),
+    }
+}
+
+/// Upgrade the connection to a WebSocket and hand it to the stream, keeping an eye out for the client closing it
+fn open_stream(request: &HttpRequest, mut connection: TcpStream, tx: &Sender<Call>, clients: &Sender<(TcpStream, Status)>) {
+    let Some(key) = request.header("sec-websocket-key") else {
+        let _ = HttpResponse::error(400, "Expected a WebSocket handshake").write(&mut connection);
+        return;
+    };
+
+    let Response::Status { status } = call(tx, Request::Status) else {
+        return;
+    };
+
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        websocket_accept(key)
+    );
+    if connection.write_all(handshake.as_bytes()).is_err() {
+        return;
+    }
+
+    let Ok(mut reader) = connection.try_clone() else {
+        return;
+    };
+    if clients.send((connection, *status)).is_err() {
+        return;
+    }
+
+    // Nothing the client sends is needed, other than knowing when it's done
+    let _ = reader.set_read_timeout(None);
+    while let Ok(opcode) = read_websocket_frame(&mut reader) {
+        if opcode == 0x8 {
+            let _ = reader.write_all(&websocket_frame(0x8, &[]));
+            break;
+        }
+    }
+    let _ = reader.shutdown(Shutdown::Both);
+}
+
+/// What the client expects back for its `Sec-WebSocket-Key`, proving the server speaks WebSocket
+fn websocket_accept(key: &str) -> String {
+    base64(&Sha1::digest(format!("{key}{WEBSOCKET_GUID}").as_bytes()))
+}
+
+fn websocket_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend(payload);
+    frame
+}
+
+/// Skip over a frame sent by the client, returning its opcode
+fn read_websocket_frame(reader: &mut impl Read) -> io::Result<u8> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head)?;
+
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    // Frames from clients are always masked
+    let mask_len = if head[1] & 0x80 == 0 { 0 } else { 4 };
+
+    io::copy(&mut reader.take(mask_len + len), &mut io::sink())?;
+
+    Ok(head[0] & 0x0F)
+}
+
+/// Compare without giving away through the time it takes how much of the token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (u32::from(chunk[0]) << 16) | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8) | u32::from(*chunk.get(2).unwrap_or(&0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        time::{Duration, Instant},
+    };
+
+    use crossbeam_channel::{Receiver, Sender};
+
+    use super::{base64, percent_decode, read_websocket_frame, websocket_accept, websocket_frame, HttpRequest, StreamClient, MAX_BODY_LEN, STREAM_BACKLOG};
+    use crate::{ipc::Status, manager::profile::Profile};
+
+    fn request(raw: &str) -> HttpRequest {
+        HttpRequest::read(raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn reads_requests_within_limits() {
+        let parsed = request("POST /profile?name=Night+time%21 HTTP/1.1\r\nHost: localhost:8080\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}extra");
+        assert_eq!((parsed.method.as_str(), parsed.path.as_str()), ("POST", "/profile"));
+        assert_eq!(parsed.query_param("name").as_deref(), Some("Night time!"));
+        assert_eq!(parsed.header("content-type"), Some("application/json"));
+        assert_eq!(parsed.body, b"{}");
+
+        let too_many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X: y\r\n".repeat(100));
+        assert!(HttpRequest::read(too_many_headers.as_bytes()).is_err());
+
+        let huge_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "y".repeat(20 * 1024));
+        assert!(HttpRequest::read(huge_header.as_bytes()).is_err());
+
+        let huge_body = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_LEN + 1);
+        assert!(HttpRequest::read(huge_body.as_bytes()).is_err());
+
+        assert!(HttpRequest::read("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}".as_bytes()).is_err());
+        assert!(HttpRequest::read("GET / HTTP/1.1\r\nHost: localhost".as_bytes()).is_err());
+        assert!(HttpRequest::read("\r\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn only_names_that_cant_be_rebound_are_allowed() {
+        let allowed = |host: &str, port| request(&format!("GET / HTTP/1.1\r\nHost: {host}\r\n\r\n")).allowed_host(port);
+
+        assert!(allowed("localhost:8080", 8080));
+        assert!(allowed("LocalHost:8080", 8080));
+        assert!(allowed("127.0.0.1:8080", 8080));
+        assert!(allowed("192.168.1.20:8080", 8080));
+        assert!(allowed("[::1]:8080", 8080));
+        assert!(allowed("localhost", 80));
+
+        assert!(!allowed("localhost:8081", 8080));
+        assert!(!allowed("localhost", 8080));
+        assert!(!allowed("[::1]", 8080));
+        assert!(!allowed("attacker.example:8080", 8080));
+        assert!(!allowed("localhost.attacker.example:8080", 8080));
+        assert!(!request("GET / HTTP/1.1\r\n\r\n").allowed_host(80));
+    }
+
+    #[test]
+    fn pages_on_other_sites_are_turned_away() {
+        let same_origin = |head: &str| request(&format!("{head}\r\nHost: localhost:8080\r\n\r\n")).same_origin();
+
+        assert!(same_origin("GET /status HTTP/1.1"));
+        assert!(same_origin("POST /profile HTTP/1.1\r\nOrigin: http://localhost:8080"));
+        assert!(same_origin("POST /profile HTTP/1.1\r\nAuthorization: Bearer secret"));
+
+        assert!(!same_origin("POST /profile HTTP/1.1"));
+        assert!(!same_origin("GET /events HTTP/1.1\r\nUpgrade: websocket"));
+        assert!(!same_origin("POST /profile HTTP/1.1\r\nOrigin: http://attacker.example"));
+        assert!(!same_origin("GET /events HTTP/1.1\r\nUpgrade: websocket\r\nOrigin: http://localhost:8080.attacker.example"));
+        assert!(!same_origin("POST /profile HTTP/1.1\r\nOrigin: null"));
+    }
+
+    #[test]
+    fn takes_the_token_from_the_header_or_the_query() {
+        let authorized = |target: &str, headers: &str| request(&format!("GET {target} HTTP/1.1\r\n{headers}\r\n")).authorized("s3cr%t");
+
+        assert!(authorized("/", "Authorization: Bearer s3cr%t\r\n"));
+        assert!(authorized("/events?token=s3cr%25t", ""));
+
+        assert!(!authorized("/", ""));
+        assert!(!authorized("/", "Authorization: Bearer s3cr\r\n"));
+        assert!(!authorized("/", "Authorization: Basic s3cr%t\r\n"));
+        assert!(!authorized("/events?token=wrong", ""));
+
+        // Without a token set, anyone allowed to connect is
+        assert!(request("GET / HTTP/1.1\r\n\r\n").authorized(""));
+    }
+
+    #[test]
+    fn speaks_websocket() {
+        // The example handshake from RFC 6455
+        assert_eq!(websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        for len in [0, 125, 126, 0xFFFF, 0x10000] {
+            let frame = websocket_frame(0x1, &vec![b'a'; len]);
+            let frames = [frame.as_slice(), &websocket_frame(0x8, &[])].concat();
+            let mut reader = frames.as_slice();
+            assert_eq!(read_websocket_frame(&mut reader).unwrap(), 0x1);
+            assert_eq!(read_websocket_frame(&mut reader).unwrap(), 0x8);
+            assert!(reader.is_empty());
+        }
+
+        // A masked close frame from a client, with a two byte status code
+        let mut reader: &[u8] = &[0x88, 0x82, 1, 2, 3, 4, 0x02, 0xEA];
+        assert_eq!(read_websocket_frame(&mut reader).unwrap(), 0x8);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn encodes_and_decodes_text() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+
+        assert_eq!(percent_decode("caf%C3%A9%20au%2"), "café au%2");
+        assert_eq!(percent_decode("%zz%"), "%zz%");
+    }
+
+    /// A client that only takes what is written once told to, failing when it can't be told anymore
+    struct Stalled {
+        release: Receiver<()>,
+        written: Sender<String>,
+    }
+
+    impl Write for Stalled {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.release.recv().map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            // Statuses are long enough for two more bytes of length, frames aren't
+            let header = if buf[1] == 126 { 4 } else { 2 };
+            let _ = self.written.send(String::from_utf8_lossy(&buf[header..]).into_owned());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn stalled() -> (StreamClient, Sender<()>, Receiver<String>) {
+        let (release_tx, release) = crossbeam_channel::unbounded();
+        let (written_tx, written) = crossbeam_channel::unbounded();
+        (StreamClient::spawn(Stalled { release, written: written_tx }), release_tx, written)
+    }
+
+    fn status() -> Status {
+        Status {
+            profile: Profile::default(),
+            custom_effect: None,
+            lights_on: true,
+            paused: false,
+        }
+    }
+
+    #[test]
+    fn a_stalled_client_holds_nobody_up() {
+        let (client, _release, _written) = stalled();
+        assert!(client.send_status(&status()));
+
+        let started = Instant::now();
+        assert!((0..10_000).all(|i| client.send_frame([i as u8; 12])));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // One status is being written, the rest wait their turn until there's no room left
+        let queued = (0..STREAM_BACKLOG + 1).filter(|_| client.send_status(&status())).count();
+        assert!((STREAM_BACKLOG - 1..=STREAM_BACKLOG).contains(&queued), "{queued} queued");
+        assert!(!client.send_status(&status()));
+    }
+
+    #[test]
+    fn a_client_that_fell_behind_only_gets_the_newest_frame() {
+        let (client, release, written) = stalled();
+        assert!(client.send_status(&status()));
+        for colors in 1..=3 {
+            assert!(client.send_frame([colors; 12]));
+        }
+
+        for _ in 0..10 {
+            release.send(()).unwrap();
+        }
+        drop(client);
+
+        let written: Vec<String> = written.iter().collect();
+        assert_eq!(written.len(), 2, "{written:?}");
+        assert!(written[0].starts_with(r#"{"type":"status""#));
+        assert_eq!(written[1], r#"{"type":"frame","colors":[3,3,3,3,3,3,3,3,3,3,3,3]}"#);
+    }
+
+    #[test]
+    fn a_client_that_went_away_is_dropped() {
+        let (client, release, _written) = stalled();
+        drop(release);
+        assert!(client.send_status(&status()));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.send_frame([0; 12]) {
+            assert!(Instant::now() < deadline);
+        }
+        assert!(!client.send_status(&status()));
+    }
+}
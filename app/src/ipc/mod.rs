@@ -0,0 +1,274 @@
+//! Lets other instances of the program, and scripts, control the one that owns the keyboard
+//!
+//! Messages are JSON objects, one per line, each carrying the version of the protocol they follow. Requests get
+//! exactly one response, except for subscribing, after which the state is sent every time it changes.
+
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use error_stack::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    enums::Action,
+    manager::{custom_effect::CustomEffect, profile::Profile, Preview},
+};
+
+#[cfg(target_os = "linux")]
+mod dbus;
+mod http;
+mod mqtt;
+#[cfg(unix)]
+mod unix;
+#[cfg(not(unix))]
+mod unsupported;
+
+use http::Api;
+use mqtt::Mqtt;
+#[cfg(unix)]
+pub use unix::Client;
+#[cfg(unix)]
+use unix::Socket;
+#[cfg(not(unix))]
+pub use unsupported::Client;
+#[cfg(not(unix))]
+use unsupported::Socket;
+
+/// Bumped whenever a message changes in a way older instances wouldn't understand
+pub const PROTOCOL_VERSION: u32 = 1;
+/// How long a connection waits on the owner of the keyboard before giving up on a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum IpcError {
+    #[error("Could not connect to a running instance")]
+    Connect,
+    #[error("Could not listen for other instances")]
+    Listen,
+    #[error("Could not understand the other end")]
+    Protocol,
+    #[error("Could not talk to the MQTT broker")]
+    Broker,
+    #[cfg(not(unix))]
+    #[error("Talking to other instances is not supported on this platform")]
+    Unsupported,
+}
+
+/// What goes over the socket, tagged with the version of the protocol
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope<T> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub body: T,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Show a profile, which doesn't have to be a saved one
+    SetProfile {
+        profile: Box<Profile>,
+    },
+    CustomEffect {
+        effect: CustomEffect,
+    },
+    /// Do anything a hotkey can
+    Action {
+        action: Action,
+    },
+    Status,
+    /// Receive the status now and every time it changes, for as long as the connection is open
+    Subscribe,
+    Profiles,
+    /// Save a profile, replacing the one with the same name if there is one
+    SaveProfile {
+        profile: Box<Profile>,
+    },
+    CustomEffects,
+    /// Save a custom effect, replacing the one with the same name if there is one
+    SaveCustomEffect {
+        effect: CustomEffect,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Status { status: Box<Status> },
+    Profiles { profiles: Vec<Profile> },
+    CustomEffects { effects: Vec<CustomEffect> },
+    Error { message: String },
+}
+
+/// What the instance that owns the keyboard is showing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Status {
+    pub profile: Profile,
+    /// The name of the custom effect playing over the profile, if one is
+    pub custom_effect: Option<String>,
+    pub lights_on: bool,
+    pub paused: bool,
+}
+
+/// A request along with where its response goes
+pub type Call = (Request, Sender<Response>);
+
+/// Takes requests for whoever owns the keyboard, from other instances and scripts and whatever else is turned on
+pub struct Server {
+    socket: Socket,
+    #[cfg(target_os = "linux")]
+    bus: Option<dbus::Service>,
+    /// Kept to hand to the HTTP API and MQTT whenever they are started again
+    tx: Sender<Call>,
+    api: Option<Api>,
+    mqtt: Option<Mqtt>,
+}
+
+impl Server {
+    pub fn start() -> Result<(Self, Receiver<Call>), IpcError> {
+        let (tx, rx) = crossbeam_channel::unbounded::<Call>();
+        let socket = Socket::start(tx.clone())?;
+
+        // The socket is enough to go on with, so the bus not being there shouldn't stop anything
+        #[cfg(target_os = "linux")]
+        let bus = dbus::Service::start(tx.clone()).inspect_err(|err| eprintln!("{err:?}")).ok();
+
+        Ok((
+            Self {
+                socket,
+                #[cfg(target_os = "linux")]
+                bus,
+                tx,
+                api: None,
+                mqtt: None,
+            },
+            rx,
+        ))
+    }
+
+    /// Tell everyone listening what is being shown now
+    pub fn broadcast(&self, status: &Status) {
+        self.socket.broadcast(status);
+
+        #[cfg(target_os = "linux")]
+        if let Some(bus) = &self.bus {
+            bus.publish(status);
+        }
+
+        if let Some(api) = &self.api {
+            api.broadcast(status);
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.broadcast(status);
+        }
+    }
+
+    /// Start the HTTP API over with new settings, or stop it if they say so
+    pub fn serve_api(&mut self, settings: &ApiSettings, preview: Preview) -> Result<(), IpcError> {
+        // The old one has to let go of its address first
+        self.api = None;
+
+        if settings.enabled {
+            self.api = Some(Api::start(settings, self.tx.clone(), preview)?);
+        }
+
+        Ok(())
+    }
+
+    /// Connect to the MQTT broker over again with new settings, or stay away from it if they say so
+    pub fn serve_mqtt(&mut self, settings: &MqttSettings) {
+        // Dropping the old connection is what makes it go offline
+        self.mqtt = None;
+
+        if settings.enabled {
+            self.mqtt = Some(Mqtt::start(settings, self.tx.clone()));
+        }
+    }
+}
+
+/// The HTTP API, which is off unless asked for
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub address: String,
+    /// Needed on every request when set, and has to be for addresses other machines can reach
+    pub token: String,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:7878".to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+/// Home Assistant, through an MQTT broker, which is off unless asked for
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// Left empty for brokers that let anyone in
+    pub username: String,
+    pub password: String,
+    /// Where Home Assistant looks for new devices
+    pub discovery_prefix: String,
+    /// Where the state of the keyboard goes and commands for it come from
+    pub base_topic: String,
+    /// Keep the last state on the broker for whoever subscribes later
+    pub retain: bool,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            username: String::new(),
+            password: String::new(),
+            discovery_prefix: "homeassistant".to_string(),
+            base_topic: "legion-kb-rgb".to_string(),
+            retain: true,
+        }
+    }
+}
+
+/// Hand a request to the owner of the keyboard and wait for what it says
+fn call(tx: &Sender<Call>, request: Request) -> Response {
+    let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+
+    if tx.send((request, reply_tx)).is_err() {
+        return Response::Error {
+            message: "The instance is shutting down".to_string(),
+        };
+    }
+
+    reply_rx.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(|_| Response::Error {
+        message: "The instance did not answer in time".to_string(),
+    })
+}
+
+/// Save `item` over the one with the same name, or after the others if there isn't one
+pub fn save_named<T>(items: &mut Vec<T>, item: T, name: fn(&T) -> Option<&String>) -> Response {
+    let Some(item_name) = name(&item).filter(|item_name| !item_name.is_empty()).cloned() else {
+        return Response::Error {
+            message: "Only items with a name can be saved".to_string(),
+        };
+    };
+
+    match items.iter_mut().find(|saved| name(saved) == Some(&item_name)) {
+        Some(saved) => *saved = item,
+        None => items.push(item),
+    }
+
+    Response::Ok
+}
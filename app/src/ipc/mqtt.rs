@@ -0,0 +1,715 @@
+//! Shows the keyboard in Home Assistant as a light, through an MQTT broker and its discovery
+//!
+//! Only the parts of MQTT 3.1.1 that takes: a single connection, messages sent at most once and a last will.
+
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{select, Receiver, RecvTimeoutError, Sender};
+use error_stack::{Report, Result, ResultExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use strum::IntoEnumIterator;
+
+use crate::{
+    enums::{Action, Brightness, Effects},
+    manager::profile::Profile,
+};
+
+use super::{call, Call, IpcError, MqttSettings, Request, Response, Status};
+
+/// How long the broker goes without hearing from the connection before giving up on it, which pings are sent well within
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait before connecting again, after the broker couldn't be reached or the connection was lost
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+/// The keyboard as far as Home Assistant is concerned
+const OBJECT_ID: &str = "legion_kb_rgb";
+
+/// Talks to the broker until dropped
+pub struct Mqtt {
+    statuses: Sender<Status>,
+}
+
+impl Mqtt {
+    pub fn start(settings: &MqttSettings, tx: Sender<Call>) -> Self {
+        let (statuses_tx, statuses_rx) = crossbeam_channel::unbounded();
+
+        let settings = settings.clone();
+        thread::spawn(move || run(&settings, &tx, &statuses_rx));
+
+        Self { statuses: statuses_tx }
+    }
+
+    /// Tell Home Assistant what is being shown now
+    pub fn broadcast(&self, status: &Status) {
+        let _ = self.statuses.send(status.clone());
+    }
+}
+
+/// Stay connected to the broker for as long as statuses keep coming
+fn run(settings: &MqttSettings, tx: &Sender<Call>, statuses: &Receiver<Status>) {
+    // Only the first of a run of failures is worth printing
+    let mut reported = false;
+    let mut warned = false;
+    // Brokers drop whoever was connected with the same id, such as another instance or another machine using the same topic
+    let client_id = format!("legionkbrgb{:08x}", rand::random::<u32>());
+
+    loop {
+        match Session::connect(settings, &client_id) {
+            Ok(mut session) => {
+                reported = false;
+                if !settings.password.is_empty() && !warned && session.stream.peer_addr().is_ok_and(|address| !address.ip().is_loopback()) {
+                    eprintln!("The MQTT password is sent to {} unencrypted, as connecting over TLS isn't supported", settings.host);
+                    warned = true;
+                }
+
+                if session.serve(tx, statuses) == End::Stopped {
+                    return;
+                }
+            }
+            Err(err) if !reported => {
+                eprintln!("{err:?}");
+                reported = true;
+            }
+            Err(_) => {}
+        }
+
+        loop {
+            match statuses.recv_timeout(RETRY_INTERVAL) {
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum End {
+    /// Nothing is left to tell the broker
+    Stopped,
+    /// The broker went away or stopped answering
+    Lost,
+}
+
+struct Topics {
+    config: String,
+    state: String,
+    command: String,
+    availability: String,
+    /// Where Home Assistant says it came back online, after which it has to be told about the keyboard again
+    home_assistant: String,
+}
+
+impl Topics {
+    fn new(settings: &MqttSettings) -> Self {
+        let base = &settings.base_topic;
+        Self {
+            config: format!("{}/light/{OBJECT_ID}/config", settings.discovery_prefix),
+            state: format!("{base}/state"),
+            command: format!("{base}/set"),
+            availability: format!("{base}/availability"),
+            home_assistant: format!("{}/status", settings.discovery_prefix),
+        }
+    }
+}
+
+/// What Home Assistant asks for, in the JSON schema of its MQTT lights
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Command {
+    state: Option<String>,
+    brightness: Option<u8>,
+    color: Option<Color>,
+    effect: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Color {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+struct Session {
+    stream: TcpStream,
+    topics: Topics,
+    retain: bool,
+    /// The state last sent, since not everything that changes in a status shows up in it
+    published: Option<String>,
+}
+
+impl Session {
+    fn connect(settings: &MqttSettings, client_id: &str) -> Result<Self, IpcError> {
+        let addresses: Vec<SocketAddr> = (settings.host.as_str(), settings.port).to_socket_addrs().change_context(IpcError::Broker)?.collect();
+        let mut stream = connect_any(&addresses).attach_printable_lazy(|| format!("Could not reach the MQTT broker at {}:{}", settings.host, settings.port))?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT)).change_context(IpcError::Broker)?;
+
+        let topics = Topics::new(settings);
+        stream.write_all(&connect_packet(settings, client_id, &topics)).change_context(IpcError::Broker)?;
+
+        let (kind, body) = read_packet(&mut stream).change_context(IpcError::Broker)?;
+        if kind != 0x20 || body.get(1) != Some(&0) {
+            return Err(Report::new(IpcError::Broker).attach_printable(format!("The MQTT broker refused the connection with code {:?}", body.get(1))));
+        }
+
+        // The broker answers pings well within this, so not hearing anything for longer means it's gone
+        stream.set_read_timeout(Some(KEEP_ALIVE)).change_context(IpcError::Broker)?;
+
+        Ok(Self {
+            stream,
+            topics,
+            retain: settings.retain,
+            published: None,
+        })
+    }
+
+    fn serve(&mut self, tx: &Sender<Call>, statuses: &Receiver<Status>) -> End {
+        let end = self.serve_until_done(tx, statuses).unwrap_or(End::Lost);
+        let _ = self.stream.shutdown(Shutdown::Both);
+        end
+    }
+
+    fn serve_until_done(&mut self, tx: &Sender<Call>, statuses: &Receiver<Status>) -> io::Result<End> {
+        let Response::Status { status } = call(tx, Request::Status) else {
+            return Ok(End::Stopped);
+        };
+        let mut status = *status;
+        let mut profiles = saved_profiles(tx);
+
+        let (incoming_tx, incoming) = crossbeam_channel::unbounded();
+        let mut reader = self.stream.try_clone()?;
+        thread::spawn(move || {
+            while let Ok(packet) = read_packet(&mut reader) {
+                if incoming_tx.send(packet).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let topics = [self.topics.command.as_str(), self.topics.home_assistant.as_str()];
+        self.stream.write_all(&subscribe_packet(&topics))?;
+        self.announce(&status, &profiles)?;
+
+        let pings = crossbeam_channel::tick(KEEP_ALIVE / 2);
+        loop {
+            select! {
+                recv(statuses) -> next => {
+                    let Ok(next) = next else {
+                        self.publish(&self.topics.availability, b"offline", self.retain)?;
+                        self.stream.write_all(&[0xE0, 0x00])?;
+                        return Ok(End::Stopped);
+                    };
+
+                    status = next;
+                    if !self.refresh_profiles(tx, &status, &mut profiles)? {
+                        self.publish_state(&status, &profiles)?;
+                    }
+                }
+                recv(incoming) -> packet => {
+                    let Ok((kind, body)) = packet else {
+                        return Ok(End::Lost);
+                    };
+
+                    match parse_publish(kind, &body) {
+                        Some((topic, payload)) if topic == self.topics.command => {
+                            let command = serde_json::from_slice(payload).unwrap_or_default();
+                            run_command(&command, &status, &profiles, tx);
+                        }
+                        Some((topic, payload)) if topic == self.topics.home_assistant && payload == b"online" => self.announce(&status, &profiles)?,
+                        _ => {}
+                    }
+                }
+                recv(pings) -> _ => {
+                    self.stream.write_all(&[0xC0, 0x00])?;
+                    // Saving a profile doesn't change what is shown, so nothing else would bring it up
+                    self.refresh_profiles(tx, &status, &mut profiles)?;
+                }
+            }
+        }
+    }
+
+    /// Announce the keyboard again if profiles were saved or removed, since they show up in the list of effects
+    fn refresh_profiles(&mut self, tx: &Sender<Call>, status: &Status, profiles: &mut Vec<Profile>) -> io::Result<bool> {
+        let saved = saved_profiles(tx);
+        if profile_names(&saved).eq(profile_names(profiles)) {
+            return Ok(false);
+        }
+
+        *profiles = saved;
+        self.announce(status, profiles)?;
+        Ok(true)
+    }
+
+    /// Tell Home Assistant about the keyboard and what it's showing
+    fn announce(&mut self, status: &Status, profiles: &[Profile]) -> io::Result<()> {
+        // Effects can't be listed twice, and a profile with the same name as one would take its place anyway
+        let builtin: Vec<String> = Effects::iter().map(|effect| effect.to_string()).collect();
+        let saved = profile_names(profiles).filter(|name| !builtin.iter().any(|effect| effect == name)).map(str::to_string);
+        let effects: Vec<String> = builtin.iter().cloned().chain(saved).collect();
+
+        let config = json!({
+            "name": null,
+            "unique_id": OBJECT_ID,
+            "object_id": OBJECT_ID,
+            "schema": "json",
+            "command_topic": self.topics.command,
+            "state_topic": self.topics.state,
+            "availability_topic": self.topics.availability,
+            "brightness": true,
+            "brightness_scale": 2,
+            "supported_color_modes": ["rgb"],
+            "effect": true,
+            "effect_list": effects,
+            "device": {
+                "identifiers": [OBJECT_ID],
+                "name": "Legion Keyboard",
+                "manufacturer": "Lenovo",
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+
+        self.publish(&self.topics.config, config.to_string().as_bytes(), true)?;
+        self.publish(&self.topics.availability, b"online", self.retain)?;
+        self.published = None;
+        self.publish_state(status, profiles)
+    }
+
+    fn publish_state(&mut self, status: &Status, profiles: &[Profile]) -> io::Result<()> {
+        let state = state(status, profiles).to_string();
+        if self.published.as_ref() == Some(&state) {
+            return Ok(());
+        }
+
+        self.publish(&self.topics.state, state.as_bytes(), self.retain)?;
+        self.published = Some(state);
+        Ok(())
+    }
+
+    fn publish(&self, topic: &str, payload: &[u8], retain: bool) -> io::Result<()> {
+        let mut body = Vec::new();
+        push_str(&mut body, topic);
+        body.extend(payload);
+
+        (&self.stream).write_all(&packet(0x30 | u8::from(retain), &body))
+    }
+}
+
+/// Connect to the first of the addresses that answers, such as IPv4 when the broker doesn't listen on IPv6
+fn connect_any(addresses: &[SocketAddr]) -> Result<TcpStream, IpcError> {
+    let mut error = Report::new(IpcError::Broker).attach_printable("The host doesn't point anywhere");
+
+    for address in addresses {
+        match TcpStream::connect_timeout(address, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => error = Report::new(err).change_context(IpcError::Broker).attach_printable(format!("Could not connect to {address}")),
+        }
+    }
+
+    Err(error)
+}
+
+fn saved_profiles(tx: &Sender<Call>) -> Vec<Profile> {
+    match call(tx, Request::Profiles) {
+        Response::Profiles { profiles } => profiles,
+        _ => Vec::new(),
+    }
+}
+
+fn profile_names(profiles: &[Profile]) -> impl Iterator<Item = &str> {
+    profiles.iter().filter_map(|profile| profile.name.as_deref())
+}
+
+/// The state of the light, with the effect being the saved profile that is showing if there is one
+fn state(status: &Status, profiles: &[Profile]) -> Value {
+    let profile = &status.profile;
+    let saved = profile.name.as_deref().filter(|name| profile_names(profiles).any(|saved| saved == *name));
+    let [r, g, b] = profile.rgb_zones[0].rgb;
+
+    json!({
+        "state": if status.lights_on { "ON" } else { "OFF" },
+        "brightness": if profile.brightness == Brightness::High { 2 } else { 1 },
+        "color_mode": "rgb",
+        "color": { "r": r, "g": g, "b": b },
+        "effect": saved.map_or_else(|| profile.effect.to_string(), str::to_string),
+    })
+}
+
+fn run_command(command: &Command, status: &Status, profiles: &[Profile], tx: &Sender<Call>) {
+    if let Some(state) = &command.state {
+        if (state == "ON") != status.lights_on {
+            call(tx, Request::Action { action: Action::ToggleLights });
+        }
+    }
+
+    if command.brightness.is_none() && command.color.is_none() && command.effect.is_none() {
+        return;
+    }
+
+    let saved = command.effect.as_ref().and_then(|name| profiles.iter().find(|profile| profile.name.as_ref() == Some(name)));
+    let mut profile = saved.unwrap_or(&status.profile).clone();
+
+    if let Some(effect) = command.effect.as_ref().filter(|_| saved.is_none()) {
+        // Picking the effect already showing shouldn't throw away how it was set up
+        if let Some(effect) = Effects::iter().find(|builtin| builtin.to_string() == *effect && builtin.to_string() != profile.effect.to_string()) {
+            profile.effect = effect;
+        }
+    }
+    if let Some(brightness) = command.brightness {
+        profile.brightness = if brightness > 1 { Brightness::High } else { Brightness::Low };
+    }
+    if let Some(Color { r, g, b }) = command.color {
+        for zone in &mut profile.rgb_zones {
+            zone.rgb = [r, g, b];
+        }
+    }
+
+    call(tx, Request::SetProfile { profile: Box::new(profile) });
+}
+
+fn connect_packet(settings: &MqttSettings, client_id: &str, topics: &Topics) -> Vec<u8> {
+    // Start over each time, with a last will marking the keyboard as gone if the connection drops
+    let mut flags = 0x02 | 0x04;
+    if settings.retain {
+        flags |= 0x20;
+    }
+    if !settings.username.is_empty() {
+        flags |= 0x80;
+    }
+    if !settings.password.is_empty() {
+        flags |= 0x40;
+    }
+
+    let mut body = Vec::new();
+    push_str(&mut body, "MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend((KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    push_str(&mut body, client_id);
+    push_str(&mut body, &topics.availability);
+    push_str(&mut body, "offline");
+    if !settings.username.is_empty() {
+        push_str(&mut body, &settings.username);
+    }
+    if !settings.password.is_empty() {
+        push_str(&mut body, &settings.password);
+    }
+
+    packet(0x10, &body)
+}
+
+fn subscribe_packet(topics: &[&str]) -> Vec<u8> {
+    let mut body = 1u16.to_be_bytes().to_vec();
+    for topic in topics {
+        push_str(&mut body, topic);
+        body.push(0);
+    }
+
+    packet(0x82, &body)
+}
+
+/// The topic and payload of a message from the broker, if that's what the packet is
+fn parse_publish(kind: u8, body: &[u8]) -> Option<(&str, &[u8])> {
+    if kind & 0xF0 != 0x30 {
+        return None;
+    }
+
+    let len = usize::from(u16::from_be_bytes([*body.first()?, *body.get(1)?]));
+    let topic = std::str::from_utf8(body.get(2..2 + len)?).ok()?;
+    // Messages sent more reliably than asked for come with an id first
+    let id_len = if kind & 0x06 == 0 { 0 } else { 2 };
+
+    Some((topic, body.get(2 + len + id_len..)?))
+}
+
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+
+    packet.extend(body);
+    packet
+}
+
+fn read_packet(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut kind = [0];
+    reader.read_exact(&mut kind)?;
+
+    let mut len = 0;
+    for shift in (0..28).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        len |= usize::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+
+    Ok((kind[0], body))
+}
+
+fn push_str(buffer: &mut Vec<u8>, text: &str) {
+    buffer.extend((text.len() as u16).to_be_bytes());
+    buffer.extend(text.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        io::Write,
+        net::TcpStream,
+        process, thread,
+        time::{Duration, Instant},
+    };
+
+    use crossbeam_channel::{Receiver, Sender};
+
+    use super::{connect_packet, packet, parse_publish, push_str, read_packet, run_command, state, subscribe_packet, Command, Mqtt, Topics};
+    use crate::{
+        enums::{Action, Brightness, Effects},
+        ipc::{Call, MqttSettings, Request, Response, Status},
+        manager::profile::Profile,
+    };
+
+    fn status(lights_on: bool) -> Status {
+        Status {
+            profile: Profile::default(),
+            custom_effect: None,
+            lights_on,
+            paused: false,
+        }
+    }
+
+    fn named(name: &str) -> Profile {
+        Profile {
+            name: Some(name.to_string()),
+            ..Profile::default()
+        }
+    }
+
+    /// A thread standing in for the owner of the keyboard, passing on every request it gets
+    fn owner(status: Status, profiles: Vec<Profile>) -> (Sender<Call>, Receiver<Request>) {
+        let (tx, rx) = crossbeam_channel::unbounded::<Call>();
+        let (requests_tx, requests) = crossbeam_channel::unbounded();
+
+        thread::spawn(move || {
+            for (request, reply) in rx {
+                let response = match request {
+                    Request::Status => Response::Status { status: Box::new(status.clone()) },
+                    Request::Profiles => Response::Profiles { profiles: profiles.clone() },
+                    _ => Response::Ok,
+                };
+                let _ = reply.send(response);
+                let _ = requests_tx.send(request);
+            }
+        });
+
+        (tx, requests)
+    }
+
+    fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        push_str(&mut body, topic);
+        body.extend(payload);
+        packet(0x30, &body)
+    }
+
+    #[test]
+    fn packets_survive_a_round_trip() {
+        // The remaining length takes one more byte past each of these
+        for (len, len_bytes) in [(0, 1), (127, 1), (128, 2), (16_383, 2), (16_384, 3), (2_097_152, 4)] {
+            let body = vec![0xAB; len];
+            let encoded = packet(0x30, &body);
+            assert_eq!(encoded.len(), 1 + len_bytes + len);
+
+            let (kind, decoded) = read_packet(&mut encoded.as_slice()).unwrap();
+            assert_eq!(kind, 0x30);
+            assert_eq!(decoded, body);
+        }
+
+        // Cut off partway through
+        assert!(read_packet(&mut &packet(0x30, &[1, 2, 3])[..4]).is_err());
+    }
+
+    #[test]
+    fn reads_messages_at_any_qos() {
+        let (kind, body) = read_packet(&mut publish_packet("legion-kb-rgb/set", b"{}").as_slice()).unwrap();
+        assert_eq!(parse_publish(kind, &body), Some(("legion-kb-rgb/set", &b"{}"[..])));
+
+        // At least once, retained, with the id the broker gave it
+        let mut body = Vec::new();
+        push_str(&mut body, "homeassistant/status");
+        body.extend([0x12, 0x34]);
+        body.extend(b"online");
+        assert_eq!(parse_publish(0x33, &body), Some(("homeassistant/status", &b"online"[..])));
+
+        assert_eq!(parse_publish(0x90, &body), None);
+        assert_eq!(parse_publish(0x30, &[0, 10, b'a']), None);
+        assert_eq!(parse_publish(0x30, &[0]), None);
+    }
+
+    #[test]
+    fn connects_with_a_last_will_and_only_the_credentials_given() {
+        let mut settings = MqttSettings {
+            username: "user".to_string(),
+            retain: false,
+            ..MqttSettings::default()
+        };
+        let topics = Topics::new(&settings);
+
+        let (kind, body) = read_packet(&mut connect_packet(&settings, "legionkbrgb0000abcd", &topics).as_slice()).unwrap();
+        assert_eq!(kind, 0x10);
+        assert_eq!(&body[..7], b"\0\x04MQTT\x04");
+        // A clean session with a will, and a username but no password
+        assert_eq!(body[7], 0x02 | 0x04 | 0x80);
+        assert_eq!(&body[8..10], &30u16.to_be_bytes());
+
+        let mut expected = Vec::new();
+        for field in ["legionkbrgb0000abcd", "legion-kb-rgb/availability", "offline", "user"] {
+            push_str(&mut expected, field);
+        }
+        assert_eq!(&body[10..], expected);
+
+        settings.password = "hunter2".to_string();
+        settings.retain = true;
+        let (_, body) = read_packet(&mut connect_packet(&settings, "id", &topics).as_slice()).unwrap();
+        assert_eq!(body[7], 0x02 | 0x04 | 0x20 | 0x80 | 0x40);
+        assert!(body.ends_with(b"\0\x07hunter2"));
+
+        let (kind, body) = read_packet(&mut subscribe_packet(&["a", "b/c"]).as_slice()).unwrap();
+        assert_eq!(kind, 0x82);
+        assert_eq!(body, b"\0\x01\0\x01a\0\0\x03b/c\0");
+    }
+
+    #[test]
+    fn state_names_the_saved_profile_showing() {
+        let mut status = status(false);
+        status.profile = named("Night");
+        status.profile.brightness = Brightness::High;
+        status.profile.rgb_zones[0].rgb = [1, 2, 3];
+
+        let shown = state(&status, &[named("Night")]);
+        assert_eq!(shown["state"], "OFF");
+        assert_eq!(shown["brightness"], 2);
+        assert_eq!(shown["color"]["g"], 2);
+        assert_eq!(shown["effect"], "Night");
+
+        // Once removed, it's only whatever effect it was
+        assert_eq!(state(&status, &[])["effect"], Effects::Static.to_string());
+    }
+
+    #[test]
+    fn commands_become_requests() {
+        let command = |json: &str, status: &Status, profiles: &[Profile]| {
+            let (tx, requests) = owner(status.clone(), profiles.to_vec());
+            let command: Command = serde_json::from_str(json).unwrap();
+            run_command(&command, status, profiles, &tx);
+            drop(tx);
+            requests.iter().collect::<Vec<_>>()
+        };
+
+        assert!(command(r#"{"state":"ON"}"#, &status(true), &[]).is_empty());
+        assert!(matches!(
+            command(r#"{"state":"ON"}"#, &status(false), &[]).as_slice(),
+            [Request::Action { action: Action::ToggleLights }]
+        ));
+
+        let requests = command(r#"{"state":"ON","color":{"r":9,"g":8,"b":7},"effect":"Night"}"#, &status(true), &[named("Night")]);
+        let [Request::SetProfile { profile }] = requests.as_slice() else {
+            panic!("unexpected {requests:?}");
+        };
+        assert_eq!(profile.name.as_deref(), Some("Night"));
+        assert!(profile.rgb_zones.iter().all(|zone| zone.rgb == [9, 8, 7]));
+
+        let requests = command(r#"{"effect":"Breath","brightness":1}"#, &status(true), &[]);
+        let [Request::SetProfile { profile }] = requests.as_slice() else {
+            panic!("unexpected {requests:?}");
+        };
+        assert!(matches!((profile.effect, profile.brightness), (Effects::Breath, Brightness::Low)));
+    }
+
+    /// Run with `cargo test -- --ignored` against a broker such as `mosquitto -p 1883`
+    #[test]
+    #[ignore = "needs an MQTT broker on localhost:1883, or at the address in LEGION_KEYBOARD_TEST_BROKER"]
+    fn follows_commands_through_a_broker() {
+        let broker = env::var("LEGION_KEYBOARD_TEST_BROKER").unwrap_or_else(|_| "localhost:1883".to_string());
+        let (host, port) = broker.rsplit_once(':').unwrap();
+        // Kept away from anything a real Home Assistant would pick up
+        let base_topic = format!("legion-kb-rgb-test-{}", process::id());
+        let settings = MqttSettings {
+            enabled: true,
+            host: host.to_string(),
+            port: port.parse().unwrap(),
+            discovery_prefix: format!("{base_topic}/discovery"),
+            base_topic,
+            retain: false,
+            ..MqttSettings::default()
+        };
+        let topics = Topics::new(&settings);
+
+        // Home Assistant, as far as the test is concerned
+        let mut watcher = TcpStream::connect(&broker).unwrap();
+        watcher.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let mut body = Vec::new();
+        push_str(&mut body, "MQTT");
+        body.extend([4, 0x02, 0, 30]);
+        push_str(&mut body, &format!("legionkbrgbtest{}", process::id()));
+        watcher.write_all(&packet(0x10, &body)).unwrap();
+        assert_eq!(read_packet(&mut watcher).unwrap(), (0x20, vec![0, 0]));
+        watcher.write_all(&subscribe_packet(&[&topics.state, &topics.availability])).unwrap();
+        assert_eq!(read_packet(&mut watcher).unwrap().0, 0x90);
+
+        let (tx, requests) = owner(status(true), vec![named("Night")]);
+        let mqtt = Mqtt::start(&settings, tx);
+
+        let mut reader = watcher.try_clone().unwrap();
+        let mut next_message = || loop {
+            let (kind, body) = read_packet(&mut reader).unwrap();
+            if let Some((topic, payload)) = parse_publish(kind, &body) {
+                break (topic.to_string(), String::from_utf8_lossy(payload).into_owned());
+            }
+        };
+        assert_eq!(next_message(), (topics.availability.clone(), "online".to_string()));
+        let (topic, shown) = next_message();
+        assert_eq!(topic, topics.state);
+        assert!(shown.contains(r#""state":"ON""#), "{shown}");
+
+        watcher.write_all(&publish_packet(&topics.command, br#"{"state":"OFF"}"#)).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            match requests.recv_deadline(deadline).unwrap() {
+                Request::Action { action: Action::ToggleLights } => break,
+                _ => continue,
+            }
+        }
+
+        // Going away on purpose is announced too, not only through the last will
+        drop(mqtt);
+        assert_eq!(next_message(), (topics.availability.clone(), "offline".to_string()));
+
+        // An empty retained message takes the one left by the discovery off the broker
+        let mut clear = publish_packet(&topics.config, b"");
+        clear[0] |= 0x01;
+        watcher.write_all(&clear).unwrap();
+        watcher.write_all(&[0xE0, 0x00]).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::Sender;
+use error_stack::{Report, Result, ResultExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{call, Call, Envelope, IpcError, Request, Response, Status, PROTOCOL_VERSION};
+
+const SOCKET_VAR: &str = "LEGION_KEYBOARD_SOCKET";
+/// Statuses waiting to be written to a subscriber, past which it's taken to have stopped reading and is dropped
+const SUBSCRIBER_BACKLOG: usize = 16;
+/// How long a subscriber gets to take a status before it's given up on
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the instance that owns the keyboard listens, which has to be somewhere other users can't get at
+fn socket_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(SOCKET_VAR) {
+        return Some(PathBuf::from(path));
+    }
+
+    env::var_os("XDG_RUNTIME_DIR").map(|runtime| PathBuf::from(runtime).join(format!("{}.sock", env!("CARGO_PKG_NAME"))))
+}
+
+/// Whether only this user can get into the directory, so nobody else can connect to the socket or put something in its place
+fn is_private(dir: &Path) -> bool {
+    fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_dir() && metadata.permissions().mode() & 0o077 == 0)
+}
+
+/// Listens for other instances, handing their requests to whoever owns the keyboard
+pub struct Socket {
+    path: PathBuf,
+    /// Each written to by its own connection thread, so a subscriber that stops reading can't hold anyone else up
+    subscribers: Arc<Mutex<Vec<Sender<String>>>>,
+}
+
+impl Socket {
+    pub fn start(tx: Sender<Call>) -> Result<Self, IpcError> {
+        let path = socket_path().ok_or_else(|| Report::new(IpcError::Listen).attach_printable(format!("Neither XDG_RUNTIME_DIR nor {SOCKET_VAR} is set")))?;
+        if !path.parent().is_some_and(is_private) {
+            return Err(Report::new(IpcError::Listen).attach_printable(format!("Other users can get into the directory {} would be in", path.display())));
+        }
+
+        // Only one instance can own the keyboard, so whatever is left there is from one that didn't exit cleanly
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                return Err(Report::new(IpcError::Listen).attach_printable(format!("Something other than a socket is at {}", path.display())));
+            }
+            if UnixStream::connect(&path).is_ok() {
+                return Err(Report::new(IpcError::Listen).attach_printable(format!("Something is already listening at {}", path.display())));
+            }
+            let _ = fs::remove_file(&path);
+        }
+
+        // The directory already keeps others out, this only keeps it that way if the socket is ever moved
+        let listener = UnixListener::bind(&path).change_context(IpcError::Listen)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).change_context(IpcError::Listen)?;
+
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+
+        let subscribers_c = subscribers.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                let subscribers = subscribers_c.clone();
+                thread::spawn(move || serve_connection(stream, &tx, &subscribers));
+            }
+        });
+
+        Ok(Self { path, subscribers })
+    }
+
+    /// Tell every subscriber what is being shown now
+    pub fn broadcast(&self, status: &Status) {
+        let line = encode(&Response::Status { status: Box::new(status.clone()) });
+        self.subscribers.lock().unwrap().retain(|subscriber| subscriber.try_send(line.clone()).is_ok());
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn serve_connection(stream: UnixStream, tx: &Sender<Call>, subscribers: &Mutex<Vec<Sender<String>>>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        let response = match serde_json::from_str::<Envelope<Request>>(&line) {
+            Ok(envelope) if envelope.version != PROTOCOL_VERSION => Response::Error {
+                message: format!("Unsupported protocol version {}, expected {PROTOCOL_VERSION}", envelope.version),
+            },
+            Ok(envelope) => {
+                let subscribe = matches!(envelope.body, Request::Subscribe);
+                let response = call(tx, envelope.body);
+
+                if subscribe && matches!(response, Response::Status { .. }) {
+                    // Anything else sent over a subscribed connection is ignored
+                    serve_subscriber(writer, &encode(&response), subscribers);
+                    return;
+                }
+
+                response
+            }
+            Err(err) => Response::Error { message: err.to_string() },
+        };
+
+        if writer.write_all(encode(&response).as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+/// Write every status to a subscriber until it goes away or falls too far behind
+fn serve_subscriber(mut writer: UnixStream, first: &str, subscribers: &Mutex<Vec<Sender<String>>>) {
+    if writer.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() || writer.write_all(first.as_bytes()).is_err() {
+        return;
+    }
+
+    let (lines_tx, lines) = crossbeam_channel::bounded(SUBSCRIBER_BACKLOG);
+    subscribers.lock().unwrap().push(lines_tx);
+
+    for line in lines {
+        if writer.write_all(line.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+/// A connection to the instance that owns the keyboard
+pub struct Client {
+    reader: BufReader<UnixStream>,
+}
+
+impl Client {
+    pub fn connect() -> Result<Self, IpcError> {
+        let path = socket_path().ok_or_else(|| Report::new(IpcError::Connect).attach_printable(format!("Neither XDG_RUNTIME_DIR nor {SOCKET_VAR} is set")))?;
+        let stream = UnixStream::connect(path).change_context(IpcError::Connect)?;
+        Ok(Self { reader: BufReader::new(stream) })
+    }
+
+    pub fn request(&mut self, request: &Request) -> Result<Response, IpcError> {
+        self.reader.get_mut().write_all(encode(request).as_bytes()).change_context(IpcError::Connect)?;
+        self.receive()
+    }
+
+    /// Every status sent from now on, starting with the current one, until the instance goes away
+    pub fn subscribe(mut self) -> Result<impl Iterator<Item = Status>, IpcError> {
+        let first = match self.request(&Request::Subscribe)? {
+            Response::Status { status } => *status,
+            response => return Err(Report::new(IpcError::Protocol).attach_printable(format!("Unexpected response: {response:?}"))),
+        };
+
+        let rest = std::iter::from_fn(move || match self.receive() {
+            Ok(Response::Status { status }) => Some(*status),
+            _ => None,
+        });
+
+        Ok(std::iter::once(first).chain(rest))
+    }
+
+    fn receive(&mut self) -> Result<Response, IpcError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).change_context(IpcError::Connect)? == 0 {
+            return Err(Report::new(IpcError::Connect).attach_printable("The instance closed the connection"));
+        }
+
+        let envelope: Envelope<Response> = decode(&line)?;
+        if envelope.version != PROTOCOL_VERSION {
+            return Err(Report::new(IpcError::Protocol).attach_printable(format!("The instance speaks version {} of the protocol, expected {PROTOCOL_VERSION}", envelope.version)));
+        }
+
+        Ok(envelope.body)
+    }
+}
+
+fn encode<T: Serialize>(body: &T) -> String {
+    let mut line = serde_json::to_string(&Envelope { version: PROTOCOL_VERSION, body }).unwrap();
+    line.push('\n');
+    line
+}
+
+fn decode<T: DeserializeOwned>(line: &str) -> Result<Envelope<T>, IpcError> {
+    serde_json::from_str(line).change_context(IpcError::Protocol)
+}
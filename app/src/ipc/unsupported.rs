@@ -0,0 +1,31 @@
+use crossbeam_channel::Sender;
+use error_stack::Result;
+
+use super::{Call, IpcError, Request, Response, Status};
+
+/// Nothing for other instances to reach here, which leaves the HTTP API and MQTT to control the keyboard with
+pub struct Socket;
+
+impl Socket {
+    pub fn start(_tx: Sender<Call>) -> Result<Self, IpcError> {
+        Ok(Self)
+    }
+
+    pub fn broadcast(&self, _status: &Status) {}
+}
+
+pub struct Client;
+
+impl Client {
+    pub fn connect() -> Result<Self, IpcError> {
+        Err(IpcError::Unsupported.into())
+    }
+
+    pub fn request(&mut self, _request: &Request) -> Result<Response, IpcError> {
+        Err(IpcError::Unsupported.into())
+    }
+
+    pub fn subscribe(self) -> Result<std::iter::Empty<Status>, IpcError> {
+        Err(IpcError::Unsupported.into())
+    }
+}
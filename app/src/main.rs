@@ -4,10 +4,17 @@
 mod cli;
 #[cfg(target_os = "windows")]
 mod console;
+mod controller;
+mod daemon;
 mod enums;
+mod focus;
 mod gui;
+mod hotkeys;
+mod input;
+mod ipc;
 mod manager;
 mod persist;
+mod schedule;
 mod tray;
 mod util;
 
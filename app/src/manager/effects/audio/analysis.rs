@@ -0,0 +1,82 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// Samples looked at on every frame
+pub const FFT_SIZE: usize = 2048;
+/// Edges of the bands shown on each zone, in Hz, from bass to treble
+const BAND_EDGES: [f32; 5] = [20.0, 150.0, 600.0, 2500.0, 16000.0];
+/// Range of volumes shown, anything quieter than this many decibels below full scale is considered silence
+const RANGE_DB: f32 = 60.0;
+
+/// Turns samples into levels from `0.0` to `1.0` that are easy to show on the keyboard
+pub struct Analyzer {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    sensitivity: f32,
+    smoothing: f32,
+    bands: [f32; 4],
+    volume: f32,
+}
+
+impl Analyzer {
+    pub fn new(sensitivity: f32, smoothing: f32) -> Self {
+        // Hann window, to keep the edges of the chunk from smearing energy across the spectrum
+        let window = (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos()).collect();
+
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window,
+            buffer: vec![Complex::default(); FFT_SIZE],
+            sensitivity: sensitivity.max(0.0),
+            smoothing: smoothing.clamp(0.0, 0.99),
+            bands: [0.0; 4],
+            volume: 0.0,
+        }
+    }
+
+    /// The energy of each band, bass first
+    pub fn bands(&mut self, samples: &[f32], sample_rate: u32) -> [f32; 4] {
+        for ((out, sample), weight) in self.buffer.iter_mut().zip(samples).zip(&self.window) {
+            *out = Complex::new(sample * weight, 0.0);
+        }
+
+        self.fft.process(&mut self.buffer);
+
+        let bin_width = sample_rate as f32 / FFT_SIZE as f32;
+        // Undo the scaling from both the transform and the window so a full scale sine ends up at 1.0
+        let normalization = 2.0 / self.window.iter().sum::<f32>();
+
+        for (band, edges) in BAND_EDGES.windows(2).enumerate() {
+            let start = ((edges[0] / bin_width) as usize).clamp(1, FFT_SIZE / 2 - 1);
+            let end = ((edges[1] / bin_width) as usize).clamp(start + 1, FFT_SIZE / 2);
+
+            let power = self.buffer[start..end].iter().map(Complex::norm_sqr).sum::<f32>();
+            let amplitude = power.sqrt() * normalization;
+
+            self.bands[band] = self.smooth(self.bands[band], self.level(amplitude));
+        }
+
+        self.bands
+    }
+
+    /// The overall volume of the most recent samples
+    pub fn volume(&mut self, samples: &[f32]) -> f32 {
+        // Only look at the last few milliseconds so the meter stays snappy
+        let recent = &samples[samples.len() - samples.len() / 4..];
+        let rms = (recent.iter().map(|sample| sample * sample).sum::<f32>() / recent.len().max(1) as f32).sqrt();
+
+        self.volume = self.smooth(self.volume, self.level(rms));
+        self.volume
+    }
+
+    fn level(&self, amplitude: f32) -> f32 {
+        let db = 20.0 * (amplitude * self.sensitivity).max(f32::MIN_POSITIVE).log10();
+        ((db + RANGE_DB) / RANGE_DB).clamp(0.0, 1.0)
+    }
+
+    fn smooth(&self, previous: f32, current: f32) -> f32 {
+        previous.mul_add(self.smoothing, current * (1.0 - self.smoothing))
+    }
+}
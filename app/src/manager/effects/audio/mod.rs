@@ -0,0 +1,83 @@
+use std::{sync::atomic::Ordering, thread, time::Duration};
+
+use crate::{
+    enums::{Direction, VisualizerMode},
+    manager::{profile::Profile, Inner},
+};
+
+use super::{clock, gradient};
+
+use analysis::{Analyzer, FFT_SIZE};
+use source::Capture;
+
+mod analysis;
+mod source;
+
+/// How long to wait before trying to open a source that couldn't be opened again
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+pub fn play(manager: &mut Inner, profile: &Profile, mode: VisualizerMode) {
+    let Some(capture) = open_capture(manager, profile) else {
+        return;
+    };
+
+    let samples = capture.samples();
+    let palette = profile.palette.sorted();
+    let direction = profile.direction;
+
+    let mut analyzer = Analyzer::new(profile.audio.sensitivity, profile.audio.smoothing);
+    let mut window = vec![0.0; FFT_SIZE];
+
+    match mode {
+        VisualizerMode::Spectrum => clock::run(manager, |_| {
+            samples.latest(&mut window);
+            let bands = analyzer.bands(&window, samples.sample_rate());
+
+            let mut frame = [0; 12];
+            for (i, zone) in frame.chunks_exact_mut(3).enumerate() {
+                // Bass starts on the side given by the direction
+                let band = match direction {
+                    Direction::Left => i,
+                    Direction::Right => 3 - i,
+                };
+
+                let rgb = palette.sample((i as f32 + 0.5) / 4.0).map(|channel| (channel * bands[band]).round() as u8);
+                zone.copy_from_slice(&rgb);
+            }
+
+            frame
+        }),
+        VisualizerMode::Meter => clock::run_canvas(manager, |canvas, _| {
+            samples.latest(&mut window);
+            let volume = analyzer.volume(&window);
+
+            gradient::draw_meter(canvas, &palette, direction, volume);
+        }),
+    }
+}
+
+/// Open the source, trying again with the lights off until it works or the effect is stopped, as a device may be plugged in or a file written later
+fn open_capture(manager: &mut Inner, profile: &Profile) -> Option<Capture> {
+    let mut reported = false;
+
+    loop {
+        match Capture::open(&profile.audio) {
+            Ok(capture) => return Some(capture),
+            // Only said once, as it'll most likely keep failing the same way
+            Err(err) if !reported => {
+                eprintln!("{:?}", err.attach_printable(format!("Showing nothing until the {:?} audio source can be opened", profile.audio.source)));
+                reported = true;
+            }
+            Err(_) => {}
+        }
+
+        manager.keyboard.set_colors_to(&[0; 12]).unwrap();
+
+        for _ in 0..RETRY_INTERVAL.as_millis() / 50 {
+            if manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+};
+use error_stack::{Report, Result, ResultExt};
+use thiserror::Error;
+
+use crate::{enums::AudioSource, manager::profile::AudioSettings};
+
+/// How many samples are kept around for analysis
+const BUFFER_LEN: usize = 8192;
+/// How often the WAV file source hands over new samples
+const FILE_CHUNK_INTERVAL: Duration = Duration::from_millis(10);
+/// How long the pipe source waits before looking for more samples, or for the pipe to show up
+const PIPE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long the pipe source waits before trying to open the pipe again
+const PIPE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Error)]
+#[error("Could not open the audio source")]
+pub struct AudioSourceError;
+
+/// The most recent samples of a source, mixed down to mono
+#[derive(Clone)]
+pub struct Samples {
+    inner: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+}
+
+impl Samples {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(VecDeque::with_capacity(BUFFER_LEN))),
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&self, samples: impl Iterator<Item = f32>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.extend(samples);
+
+        let excess = inner.len().saturating_sub(BUFFER_LEN);
+        inner.drain(..excess);
+    }
+
+    /// Push interleaved frames, averaging their channels
+    fn push_interleaved(&self, data: &[f32], channels: usize) {
+        let channels = channels.max(1);
+        self.push(data.chunks_exact(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32));
+    }
+
+    /// Copy the latest samples into `out`, padding the start with silence if there aren't enough yet
+    pub fn latest(&self, out: &mut [f32]) {
+        let inner = self.inner.lock().unwrap();
+
+        let available = inner.len().min(out.len());
+        let (silence, recent) = out.split_at_mut(out.len() - available);
+
+        silence.fill(0.0);
+        for (dst, src) in recent.iter_mut().zip(inner.range(inner.len() - available..)) {
+            *dst = *src;
+        }
+    }
+}
+
+/// A running audio source, which stops once dropped
+pub struct Capture {
+    samples: Samples,
+    /// Kept alive for as long as the capture runs
+    _stream: Option<Stream>,
+    stop: Arc<AtomicBool>,
+    /// Whatever is reading the file or pipe, waited on when dropped
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Capture {
+    pub fn open(settings: &AudioSettings) -> Result<Self, AudioSourceError> {
+        match settings.source {
+            AudioSource::Output | AudioSource::Input => Self::open_device(settings.source),
+            AudioSource::File => Self::open_file(Path::new(&settings.path)),
+            AudioSource::Pipe => Ok(Self::open_pipe(settings.path.clone(), settings.pipe_sample_rate, settings.pipe_channels)),
+        }
+    }
+
+    pub fn samples(&self) -> &Samples {
+        &self.samples
+    }
+
+    fn open_device(source: AudioSource) -> Result<Self, AudioSourceError> {
+        let host = cpal::default_host();
+
+        let device = if source == AudioSource::Output {
+            find_output_capture_device(&host)
+        } else {
+            host.default_input_device()
+        }
+        .ok_or_else(|| Report::new(AudioSourceError).attach_printable("No suitable audio device was found"))?;
+
+        let supported = if source == AudioSource::Output && cfg!(target_os = "windows") {
+            // Opening the output device for recording makes WASAPI hand over what is being played on it
+            device.default_output_config().change_context(AudioSourceError)?
+        } else {
+            device.default_input_config().change_context(AudioSourceError)?
+        };
+
+        let config = supported.config();
+        let samples = Samples::new(config.sample_rate.0);
+
+        let stream = match supported.sample_format() {
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, samples.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, samples.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, samples.clone()),
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, samples.clone()),
+            format => return Err(Report::new(AudioSourceError).attach_printable(format!("Unsupported sample format: {format}"))),
+        }?;
+
+        stream.play().change_context(AudioSourceError)?;
+
+        Ok(Self {
+            samples,
+            _stream: Some(stream),
+            stop: Arc::new(AtomicBool::new(false)),
+            reader: None,
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<Self, AudioSourceError> {
+        let reader = hound::WavReader::open(path)
+            .change_context(AudioSourceError)
+            .attach_printable_lazy(|| format!("Could not read {}", path.display()))?;
+
+        let spec = reader.spec();
+        let data: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<std::result::Result<_, _>>(),
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.into_samples::<i32>().map(|sample| sample.map(|sample| sample as f32 * scale)).collect()
+            }
+        }
+        .change_context(AudioSourceError)?;
+
+        let samples = Samples::new(spec.sample_rate);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let channels = usize::from(spec.channels.max(1));
+        let frames_per_chunk = (spec.sample_rate as usize * FILE_CHUNK_INTERVAL.as_millis() as usize / 1000).max(1);
+        let chunk_len = frames_per_chunk * channels;
+
+        let samples_c = samples.clone();
+        let stop_c = stop.clone();
+        let reader = thread::spawn(move || {
+            if data.len() < channels {
+                return;
+            }
+
+            let mut next = Instant::now();
+
+            // Loop the file in real time
+            for chunk in data.chunks(chunk_len).cycle() {
+                if stop_c.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                samples_c.push_interleaved(chunk, channels);
+
+                next += FILE_CHUNK_INTERVAL;
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+        });
+
+        Ok(Self {
+            samples,
+            _stream: None,
+            stop,
+            reader: Some(reader),
+        })
+    }
+
+    fn open_pipe(path: String, sample_rate: u32, channels: u16) -> Self {
+        let samples = Samples::new(sample_rate.max(1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let frame_len = 2 * usize::from(channels.max(1));
+        // Elsewhere stdin can only be read by waiting on it, so there'd be no telling when it stops
+        let joinable = cfg!(unix) || path != "-";
+
+        let samples_c = samples.clone();
+        let stop_c = stop.clone();
+        let reader = thread::spawn(move || {
+            // Neither opening nor reading ever waits, so stopping is never held up by a pipe nobody writes to
+            while !stop_c.load(Ordering::SeqCst) {
+                let Ok(mut pipe) = open_nonblocking(&path) else {
+                    sleep_unless_stopped(&stop_c, PIPE_RETRY_INTERVAL);
+                    continue;
+                };
+
+                let mut bytes = vec![0; frame_len * 256];
+                // Whatever is left of a frame that hasn't fully come through yet
+                let mut pending = Vec::new();
+
+                while !stop_c.load(Ordering::SeqCst) {
+                    match pipe.read(&mut bytes) {
+                        // Either nothing has been written yet, or the writer went away and another may come along
+                        Ok(0) => thread::sleep(PIPE_POLL_INTERVAL),
+                        Ok(len) => {
+                            pending.extend_from_slice(&bytes[..len]);
+
+                            let whole = pending.len() - pending.len() % frame_len;
+                            let data: Vec<f32> = pending[..whole].chunks_exact(2).map(|pair| f32::from(i16::from_le_bytes([pair[0], pair[1]])) / 32768.0).collect();
+                            samples_c.push_interleaved(&data, frame_len / 2);
+                            pending.drain(..whole);
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(PIPE_POLL_INTERVAL),
+                        Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                        Err(_) => {
+                            sleep_unless_stopped(&stop_c, PIPE_RETRY_INTERVAL);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            samples,
+            _stream: None,
+            stop,
+            reader: joinable.then_some(reader),
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Open a file or named pipe without waiting for a writer, `-` standing for stdin
+#[cfg(unix)]
+fn open_nonblocking(path: &str) -> io::Result<Box<dyn Read>> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+
+    // Opened over again so that reading it doesn't wait either
+    let path = if path == "-" { "/dev/stdin" } else { path };
+
+    Ok(Box::new(OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?))
+}
+
+#[cfg(not(unix))]
+fn open_nonblocking(path: &str) -> io::Result<Box<dyn Read>> {
+    if path == "-" {
+        return Ok(Box::new(io::stdin()));
+    }
+
+    Ok(Box::new(std::fs::File::open(path)?))
+}
+
+/// Sleep for up to `duration`, waking up early to stop
+fn sleep_unless_stopped(stop: &AtomicBool, duration: Duration) {
+    let until = Instant::now() + duration;
+
+    while !stop.load(Ordering::SeqCst) && Instant::now() < until {
+        thread::sleep(PIPE_POLL_INTERVAL);
+    }
+}
+
+/// Find something to record what is being played from
+fn find_output_capture_device(host: &cpal::Host) -> Option<Device> {
+    if cfg!(target_os = "windows") {
+        return host.default_output_device();
+    }
+
+    // PulseAudio and PipeWire expose what is being played as "monitor" sources, otherwise it's up to
+    // the user to make the default input device record the output
+    host.input_devices()
+        .ok()
+        .and_then(|mut devices| devices.find(|device| device.name().is_ok_and(|name| name.to_lowercase().contains("monitor"))))
+        .or_else(|| host.default_input_device())
+}
+
+fn build_stream<T>(device: &Device, config: &StreamConfig, samples: Samples) -> Result<Stream, AudioSourceError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = usize::from(config.channels);
+    let mut converted = Vec::new();
+
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                converted.clear();
+                converted.extend(data.iter().map(|sample| sample.to_sample::<f32>()));
+                samples.push_interleaved(&converted, channels);
+            },
+            |err| eprintln!("Audio stream error: {err}"),
+            None,
+        )
+        .change_context(AudioSourceError)
+}
@@ -0,0 +1,196 @@
+use std::{
+    f32::consts::TAU,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    enums::Direction,
+    manager::{
+        overlay::{Overlay, Sampler},
+        palette::Palette,
+        power_supply::{BatteryState, ChargeStatus, PowerSupply},
+        profile::{BatterySettings, Profile},
+        Inner,
+    },
+};
+
+use super::{clock, color};
+
+/// How often the battery is read
+const READ_INTERVAL: Duration = Duration::from_secs(5);
+const FPS: u32 = 30;
+/// Length of a pulse of the low battery warning, in seconds
+const WARNING_PERIOD: f32 = 1.5;
+/// Length of a breath while charging, in seconds
+const CHARGING_PERIOD: f32 = 3.0;
+/// How dim a breath gets at its lowest
+const BREATH_FLOOR: f32 = 0.2;
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let mut indicator = Indicator::new(&profile.battery, PowerSupply::default());
+
+    if indicator.state().is_none() {
+        eprintln!("Could not find a battery");
+        return;
+    }
+
+    let direction = profile.direction;
+
+    clock::run_at(manager, FPS, |elapsed| indicator.frame(direction, elapsed));
+}
+
+/// Keeps track of a battery and turns its state into colors
+pub struct Indicator {
+    supply: PowerSupply,
+    state: Option<BatteryState>,
+    last_read: Option<Instant>,
+    warning_level: u8,
+    warning_color: [f32; 3],
+    charging_color: [f32; 3],
+    gradient: Palette,
+}
+
+impl Indicator {
+    pub fn new(settings: &BatterySettings, supply: PowerSupply) -> Self {
+        Self {
+            supply,
+            state: None,
+            last_read: None,
+            warning_level: settings.warning_level,
+            warning_color: settings.warning_color.map(f32::from),
+            charging_color: settings.charging_color.map(f32::from),
+            gradient: settings.gradient.sorted(),
+        }
+    }
+
+    /// The state of the battery, read again if it's time to
+    pub fn state(&mut self) -> Option<BatteryState> {
+        if self.last_read.is_none_or(|last_read| last_read.elapsed() >= READ_INTERVAL) {
+            // Keep the last state around if a read fails
+            self.state = self.supply.battery().or(self.state);
+            self.last_read = Some(Instant::now());
+        }
+
+        self.state
+    }
+
+    fn is_low(&self, state: BatteryState) -> bool {
+        state.capacity <= self.warning_level && !matches!(state.status, ChargeStatus::Charging | ChargeStatus::Full)
+    }
+
+    /// A pulsing warning when the battery is low, a breathing bar while it charges and a plain bar otherwise
+    pub fn frame(&mut self, direction: Direction, elapsed: f32) -> [u8; 12] {
+        let Some(state) = self.state() else {
+            return [0; 12];
+        };
+
+        if self.is_low(state) {
+            let rgb = self.warning_color.map(|channel| (channel * pulse(elapsed, WARNING_PERIOD)).round() as u8);
+            return [rgb; 4].concat().try_into().unwrap();
+        }
+
+        let level = f32::from(state.capacity) / 100.0;
+
+        if state.status == ChargeStatus::Charging {
+            let breath = (1.0 - BREATH_FLOOR).mul_add(pulse(elapsed, CHARGING_PERIOD), BREATH_FLOOR);
+            bar(self.charging_color.map(|channel| channel * breath), direction, level)
+        } else {
+            bar(self.gradient.sample(level), direction, level)
+        }
+    }
+}
+
+/// Pulses the warning color over whatever is shown while the battery is low
+pub struct BatteryOverlay {
+    indicator: Indicator,
+    state: Sampler<Option<BatteryState>>,
+    started: Instant,
+}
+
+impl BatteryOverlay {
+    pub fn new(settings: &BatterySettings, supply: PowerSupply) -> Self {
+        Self {
+            indicator: Indicator::new(settings, supply.clone()),
+            // Keep the last state around if a read fails
+            state: Sampler::spawn(READ_INTERVAL, None, move |last| supply.battery().or(*last)),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Overlay for BatteryOverlay {
+    fn draw(&mut self, frame: &mut [u8; 12]) -> bool {
+        if !self.state.get().is_some_and(|state| self.indicator.is_low(state)) {
+            return false;
+        }
+
+        let amount = pulse(self.started.elapsed().as_secs_f32(), WARNING_PERIOD);
+
+        let zones = color::zones(frame);
+        for (zone, rgb) in frame.chunks_exact_mut(3).zip(zones) {
+            let blended = color::lerp(rgb, self.indicator.warning_color, amount);
+            zone.copy_from_slice(&blended.map(|channel| channel.round() as u8));
+        }
+
+        true
+    }
+}
+
+/// Goes smoothly from `0.0` up to `1.0` and back every `period` seconds
+fn pulse(elapsed: f32, period: f32) -> f32 {
+    (1.0 - (elapsed / period * TAU).cos()) / 2.0
+}
+
+/// Fill the zones up to `level` from the side given by `direction`, the last one only partially
+fn bar(rgb: [f32; 3], direction: Direction, level: f32) -> [u8; 12] {
+    let mut frame = [0; 12];
+
+    for (i, zone) in frame.chunks_exact_mut(3).enumerate() {
+        let distance = match direction {
+            Direction::Left => i,
+            Direction::Right => 3 - i,
+        } as f32;
+
+        let lit = level.mul_add(4.0, -distance).clamp(0.0, 1.0);
+        zone.copy_from_slice(&rgb.map(|channel| (channel * lit).round() as u8));
+    }
+
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        enums::Direction,
+        manager::{power_supply::fake::FakeSupplies, profile::BatterySettings},
+    };
+
+    use super::{bar, Indicator, WARNING_PERIOD};
+
+    #[test]
+    fn bar_fills_from_the_chosen_side() {
+        let rgb = [200.0, 100.0, 0.0];
+
+        assert_eq!(bar(rgb, Direction::Left, 0.5), [200, 100, 0, 200, 100, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bar(rgb, Direction::Right, 0.125), [0, 0, 0, 0, 0, 0, 0, 0, 0, 100, 50, 0]);
+        assert_eq!(bar(rgb, Direction::Left, 0.0), [0; 12]);
+    }
+
+    #[test]
+    fn warns_only_while_discharging_low() {
+        let supplies = FakeSupplies::new("indicator");
+        supplies.set("BAT0", &[("type", "Battery"), ("capacity", "10"), ("status", "Discharging")]);
+
+        let settings = BatterySettings::default();
+        let mut indicator = Indicator::new(&settings, supplies.supply());
+
+        // The warning pulses in from off
+        assert_eq!(indicator.frame(Direction::Left, 0.0), [0; 12]);
+        assert_eq!(indicator.frame(Direction::Left, WARNING_PERIOD / 2.0), [settings.warning_color; 4].concat().as_slice());
+
+        supplies.set("BAT0", &[("status", "Charging")]);
+        let mut indicator = Indicator::new(&settings, supplies.supply());
+        let state = indicator.state().unwrap();
+        assert!(!indicator.is_low(state));
+    }
+}
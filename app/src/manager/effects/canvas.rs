@@ -0,0 +1,154 @@
+/// Default amount of pixels across the width of the keyboard
+pub const WIDTH: usize = 64;
+
+/// Amount of zones the canvas is filtered down to
+pub const ZONES: usize = 4;
+
+/// A virtual strip of pixels spanning the width of the keyboard, from left to right
+///
+/// Effects can draw at a much higher resolution than the four zones the keyboard has, and the canvas is then
+/// filtered down to the zones so anything that moves across it does so smoothly instead of jumping between zones.
+/// Channels are stored as floats in the `0.0..=255.0` range to avoid rounding errors when blending.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canvas {
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new(WIDTH)
+    }
+}
+
+impl Canvas {
+    pub fn new(width: usize) -> Self {
+        Self {
+            pixels: vec![[0.0; 3]; width.max(ZONES)],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.pixels.len()
+    }
+
+    /// Where the center of a pixel lies, `0.0` being the left edge of the keyboard and `1.0` the right one
+    pub fn position(&self, index: usize) -> f32 {
+        (index as f32 + 0.5) / self.width() as f32
+    }
+
+    /// Set every pixel to the color returned for its position
+    pub fn paint(&mut self, mut f: impl FnMut(f32) -> [f32; 3]) {
+        let width = self.width() as f32;
+
+        for (i, pixel) in self.pixels.iter_mut().enumerate() {
+            *pixel = f((i as f32 + 0.5) / width);
+        }
+    }
+
+    /// Filter the canvas down to the four zones of the keyboard
+    ///
+    /// Each zone is a weighted average of the pixels around its center, with the weight falling off linearly until
+    /// the center of the neighbouring zones. Something moving from one zone to the next will then fade out of the
+    /// first one as it fades into the other instead of abruptly switching over at the boundary.
+    pub fn downsample(&self) -> [u8; 12] {
+        let mut out = [0; 12];
+
+        for (zone, rgb) in out.chunks_exact_mut(3).enumerate() {
+            let center = (zone as f32 + 0.5) / ZONES as f32;
+
+            let mut sum = [0.0; 3];
+            let mut total_weight = 0.0;
+
+            for (i, pixel) in self.pixels.iter().enumerate() {
+                let distance = (self.position(i) - center).abs() * ZONES as f32;
+                let weight = 1.0 - distance;
+
+                if weight > 0.0 {
+                    for (channel, value) in sum.iter_mut().zip(pixel) {
+                        *channel += value * weight;
+                    }
+                    total_weight += weight;
+                }
+            }
+
+            for (channel, value) in rgb.iter_mut().zip(sum) {
+                *channel = (value / total_weight).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        out
+    }
+
+    pub fn to_rgb(&self) -> Vec<[u8; 3]> {
+        self.pixels.iter().map(|pixel| pixel.map(|channel| channel.round().clamp(0.0, 255.0) as u8)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Canvas, WIDTH, ZONES};
+
+    #[test]
+    fn a_flat_canvas_keeps_its_color() {
+        let mut canvas = Canvas::default();
+        canvas.paint(|_| [10.0, 128.0, 255.0]);
+
+        assert_eq!(canvas.width(), WIDTH);
+        assert_eq!(canvas.downsample(), [10, 128, 255].repeat(ZONES).as_slice());
+    }
+
+    #[test]
+    fn a_canvas_is_at_least_as_wide_as_the_keyboard() {
+        let mut canvas = Canvas::new(1);
+        canvas.paint(|position| [position * 255.0, 0.0, 0.0]);
+
+        // With a pixel per zone, each zone is just that pixel
+        assert_eq!(canvas.width(), ZONES);
+        assert_eq!(canvas.downsample(), [32, 0, 0, 96, 0, 0, 159, 0, 0, 223, 0, 0]);
+    }
+
+    #[test]
+    fn neighbouring_zones_blend_across_their_boundary() {
+        let mut canvas = Canvas::default();
+        canvas.paint(|position| if position < 0.5 { [255.0; 3] } else { [0.0; 3] });
+
+        let zones = canvas.downsample();
+        assert_eq!((zones[0], zones[9]), (255, 0));
+        // Mostly white and mostly black, mirroring each other
+        assert!(zones[3] > 200 && zones[6] < 55, "{zones:?}");
+        assert!((254..=256).contains(&(u16::from(zones[3]) + u16::from(zones[6]))), "{zones:?}");
+    }
+
+    #[test]
+    fn something_moving_across_fades_between_zones() {
+        let mut previous: Option<[u8; 12]> = None;
+
+        for start in 0..WIDTH - 4 {
+            let mut canvas = Canvas::default();
+            canvas.paint(|position| {
+                let pixel = (position * WIDTH as f32) as usize;
+                if (start..start + 4).contains(&pixel) {
+                    [255.0; 3]
+                } else {
+                    [0.0; 3]
+                }
+            });
+
+            let zones = canvas.downsample();
+            if let Some(previous) = previous {
+                for (now, before) in zones.iter().zip(previous) {
+                    assert!(now.abs_diff(before) < 32, "jumped from {previous:?} to {zones:?}");
+                }
+            }
+            previous = Some(zones);
+        }
+    }
+
+    #[test]
+    fn pixels_are_rounded_and_clamped() {
+        let mut canvas = Canvas::new(ZONES);
+        canvas.paint(|_| [300.0, -5.0, 127.6]);
+
+        assert_eq!(canvas.to_rgb(), vec![[255, 0, 128]; ZONES]);
+    }
+}
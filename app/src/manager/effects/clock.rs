@@ -0,0 +1,81 @@
+use std::{
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::manager::Inner;
+
+use super::canvas::Canvas;
+
+/// The rate at which frame based effects are rendered
+pub const FPS: u32 = 60;
+
+/// Keeps frame based effects ticking at a steady rate
+pub struct FrameClock {
+    start: Instant,
+    next: Instant,
+    interval: Duration,
+}
+
+impl FrameClock {
+    pub fn new(fps: u32) -> Self {
+        let now = Instant::now();
+
+        Self {
+            start: now,
+            next: now,
+            interval: Duration::from_secs(1) / fps.max(1),
+        }
+    }
+
+    /// Seconds since the clock was started
+    pub fn elapsed(&self) -> f32 {
+        self.start.elapsed().as_secs_f32()
+    }
+
+    /// Sleep until the next frame is due
+    pub fn wait(&mut self) {
+        self.next += self.interval;
+
+        let now = Instant::now();
+        if self.next > now {
+            thread::sleep(self.next - now);
+        } else {
+            // Don't try to catch up if we fell behind
+            self.next = now;
+        }
+    }
+}
+
+/// Render frames with the time elapsed since the effect started until it is stopped
+pub fn run(manager: &mut Inner, render: impl FnMut(f32) -> [u8; 12]) {
+    run_at(manager, FPS, render);
+}
+
+/// Like [`run`], at a different frame rate
+pub fn run_at(manager: &mut Inner, fps: u32, mut render: impl FnMut(f32) -> [u8; 12]) {
+    let mut clock = FrameClock::new(fps);
+
+    while !manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
+        manager.keyboard.set_colors_to(&render(clock.elapsed())).unwrap();
+        clock.wait();
+    }
+}
+
+/// Like [`run`], but drawing onto a canvas that is then filtered down to the zones
+pub fn run_canvas(manager: &mut Inner, render: impl FnMut(&mut Canvas, f32)) {
+    run_canvas_at(manager, FPS, render);
+}
+
+/// Like [`run_canvas`], at a different frame rate
+pub fn run_canvas_at(manager: &mut Inner, fps: u32, mut render: impl FnMut(&mut Canvas, f32)) {
+    let mut clock = FrameClock::new(fps);
+    let mut canvas = Canvas::default();
+
+    while !manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
+        render(&mut canvas, clock.elapsed());
+        manager.keyboard.set_canvas(&canvas).unwrap();
+        clock.wait();
+    }
+}
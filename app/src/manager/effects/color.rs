@@ -0,0 +1,65 @@
+/// Convert a color in HSV space (hue in degrees, saturation and value in `0.0..=1.0`) to RGB
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [u8; 3] {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let saturation = saturation.clamp(0.0, 1.0);
+    let value = value.clamp(0.0, 1.0);
+
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let m = value - chroma;
+
+    let (r, g, b) = match hue as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+/// Convert a color to HSV space, the inverse of [`hsv_to_rgb`]
+pub fn rgb_to_hsv(rgb: [u8; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb.map(|channel| f32::from(channel) / 255.0);
+
+    let max = r.max(g).max(b);
+    let chroma = max - r.min(g).min(b);
+
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+
+    let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+
+    (hue * 60.0, saturation, max)
+}
+
+/// Shift the hue of a color by `degrees`, keeping its saturation and value
+pub fn rotate_hue(rgb: [u8; 3], degrees: f32) -> [u8; 3] {
+    let (hue, saturation, value) = rgb_to_hsv(rgb);
+    hsv_to_rgb(hue + degrees, saturation, value)
+}
+
+/// Multiply every channel of a frame by `factor`
+pub fn scale(frame: &[u8; 12], factor: f32) -> [u8; 12] {
+    frame.map(|channel| (f32::from(channel) * factor.clamp(0.0, 1.0)).round() as u8)
+}
+
+/// Linearly interpolate between two colors
+pub fn lerp(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+    let t = t.clamp(0.0, 1.0);
+    [0, 1, 2].map(|i| (to[i] - from[i]).mul_add(t, from[i]))
+}
+
+/// The color of each zone of a frame
+pub fn zones(frame: &[u8; 12]) -> [[f32; 3]; 4] {
+    [0, 1, 2, 3].map(|zone| [0, 1, 2].map(|channel| f32::from(frame[zone * 3 + channel])))
+}
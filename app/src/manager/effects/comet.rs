@@ -0,0 +1,66 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    enums::Direction,
+    manager::{profile::Profile, Inner},
+};
+
+use super::clock;
+
+/// Keyboard widths travelled per second at speed 1
+const COMET_SPEED: f32 = 0.5;
+/// Length of the tail, relative to the width of the keyboard
+const TAIL_LENGTH: f32 = 0.45;
+/// Length of the soft edge in front of the head
+const HEAD_EDGE: f32 = 0.04;
+/// The longest wait between two comets at speed 1, in seconds
+const MAX_GAP: f32 = 0.8;
+
+struct Pass {
+    started: f32,
+    rgb: [f32; 3],
+}
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let mut rng = StdRng::seed_from_u64(profile.procedural.seed);
+    let speed = f32::from(profile.speed);
+    let direction = profile.direction;
+
+    let mut colors = profile.palette.sorted().colors();
+    if colors.is_empty() {
+        colors.push([255.0; 3]);
+    }
+
+    let velocity = COMET_SPEED * speed;
+    // Until the end of the tail has left the keyboard
+    let pass_duration = (1.0 + TAIL_LENGTH + HEAD_EDGE) / velocity;
+
+    let mut passes = 0;
+    let mut pass = Pass { started: 0.0, rgb: colors[0] };
+
+    clock::run_canvas(manager, |canvas, elapsed| {
+        // Each comet takes the next color of the palette
+        while elapsed - pass.started >= pass_duration {
+            passes += 1;
+            pass = Pass {
+                started: pass.started + pass_duration + rng.random_range(0.0..MAX_GAP) / speed,
+                rgb: colors[passes % colors.len()],
+            };
+        }
+
+        let head = (elapsed - pass.started) * velocity - HEAD_EDGE;
+
+        canvas.paint(|position| {
+            // Comets come in from the side given by the direction
+            let distance = match direction {
+                Direction::Left => position,
+                Direction::Right => 1.0 - position,
+            };
+
+            let behind = head - distance;
+            let intensity = if behind < 0.0 { 1.0 + behind / HEAD_EDGE } else { (1.0 - behind / TAIL_LENGTH).max(0.0).powi(2) };
+
+            pass.rgb.map(|channel| channel * intensity.clamp(0.0, 1.0))
+        });
+    });
+}
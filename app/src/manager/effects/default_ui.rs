@@ -1,11 +1,16 @@
-use eframe::egui::{ComboBox, Slider, Ui};
+use eframe::egui::{Button, CollapsingHeader, Color32, ComboBox, CornerRadius, DragValue, Rect, Sense, Slider, TextEdit, Ui, Vec2};
 use legion_rgb_driver::SPEED_RANGE;
 use strum::IntoEnumIterator;
 
 use crate::{
-    enums::{Brightness, Direction},
+    enums::{AudioSource, Brightness, DecayCurve, Direction, Effects, LockKey},
     gui::style::SpacingStyle,
-    manager::profile::Profile,
+    manager::{
+        effects::temperature,
+        histogram,
+        palette::{ColorStop, Palette},
+        profile::{BatterySettings, LockKeySettings, Profile},
+    },
 };
 
 const COMBOBOX_WIDTH: f32 = 20.0;
@@ -60,3 +65,288 @@ pub fn show_effect_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &
         ui.label("Speed");
     });
 }
+
+pub fn show_procedural_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &mut bool) {
+    if profile.effect.takes_seed() {
+        ui.horizontal(|ui| {
+            *update_lights |= ui.add(DragValue::new(&mut profile.procedural.seed)).changed();
+            ui.label("Seed");
+            if ui.button("Randomize").clicked() {
+                profile.procedural.seed = rand::random();
+                *update_lights = true;
+            }
+        });
+    }
+
+    if profile.effect == Effects::Rainbow {
+        ui.horizontal(|ui| {
+            *update_lights |= ui.add(Slider::new(&mut profile.procedural.saturation, 0.0..=1.0)).changed();
+            ui.label("Saturation");
+        });
+    }
+}
+
+pub fn show_audio_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &mut bool) {
+    let audio = &mut profile.audio;
+
+    ComboBox::from_label("Source")
+        .width(COMBOBOX_WIDTH)
+        .selected_text({
+            let text: &'static str = audio.source.into();
+            text
+        })
+        .show_ui(ui, |ui| {
+            for val in AudioSource::iter() {
+                let text: &'static str = val.into();
+                *update_lights |= ui.selectable_value(&mut audio.source, val, text).changed();
+            }
+        });
+
+    if matches!(audio.source, AudioSource::File | AudioSource::Pipe) {
+        ui.horizontal(|ui| {
+            // Only restart the effect once the path is done being typed
+            *update_lights |= ui.add(TextEdit::singleline(&mut audio.path).desired_width(150.0)).lost_focus();
+            ui.label("Path");
+        });
+    }
+
+    if audio.source == AudioSource::Pipe {
+        ui.horizontal(|ui| {
+            *update_lights |= ui.add(DragValue::new(&mut audio.pipe_sample_rate).range(8000..=192_000).suffix(" Hz")).changed();
+            *update_lights |= ui.add(DragValue::new(&mut audio.pipe_channels).range(1..=8)).changed();
+            ui.label("Format");
+        });
+    }
+
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(Slider::new(&mut audio.sensitivity, 0.1..=10.0).logarithmic(true)).changed();
+        ui.label("Sensitivity");
+    });
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(Slider::new(&mut audio.smoothing, 0.0..=0.95)).changed();
+        ui.label("Smoothing");
+    });
+}
+
+pub fn show_temperature_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &mut bool) {
+    let settings = &mut profile.temperature;
+
+    *update_lights |= ui.checkbox(&mut settings.per_zone, "One sensor per zone").changed();
+
+    let count = if settings.per_zone { 4 } else { 1 };
+    if settings.sensors.len() < count {
+        settings.sensors.resize(count, String::new());
+    }
+
+    for (i, sensor) in settings.sensors.iter_mut().take(count).enumerate() {
+        let label = if settings.per_zone { format!("Zone {}", i + 1) } else { "Sensor".to_string() };
+        show_sensor(ui, &label, sensor, update_lights);
+    }
+
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(DragValue::new(&mut settings.min).range(0.0..=settings.max).suffix(" °C")).changed();
+        *update_lights |= ui.add(DragValue::new(&mut settings.max).range(settings.min..=150.0).suffix(" °C")).changed();
+        ui.label("Range");
+    });
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(Slider::new(&mut settings.smoothing, 0.0..=0.95)).changed();
+        ui.label("Smoothing");
+    });
+
+    show_palette(ui, "Gradient", &mut settings.gradient, update_lights);
+}
+
+pub fn show_reactive_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &mut bool) {
+    let settings = &mut profile.reactive;
+
+    ui.horizontal(|ui| {
+        *update_lights |= ui.color_edit_button_srgb(&mut settings.press_color).changed();
+        ui.label("Press color");
+    });
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(DragValue::new(&mut settings.decay_ms).range(50..=5000).speed(10).suffix(" ms")).changed();
+        ui.label("Decay");
+    });
+    ComboBox::from_label("Curve")
+        .width(COMBOBOX_WIDTH)
+        .selected_text({
+            let text: &'static str = settings.curve.into();
+            text
+        })
+        .show_ui(ui, |ui| {
+            for val in DecayCurve::iter() {
+                let text: &'static str = val.into();
+                *update_lights |= ui.selectable_value(&mut settings.curve, val, text).changed();
+            }
+        });
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(Slider::new(&mut settings.press_intensity, 0.1..=1.0)).changed();
+        ui.label("Press intensity");
+    });
+}
+
+pub fn show_typing_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &mut bool) {
+    let settings = &mut profile.typing;
+
+    if profile.effect == Effects::Heatmap {
+        ui.horizontal(|ui| {
+            *update_lights |= ui.add(Slider::new(&mut settings.cool_down, 1.0..=600.0).logarithmic(true).suffix(" s")).changed();
+            ui.label("Cool down");
+        });
+    } else {
+        ui.horizontal(|ui| {
+            *update_lights |= ui.add(DragValue::new(&mut settings.max_wpm).range(10.0..=300.0).suffix(" WPM")).changed();
+            ui.label("Full scale");
+        });
+        ui.horizontal(|ui| {
+            *update_lights |= ui.add(Slider::new(&mut settings.wpm_window, 2.0..=60.0).suffix(" s")).changed();
+            ui.label("Window");
+        });
+    }
+
+    show_palette(ui, "Gradient", &mut settings.gradient, update_lights);
+
+    *update_lights |= ui.checkbox(&mut settings.record_histogram, "Keep a key histogram").changed();
+    show_key_histogram(ui);
+}
+
+/// How often each zone and the most used keys were pressed, as recorded on disk
+fn show_key_histogram(ui: &mut Ui) {
+    CollapsingHeader::new("Key histogram").show(ui, |ui| {
+        let totals = histogram::zone_totals();
+        let most = totals.iter().copied().max().unwrap_or(0).max(1);
+
+        let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 40.0), Sense::hover());
+        let zone_width = rect.width() / 4.0;
+        for (i, total) in totals.iter().enumerate() {
+            let height = rect.height() * *total as f32 / most as f32;
+            let bar = Rect::from_min_max(
+                rect.min + Vec2::new(zone_width * i as f32 + 2.0, rect.height() - height),
+                rect.min + Vec2::new(zone_width * (i + 1) as f32 - 2.0, rect.height()),
+            );
+            ui.painter().rect_filled(bar, CornerRadius::same(2), Color32::from_gray(160));
+        }
+
+        for (key, count) in histogram::counts().iter().take(10) {
+            ui.label(format!("{key}: {count}"));
+        }
+
+        if ui.button("Clear").clicked() {
+            histogram::clear();
+        }
+    });
+}
+
+pub fn show_battery_settings(ui: &mut Ui, profile: &mut Profile, update_lights: &mut bool) {
+    let settings = &mut profile.battery;
+
+    show_battery_warning(ui, settings, update_lights);
+    ui.horizontal(|ui| {
+        *update_lights |= ui.color_edit_button_srgb(&mut settings.charging_color).changed();
+        ui.label("Charging color");
+    });
+
+    show_palette(ui, "Gradient", &mut settings.gradient, update_lights);
+}
+
+/// The low battery warning, which is also shown over other effects if the overlay is enabled
+pub fn show_battery_warning(ui: &mut Ui, settings: &mut BatterySettings, update_lights: &mut bool) {
+    ui.horizontal(|ui| {
+        *update_lights |= ui.add(Slider::new(&mut settings.warning_level, 0..=100).suffix("%")).changed();
+        ui.label("Warning level");
+    });
+    ui.horizontal(|ui| {
+        *update_lights |= ui.color_edit_button_srgb(&mut settings.warning_color).changed();
+        ui.label("Warning color");
+    });
+    *update_lights |= ui.checkbox(&mut settings.overlay, "Warn over other effects").changed();
+}
+
+/// A zone and color for each lock key, shown over any effect while the key is on
+pub fn show_lock_key_overlay(ui: &mut Ui, settings: &mut LockKeySettings, update_lights: &mut bool) {
+    for key in LockKey::iter() {
+        let indicator = settings.indicator_mut(key);
+        let text: &'static str = key.into();
+
+        ui.horizontal(|ui| {
+            *update_lights |= ui.checkbox(&mut indicator.enabled, text).changed();
+            *update_lights |= ui.color_edit_button_srgb(&mut indicator.rgb).changed();
+
+            // Zones are numbered from 1 for display
+            let mut zone = indicator.zone + 1;
+            if ui.add(DragValue::new(&mut zone).range(1..=4).prefix("Zone ")).changed() {
+                indicator.zone = zone - 1;
+                *update_lights = true;
+            }
+        });
+    }
+}
+
+/// Pick a sensor from the list, or type in a hwmon path
+fn show_sensor(ui: &mut Ui, label: &str, sensor: &mut String, update_lights: &mut bool) {
+    ui.horizontal(|ui| {
+        ComboBox::from_id_salt(label)
+            .width(COMBOBOX_WIDTH)
+            .selected_text(if sensor.is_empty() { "Automatic" } else { sensor.as_str() })
+            .show_ui(ui, |ui| {
+                *update_lights |= ui.selectable_value(sensor, String::new(), "Automatic").changed();
+                for name in temperature::sensor::available() {
+                    *update_lights |= ui.selectable_value(sensor, name.clone(), name).changed();
+                }
+            });
+
+        *update_lights |= ui.add(TextEdit::singleline(sensor).hint_text("/sys/class/hwmon/...").desired_width(120.0)).lost_focus();
+        ui.label(label);
+    });
+}
+
+pub fn show_palette(ui: &mut Ui, label: &str, palette: &mut Palette, update_lights: &mut bool) {
+    ui.label(label);
+
+    // Show what the gradient will look like with the stops in order
+    let sorted = palette.sorted();
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), 12.0), Sense::hover());
+    let steps = rect.width().max(1.0) as usize;
+    let step_width = rect.width() / steps as f32;
+    for i in 0..steps {
+        let rgb = sorted.sample((i as f32 + 0.5) / steps as f32).map(|channel| channel.round() as u8);
+        let step_rect = Rect::from_min_size(rect.min + Vec2::new(step_width * i as f32, 0.0), Vec2::new(step_width, rect.height()));
+        ui.painter().rect_filled(step_rect, CornerRadius::ZERO, Color32::from_rgb(rgb[0], rgb[1], rgb[2]));
+    }
+
+    let mut removed = None;
+    for (i, stop) in palette.stops.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            *update_lights |= ui.color_edit_button_srgb(&mut stop.rgb).changed();
+            *update_lights |= ui.add(Slider::new(&mut stop.position, 0.0..=1.0).fixed_decimals(2)).changed();
+            if ui.button("-").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+
+    if let Some(i) = removed.filter(|_| palette.stops.len() > 1) {
+        palette.stops.remove(i);
+        *update_lights = true;
+    }
+
+    if ui.add_enabled(palette.stops.len() < Palette::MAX_STOPS, Button::new("+")).clicked() {
+        // Put the new stop in the middle of the widest gap
+        let mut positions: Vec<f32> = sorted.stops.iter().map(|stop| stop.position).collect();
+        positions.insert(0, 0.0);
+        positions.push(1.0);
+
+        let (start, end) = positions
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .max_by(|a, b| (a.1 - a.0).total_cmp(&(b.1 - b.0)))
+            .unwrap_or((0.0, 1.0));
+        let position = (start + end) / 2.0;
+
+        palette.stops.push(ColorStop {
+            position,
+            rgb: sorted.sample(position).map(|channel| channel.round() as u8),
+        });
+        *update_lights = true;
+    }
+}
@@ -1,54 +1,48 @@
 use std::{
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-    thread,
+    collections::HashSet,
     time::{Duration, Instant},
 };
 
-use device_query::DeviceQuery;
-
-use crate::manager::{profile::Profile, Inner};
-
-pub fn play(manager: &mut Inner, p: &Profile) {
-    let stop_signals = manager.stop_signals.clone();
-
-    let kill_thread = Arc::new(AtomicBool::new(false));
-    let exit_thread = kill_thread.clone();
-
-    let state = device_query::DeviceState::new();
+use crate::{
+    input::{self, KeyEvent},
+    manager::{profile::Profile, Inner},
+};
 
-    thread::spawn(move || {
-        let state = device_query::DeviceState::new();
+use super::{clock, color};
 
-        loop {
-            if !state.get_keys().is_empty() {
-                stop_signals.keyboard_stop_signal.store(true, Ordering::SeqCst);
-            }
+/// There is nothing to animate until the lights start fading
+const FPS: u32 = 30;
+/// How long the lights take to fade out once the keyboard is left alone
+const FADE_SECONDS: f32 = 0.7;
 
-            if exit_thread.load(Ordering::SeqCst) {
-                break;
+pub fn play(manager: &mut Inner, p: &Profile) {
+    let events = input::subscribe();
+
+    let colors = p.rgb_array();
+    let timeout = Duration::from_secs(20 / u64::from(p.speed));
+
+    let mut held = HashSet::new();
+    let mut last_input = Instant::now();
+
+    clock::run_at(manager, FPS, |_| {
+        for event in events.try_iter() {
+            match event {
+                KeyEvent::Press(key) => {
+                    held.insert(key);
+                }
+                KeyEvent::Release(key) => {
+                    held.remove(&key);
+                }
             }
-
-            thread::sleep(Duration::from_millis(5));
+            last_input = Instant::now();
         }
-    });
 
-    let mut now = Instant::now();
-    while !manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
-        if state.get_keys().is_empty() {
-            if now.elapsed() > Duration::from_secs(20 / u64::from(p.speed)) {
-                manager.keyboard.transition_colors_to(&[0; 12], 230, 3).unwrap();
-            } else {
-                thread::sleep(Duration::from_millis(20));
-            }
-        } else {
-            manager.keyboard.set_colors_to(&p.rgb_array()).unwrap();
-            manager.stop_signals.keyboard_stop_signal.store(false, Ordering::SeqCst);
-            now = Instant::now();
+        // Keys being held down count as input too
+        if !held.is_empty() {
+            last_input = Instant::now();
         }
-    }
 
-    kill_thread.store(true, Ordering::SeqCst);
+        let fading = last_input.elapsed().saturating_sub(timeout).as_secs_f32();
+        color::scale(&colors, 1.0 - fading / FADE_SECONDS)
+    });
 }
@@ -0,0 +1,81 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    enums::Direction,
+    manager::{profile::Profile, Inner},
+};
+
+use super::{clock, color};
+
+/// Cells the flames are simulated on, stretched across the canvas afterwards
+const CELLS: usize = 16;
+/// Simulation steps per second at speed 1
+const TICKS_PER_SECOND: f32 = 12.0;
+/// The most heat a cell can lose in a single step
+const COOLING: f32 = 0.12;
+/// Chance of a new spark being lit on every step
+const SPARKING: f64 = 0.6;
+/// Sparks are only lit this close to the source of the flames
+const SPARK_CELLS: usize = 3;
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let mut rng = StdRng::seed_from_u64(profile.procedural.seed);
+    let direction = profile.direction;
+    let tick_rate = TICKS_PER_SECOND * f32::from(profile.speed);
+
+    let mut heat = [0.0; CELLS];
+    let mut ticks = 0;
+
+    clock::run_canvas(manager, |canvas, elapsed| {
+        // Step at a fixed rate so the seed plays out the same way no matter the frame timing
+        let due = (elapsed * tick_rate) as u64;
+        while ticks < due {
+            step(&mut heat, &mut rng);
+            ticks += 1;
+        }
+
+        canvas.paint(|position| {
+            // The flames rise from the side given by the direction
+            let distance = match direction {
+                Direction::Left => position,
+                Direction::Right => 1.0 - position,
+            };
+
+            let cell = distance.mul_add(CELLS as f32, -0.5).clamp(0.0, (CELLS - 1) as f32);
+            let index = cell as usize;
+            let next = (index + 1).min(CELLS - 1);
+
+            heat_color((heat[next] - heat[index]).mul_add(cell.fract(), heat[index]))
+        });
+    });
+}
+
+/// Advance the simulation by one step, `heat[0]` being the source of the flames
+fn step(heat: &mut [f32; CELLS], rng: &mut StdRng) {
+    for cell in heat.iter_mut() {
+        *cell = (*cell - rng.random_range(0.0..COOLING)).max(0.0);
+    }
+
+    // Heat drifts away from the source, spreading out a little as it goes
+    for i in (2..CELLS).rev() {
+        heat[i] = heat[i - 2].mul_add(2.0, heat[i - 1]) / 3.0;
+    }
+
+    if rng.random_bool(SPARKING) {
+        let i = rng.random_range(0..SPARK_CELLS);
+        heat[i] = (heat[i] + rng.random_range(0.6..1.0)).min(1.0);
+    }
+}
+
+/// Black, through red and yellow, up to white
+fn heat_color(heat: f32) -> [f32; 3] {
+    let t = heat.clamp(0.0, 1.0) * 3.0;
+
+    if t < 1.0 {
+        color::lerp([0.0; 3], [255.0, 0.0, 0.0], t)
+    } else if t < 2.0 {
+        color::lerp([255.0, 0.0, 0.0], [255.0, 200.0, 0.0], t - 1.0)
+    } else {
+        color::lerp([255.0, 200.0, 0.0], [255.0; 3], t - 2.0)
+    }
+}
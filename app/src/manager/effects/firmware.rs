@@ -0,0 +1,124 @@
+use std::f32::consts::TAU;
+
+use legion_rgb_driver::{BaseEffects, SPEED_RANGE};
+
+use crate::manager::{effects::clock, profile::Profile, Inner};
+
+use super::color;
+
+// The following were tuned by eye against the keyboard's own effects, indexed by speed (1 to 4)
+
+/// Seconds for a full fade in and out
+const BREATH_PERIOD: [f32; 4] = [7.0, 5.0, 3.5, 2.2];
+/// Seconds for a full trip around the color wheel
+const SMOOTH_PERIOD: [f32; 4] = [24.0, 16.0, 10.0, 6.0];
+const WAVE_PERIOD: [f32; 4] = [8.0, 5.5, 3.5, 2.0];
+/// Hue difference between two neighbouring zones in a wave, in degrees
+const WAVE_SPREAD: f32 = 60.0;
+/// The firmware never fully turns the lights off while breathing
+const BREATH_FLOOR: f32 = 0.02;
+
+fn period(table: [f32; 4], speed: u8) -> f32 {
+    let index = speed.clamp(*SPEED_RANGE.start(), *SPEED_RANGE.end()) - SPEED_RANGE.start();
+    table[usize::from(index)]
+}
+
+/// Render what a firmware effect looks like `elapsed` seconds after it started
+pub fn render(effect: BaseEffects, speed: u8, colors: &[u8; 12], elapsed: f32) -> [u8; 12] {
+    match effect {
+        BaseEffects::Static => *colors,
+        BaseEffects::Breath => {
+            let phase = elapsed / period(BREATH_PERIOD, speed);
+            let intensity = (1.0 - (phase * TAU).cos()) / 2.0;
+            // Perceived brightness is far from linear
+            color::scale(colors, (intensity * intensity).max(BREATH_FLOOR))
+        }
+        BaseEffects::Smooth => {
+            let hue = elapsed / period(SMOOTH_PERIOD, speed) * 360.0;
+            color::hsv_to_rgb(hue, 1.0, 1.0).repeat(4).try_into().unwrap()
+        }
+        BaseEffects::LeftWave | BaseEffects::RightWave => {
+            let hue = elapsed / period(WAVE_PERIOD, speed) * 360.0;
+            let spread = if effect == BaseEffects::LeftWave { WAVE_SPREAD } else { -WAVE_SPREAD };
+
+            let mut frame = [0; 12];
+            for (i, zone) in frame.chunks_exact_mut(3).enumerate() {
+                zone.copy_from_slice(&color::hsv_to_rgb((i as f32).mul_add(spread, hue), 1.0, 1.0));
+            }
+            frame
+        }
+    }
+}
+
+/// Whether the firmware keeps animating this effect by itself
+pub fn is_animated(effect: BaseEffects) -> bool {
+    !matches!(effect, BaseEffects::Static)
+}
+
+pub fn play(manager: &mut Inner, profile: &Profile, effect: BaseEffects) {
+    let colors = profile.rgb_array();
+
+    clock::run(manager, |elapsed| render(effect, profile.speed, &colors, elapsed));
+}
+
+#[cfg(test)]
+mod tests {
+    use legion_rgb_driver::BaseEffects;
+
+    use super::{is_animated, render, BREATH_PERIOD, SMOOTH_PERIOD, WAVE_PERIOD};
+    use crate::manager::effects::color;
+
+    const COLORS: [u8; 12] = [255, 0, 0, 0, 255, 0, 0, 0, 255, 100, 100, 100];
+
+    #[test]
+    fn static_never_changes() {
+        for elapsed in [0.0, 1.5, 100.0] {
+            assert_eq!(render(BaseEffects::Static, 1, &COLORS, elapsed), COLORS);
+        }
+        assert!(!is_animated(BaseEffects::Static));
+        assert!(is_animated(BaseEffects::Breath));
+    }
+
+    #[test]
+    fn breath_fades_in_and_out_without_going_dark() {
+        let period = BREATH_PERIOD[1];
+
+        let dimmest = render(BaseEffects::Breath, 2, &COLORS, 0.0);
+        assert_eq!(dimmest, [5, 0, 0, 0, 5, 0, 0, 0, 5, 2, 2, 2]);
+        assert_eq!(render(BaseEffects::Breath, 2, &COLORS, period / 2.0), COLORS);
+        assert_eq!(render(BaseEffects::Breath, 2, &COLORS, period), dimmest);
+
+        // Brightening up the whole way to the peak
+        let quarter = render(BaseEffects::Breath, 2, &COLORS, period / 4.0);
+        assert!(dimmest[0] < quarter[0] && quarter[0] < COLORS[0]);
+    }
+
+    #[test]
+    fn speeds_out_of_range_use_the_nearest_one() {
+        for elapsed in [0.3, 1.7] {
+            assert_eq!(render(BaseEffects::Smooth, 0, &COLORS, elapsed), render(BaseEffects::Smooth, 1, &COLORS, elapsed));
+            assert_eq!(render(BaseEffects::Smooth, 9, &COLORS, elapsed), render(BaseEffects::Smooth, 4, &COLORS, elapsed));
+        }
+    }
+
+    #[test]
+    fn smooth_goes_around_the_color_wheel_on_every_zone() {
+        let period = SMOOTH_PERIOD[0];
+
+        assert_eq!(render(BaseEffects::Smooth, 1, &COLORS, 0.0), [255, 0, 0].repeat(4).as_slice());
+        assert_eq!(render(BaseEffects::Smooth, 1, &COLORS, period / 3.0), [0, 255, 0].repeat(4).as_slice());
+        assert_eq!(render(BaseEffects::Smooth, 1, &COLORS, period * 2.0 / 3.0), [0, 0, 255].repeat(4).as_slice());
+    }
+
+    #[test]
+    fn waves_spread_the_hues_across_the_zones() {
+        let hues = |frame: [u8; 12]| color::zones(&frame).map(|rgb| color::rgb_to_hsv(rgb.map(|channel| channel as u8)).0.round());
+
+        assert_eq!(hues(render(BaseEffects::LeftWave, 1, &COLORS, 0.0)), [0.0, 60.0, 120.0, 180.0]);
+        assert_eq!(hues(render(BaseEffects::RightWave, 1, &COLORS, 0.0)), [0.0, 300.0, 240.0, 180.0]);
+
+        // A sixth of a period later every zone shows what its neighbour showed
+        let later = hues(render(BaseEffects::LeftWave, 1, &COLORS, WAVE_PERIOD[0] / 6.0));
+        assert_eq!(later, [60.0, 120.0, 180.0, 240.0]);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{
+    enums::{Direction, GradientMode, SwipeMode},
+    manager::{palette::Palette, profile::Profile, Inner},
+};
+
+use super::{canvas::Canvas, clock, swipe};
+
+/// How fast the hue of the gradient turns at speed 1, in degrees
+const HUE_DEGREES_PER_SECOND: f32 = 15.0;
+/// Width of the soft edge at the end of a meter
+const METER_EDGE: f32 = 0.05;
+
+pub fn play(manager: &mut Inner, profile: &Profile, mode: GradientMode) {
+    let palette = profile.palette.sorted();
+    let direction = profile.direction;
+    let speed = f32::from(profile.speed);
+
+    match mode {
+        GradientMode::Static => {
+            let mut canvas = Canvas::default();
+            draw(&mut canvas, &palette, direction);
+            manager.keyboard.set_canvas(&canvas).unwrap();
+        }
+        GradientMode::Scrolling => swipe::play_palette(manager, &palette, profile.speed, direction, SwipeMode::Change, false),
+        GradientMode::RotatingHue => {
+            let degrees_per_second = match direction {
+                Direction::Left => HUE_DEGREES_PER_SECOND,
+                Direction::Right => -HUE_DEGREES_PER_SECOND,
+            } * speed;
+
+            clock::run_canvas(manager, |canvas, elapsed| {
+                draw(canvas, &palette.rotate_hue(elapsed * degrees_per_second), direction);
+            });
+        }
+    }
+}
+
+/// Stretch the gradient across the canvas, starting from the side given by `direction`
+///
+/// Expects the palette to be [`sorted`](Palette::sorted).
+pub fn draw(canvas: &mut Canvas, palette: &Palette, direction: Direction) {
+    canvas.paint(|position| match direction {
+        Direction::Left => palette.sample(position),
+        Direction::Right => palette.sample(1.0 - position),
+    });
+}
+
+/// Fill the canvas up to `level` from the side given by `direction`, with the part of the gradient under it
+///
+/// Expects the palette to be [`sorted`](Palette::sorted).
+pub fn draw_meter(canvas: &mut Canvas, palette: &Palette, direction: Direction, level: f32) {
+    canvas.paint(|position| {
+        let distance = match direction {
+            Direction::Left => position,
+            Direction::Right => 1.0 - position,
+        };
+
+        let lit = ((level - distance) / METER_EDGE).clamp(0.0, 1.0);
+        palette.sample(distance).map(|channel| channel * lit)
+    });
+}
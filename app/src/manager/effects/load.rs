@@ -0,0 +1,162 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use sysinfo::{Disks, Networks, System, MINIMUM_CPU_UPDATE_INTERVAL};
+
+use crate::{
+    enums::{LoadMetric, MeterStyle},
+    manager::{profile::Profile, Inner},
+};
+
+use super::{clock, gradient};
+
+/// How often the metric is measured
+const READ_INTERVAL: Duration = Duration::from_millis(1000);
+const FPS: u32 = 20;
+/// How much of a change is still left to show after a second
+const SMOOTHING: f32 = 0.1;
+
+/// Anything that can report how busy some part of the machine is, from `0.0` to `1.0`
+pub trait LoadSource {
+    fn read(&mut self) -> Option<f32>;
+}
+
+pub struct CpuLoad {
+    system: System,
+}
+
+impl LoadSource for CpuLoad {
+    fn read(&mut self) -> Option<f32> {
+        self.system.refresh_cpu_usage();
+        Some(self.system.global_cpu_usage() / 100.0)
+    }
+}
+
+pub struct MemoryLoad {
+    system: System,
+}
+
+impl LoadSource for MemoryLoad {
+    fn read(&mut self) -> Option<f32> {
+        self.system.refresh_memory();
+        let total = self.system.total_memory();
+        (total > 0).then(|| self.system.used_memory() as f32 / total as f32)
+    }
+}
+
+/// Reads `gpu_busy_percent`, which the amdgpu and some other DRM drivers expose
+pub struct GpuLoad {
+    path: PathBuf,
+}
+
+impl GpuLoad {
+    fn find() -> Option<Self> {
+        fs::read_dir("/sys/class/drm").ok()?.flatten().find_map(|entry| {
+            let path = entry.path().join("device/gpu_busy_percent");
+            path.exists().then_some(Self { path })
+        })
+    }
+}
+
+impl LoadSource for GpuLoad {
+    fn read(&mut self) -> Option<f32> {
+        let percent: f32 = fs::read_to_string(&self.path).ok()?.trim().parse().ok()?;
+        Some(percent / 100.0)
+    }
+}
+
+/// Bytes read and written across every disk, relative to `full_scale` bytes per second
+pub struct DiskLoad {
+    disks: Disks,
+    last_refresh: Instant,
+    full_scale: f32,
+}
+
+impl LoadSource for DiskLoad {
+    fn read(&mut self) -> Option<f32> {
+        self.disks.refresh(true);
+        let bytes: u64 = self.disks.list().iter().map(|disk| disk.usage().read_bytes + disk.usage().written_bytes).sum();
+
+        Some(throughput(bytes, &mut self.last_refresh, self.full_scale))
+    }
+}
+
+/// Bytes received and sent on every interface but loopback, relative to `full_scale` bytes per second
+pub struct NetworkLoad {
+    networks: Networks,
+    last_refresh: Instant,
+    full_scale: f32,
+}
+
+impl LoadSource for NetworkLoad {
+    fn read(&mut self) -> Option<f32> {
+        self.networks.refresh(true);
+        let bytes: u64 = self.networks.iter().filter(|(name, _)| *name != "lo").map(|(_, data)| data.received() + data.transmitted()).sum();
+
+        Some(throughput(bytes, &mut self.last_refresh, self.full_scale))
+    }
+}
+
+fn throughput(bytes: u64, last_refresh: &mut Instant, full_scale: f32) -> f32 {
+    let elapsed = last_refresh.elapsed().as_secs_f32().max(f32::EPSILON);
+    *last_refresh = Instant::now();
+
+    bytes as f32 / elapsed / full_scale.max(1.0)
+}
+
+/// Open the source of a metric, `full_scale` being in megabytes per second for the throughput ones
+pub fn open(metric: LoadMetric, full_scale: f32) -> Option<Box<dyn LoadSource>> {
+    let full_scale = full_scale * 1_000_000.0;
+
+    Some(match metric {
+        LoadMetric::Cpu => Box::new(CpuLoad { system: System::new() }),
+        LoadMetric::Memory => Box::new(MemoryLoad { system: System::new() }),
+        LoadMetric::Gpu => Box::new(GpuLoad::find()?),
+        LoadMetric::Disk => Box::new(DiskLoad {
+            disks: Disks::new_with_refreshed_list(),
+            last_refresh: Instant::now(),
+            full_scale,
+        }),
+        LoadMetric::Network => Box::new(NetworkLoad {
+            networks: Networks::new_with_refreshed_list(),
+            last_refresh: Instant::now(),
+            full_scale,
+        }),
+    })
+}
+
+pub fn play(manager: &mut Inner, profile: &Profile, metric: LoadMetric, style: MeterStyle) {
+    let Some(mut source) = open(metric, profile.load.full_scale) else {
+        eprintln!("{metric:?} load can't be measured on this system");
+        return;
+    };
+
+    // The CPU usage is measured between two refreshes
+    source.read();
+    std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+
+    let gradient = profile.load.gradient.sorted();
+    let direction = profile.direction;
+    let smoothing = SMOOTHING.powf(1.0 / FPS as f32);
+
+    let mut reading = 0.0;
+    let mut shown: f32 = 0.0;
+    let mut last_read: Option<Instant> = None;
+
+    clock::run_canvas_at(manager, FPS, |canvas, _| {
+        if last_read.is_none_or(|last_read| last_read.elapsed() >= READ_INTERVAL) {
+            reading = source.read().unwrap_or(reading).clamp(0.0, 1.0);
+            last_read = Some(Instant::now());
+        }
+
+        shown = shown.mul_add(smoothing, reading * (1.0 - smoothing));
+
+        match style {
+            MeterStyle::Bar => gradient::draw_meter(canvas, &gradient, direction, shown),
+            MeterStyle::Gauge => canvas.paint(|_| gradient.sample(shown)),
+        }
+    });
+}
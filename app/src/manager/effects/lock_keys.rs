@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    enums::LockKey,
+    input::{self, Key, KeyEvent, Keycode},
+    manager::{
+        leds::Leds,
+        overlay::{Overlay, Sampler},
+        profile::LockKeySettings,
+    },
+};
+
+/// How often the LEDs are read
+const READ_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Lights up zones while the lock keys are on
+pub struct LockKeysOverlay {
+    settings: LockKeySettings,
+    lit: Sampler<Vec<(LockKey, bool)>>,
+}
+
+impl LockKeysOverlay {
+    pub fn new(settings: LockKeySettings, leds: Leds) -> Self {
+        let initial = LockKey::iter().map(|key| (key, false)).collect();
+        let lit = Sampler::spawn(READ_INTERVAL, initial, move |_| {
+            LockKey::iter().map(|key| (key, settings.indicator(key).enabled && is_on(&leds, key))).collect()
+        });
+
+        Self { settings, lit }
+    }
+}
+
+impl Overlay for LockKeysOverlay {
+    fn draw(&mut self, frame: &mut [u8; 12]) -> bool {
+        let mut drawn = false;
+
+        for (key, lit) in self.lit.get() {
+            if lit {
+                let indicator = self.settings.indicator(key);
+                let start = usize::from(indicator.zone.min(3)) * 3;
+                frame[start..start + 3].copy_from_slice(&indicator.rgb);
+                drawn = true;
+            }
+        }
+
+        drawn
+    }
+}
+
+fn is_on(leds: &Leds, key: LockKey) -> bool {
+    match (key, leds.is_lit(key)) {
+        (_, Some(lit)) => lit,
+        // Some setups don't expose the LEDs at all, but Caps Lock can still be followed through its key
+        (LockKey::Caps, None) => tracked_caps_lock().load(Ordering::SeqCst),
+        (_, None) => false,
+    }
+}
+
+/// Caps Lock as followed from its presses, starting out as off since there is no way of telling otherwise
+fn tracked_caps_lock() -> &'static AtomicBool {
+    static CAPS_LOCK: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    CAPS_LOCK.get_or_init(|| {
+        let caps_lock = Arc::new(AtomicBool::new(false));
+        let toggled = caps_lock.clone();
+
+        // Keep listening for the rest of the program, so that the state survives switching profiles
+        let events = input::subscribe();
+        thread::spawn(move || {
+            for event in events {
+                if event == KeyEvent::Press(Key::Code(Keycode::CapsLock)) {
+                    toggled.fetch_xor(true, Ordering::SeqCst);
+                }
+            }
+        });
+
+        caps_lock
+    })
+}
@@ -1,22 +1,40 @@
-use default_ui::{show_brightness, show_direction, show_effect_settings};
-use eframe::egui::{self, ComboBox, Slider};
+use default_ui::{
+    show_audio_settings, show_battery_settings, show_brightness, show_direction, show_effect_settings, show_palette, show_procedural_settings, show_reactive_settings, show_temperature_settings,
+    show_typing_settings,
+};
+use eframe::egui::{self, ComboBox, DragValue, Slider};
 use strum::IntoEnumIterator;
 
 use crate::{
-    enums::{Effects, SwipeMode},
+    enums::{Effects, GradientMode, LoadMetric, MeterStyle, RenderMode, SwipeMode, VisualizerMode},
     manager::profile::Profile,
 };
 
 pub mod ambient;
+pub mod audio;
+pub mod battery;
+pub mod canvas;
 pub mod christmas;
+pub mod clock;
+pub mod color;
+pub mod comet;
 pub mod default_ui;
 pub mod disco;
 pub mod fade;
+pub mod fire;
+pub mod firmware;
+pub mod gradient;
 pub mod lightning;
+pub mod load;
+pub mod lock_keys;
+pub mod plasma;
+pub mod rainbow;
+pub mod reactive;
 pub mod ripple;
 pub mod swipe;
 pub mod temperature;
-pub mod zones;
+pub mod twinkle;
+pub mod typing;
 
 pub fn show_effect_ui(ui: &mut egui::Ui, profile: &mut Profile, update_lights: &mut bool, theme: &crate::gui::style::Theme) {
     let mut effect = profile.effect;
@@ -35,6 +53,25 @@ pub fn show_effect_ui(ui: &mut egui::Ui, profile: &mut Profile, update_lights: &
                     }
                 });
                 *update_lights |= ui.add_enabled(matches!(mode, SwipeMode::Fill), egui::Checkbox::new(clean_with_black, "Clean with black")).changed();
+
+                if profile.effect.takes_palette() {
+                    show_palette(ui, "Palette", &mut profile.palette, update_lights);
+                }
+            });
+        }
+        Effects::Gradient { mode } => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                show_effect_settings(ui, profile, update_lights);
+                ComboBox::from_label("Gradient mode").width(30.0).selected_text(format!("{:?}", mode)).show_ui(ui, |ui| {
+                    for gradient_mode in GradientMode::iter() {
+                        *update_lights |= ui.selectable_value(mode, gradient_mode, format!("{:?}", gradient_mode)).changed();
+                    }
+                });
+                show_palette(ui, "Palette", &mut profile.palette, update_lights);
             });
         }
         Effects::AmbientLight { fps, saturation_boost } => {
@@ -54,6 +91,124 @@ pub fn show_effect_ui(ui: &mut egui::Ui, profile: &mut Profile, update_lights: &
                 });
             });
         }
+        Effects::Fire | Effects::Twinkle | Effects::Comet | Effects::Plasma | Effects::Rainbow => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                show_effect_settings(ui, profile, update_lights);
+                show_procedural_settings(ui, profile, update_lights);
+
+                if profile.effect.takes_palette() {
+                    show_palette(ui, "Palette", &mut profile.palette, update_lights);
+                }
+            });
+        }
+        Effects::AudioVisualizer { mode } => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                ComboBox::from_label("Visualizer mode").width(30.0).selected_text(format!("{:?}", mode)).show_ui(ui, |ui| {
+                    for visualizer_mode in VisualizerMode::iter() {
+                        *update_lights |= ui.selectable_value(mode, visualizer_mode, format!("{:?}", visualizer_mode)).changed();
+                    }
+                });
+                show_audio_settings(ui, profile, update_lights);
+                show_palette(ui, "Palette", &mut profile.palette, update_lights);
+            });
+        }
+        Effects::LoadMeter { metric, style } => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                ComboBox::from_label("Metric").width(30.0).selected_text(format!("{:?}", metric)).show_ui(ui, |ui| {
+                    for load_metric in LoadMetric::iter() {
+                        *update_lights |= ui.selectable_value(metric, load_metric, format!("{:?}", load_metric)).changed();
+                    }
+                });
+                ComboBox::from_label("Style").width(30.0).selected_text(format!("{:?}", style)).show_ui(ui, |ui| {
+                    for meter_style in MeterStyle::iter() {
+                        *update_lights |= ui.selectable_value(style, meter_style, format!("{:?}", meter_style)).changed();
+                    }
+                });
+                ui.add_enabled_ui(matches!(metric, LoadMetric::Disk | LoadMetric::Network), |ui| {
+                    ui.horizontal(|ui| {
+                        *update_lights |= ui.add(DragValue::new(&mut profile.load.full_scale).range(1.0..=10_000.0).suffix(" MB/s")).changed();
+                        ui.label("Full scale");
+                    });
+                });
+                show_palette(ui, "Gradient", &mut profile.load.gradient, update_lights);
+            });
+        }
+        Effects::Reactive => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_reactive_settings(ui, profile, update_lights);
+            });
+        }
+        Effects::TypingSpeed { style } => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                ComboBox::from_label("Style").width(30.0).selected_text(format!("{:?}", style)).show_ui(ui, |ui| {
+                    for meter_style in MeterStyle::iter() {
+                        *update_lights |= ui.selectable_value(style, meter_style, format!("{:?}", meter_style)).changed();
+                    }
+                });
+                show_typing_settings(ui, profile, update_lights);
+            });
+        }
+        Effects::Heatmap => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_typing_settings(ui, profile, update_lights);
+            });
+        }
+        Effects::Battery => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                show_battery_settings(ui, profile, update_lights);
+            });
+        }
+        Effects::Temperature => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_temperature_settings(ui, profile, update_lights);
+            });
+        }
+        Effects::Breath | Effects::Smooth | Effects::Wave => {
+            ui.scope(|ui| {
+                ui.style_mut().spacing.item_spacing = theme.spacing.default;
+
+                show_brightness(ui, profile, update_lights);
+                show_direction(ui, profile, update_lights);
+                show_effect_settings(ui, profile, update_lights);
+
+                if let Some(render_mode) = profile.render_mode_mut() {
+                    ComboBox::from_label("Render mode").width(30.0).selected_text(format!("{:?}", render_mode)).show_ui(ui, |ui| {
+                        for mode in RenderMode::iter() {
+                            *update_lights |= ui.selectable_value(render_mode, mode, format!("{:?}", mode)).changed();
+                        }
+                    });
+                }
+            });
+        }
         _ => {
             default_ui::show(ui, profile, update_lights, &theme.spacing);
         }
@@ -0,0 +1,102 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    enums::Direction,
+    manager::{profile::Profile, Inner},
+};
+
+use super::clock;
+
+/// How many noise cells fit across the keyboard
+const SCALE: f32 = 2.5;
+/// How fast the noise drifts sideways at speed 1, in noise cells per second
+const DRIFT: f32 = 0.3;
+/// How fast the noise changes shape at speed 1
+const EVOLVE: f32 = 0.25;
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let mut rng = StdRng::seed_from_u64(profile.procedural.seed);
+    let noise = Perlin::new(&mut rng);
+    let palette = profile.palette.sorted();
+    let speed = f32::from(profile.speed);
+
+    let drift = match profile.direction {
+        Direction::Left => -DRIFT,
+        Direction::Right => DRIFT,
+    } * speed;
+
+    clock::run_canvas(manager, |canvas, elapsed| {
+        let offset = elapsed * drift;
+        let time = elapsed * EVOLVE * speed;
+
+        canvas.paint(|position| {
+            let x = position.mul_add(SCALE, offset);
+            // A second, finer octave keeps it from looking too blobby
+            let value = noise.get(x, time).mul_add(0.7, noise.get(x * 2.0, time * 2.0) * 0.3);
+
+            palette.sample_wrapped(value.mul_add(0.75, 0.5))
+        });
+    });
+}
+
+/// Two dimensional gradient noise, shuffled by a seed
+struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    fn new(rng: &mut StdRng) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(rng);
+
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+
+        Self { permutation }
+    }
+
+    fn hash(&self, x: usize, y: usize) -> u8 {
+        self.permutation[usize::from(self.permutation[x]) + y]
+    }
+
+    /// Noise at the given point, roughly in the `-1.0..=1.0` range
+    fn get(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+
+        let xi = (x0 as i64).rem_euclid(256) as usize;
+        let yi = (y0 as i64).rem_euclid(256) as usize;
+
+        let corner = |cx: usize, cy: usize, gx: f32, gy: f32| gradient(self.hash(xi + cx, (yi + cy) % 256), gx, gy);
+
+        let (u, v) = (fade(dx), fade(dy));
+
+        let bottom = lerp(corner(0, 0, dx, dy), corner(1, 0, dx - 1.0, dy), u);
+        let top = lerp(corner(0, 1, dx, dy - 1.0), corner(1, 1, dx - 1.0, dy - 1.0), u);
+
+        lerp(bottom, top, v)
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * t.mul_add(t.mul_add(6.0, -15.0), 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    (b - a).mul_add(t, a)
+}
+
+fn gradient(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => y - x,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{
+    enums::Direction,
+    manager::{profile::Profile, Inner},
+};
+
+use super::{clock, color};
+
+/// How fast the rainbow scrolls at speed 1, in degrees of hue per second
+const DEGREES_PER_SECOND: f32 = 30.0;
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let saturation = profile.procedural.saturation.clamp(0.0, 1.0);
+
+    // Match the direction the other scrolling effects move in
+    let degrees_per_second = match profile.direction {
+        Direction::Left => DEGREES_PER_SECOND,
+        Direction::Right => -DEGREES_PER_SECOND,
+    } * f32::from(profile.speed);
+
+    clock::run_canvas(manager, |canvas, elapsed| {
+        let shift = elapsed * degrees_per_second;
+
+        // The whole spectrum spans the keyboard
+        canvas.paint(|position| color::hsv_to_rgb(position.mul_add(-360.0, shift), saturation, 1.0).map(f32::from));
+    });
+}
@@ -0,0 +1,44 @@
+use crate::{
+    input::{self, KeyEvent},
+    manager::{key_zones, profile::Profile, Inner},
+};
+
+use super::{clock, color};
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let events = input::subscribe();
+
+    let settings = &profile.reactive;
+    let base = color::zones(&profile.rgb_array());
+    let press = settings.press_color.map(f32::from);
+    let decay_seconds = (settings.decay_ms as f32 / 1000.0).max(f32::EPSILON);
+
+    // How lit up each zone is, dropping at a steady rate and shaped by the curve when drawn
+    let mut energy = [0.0_f32; 4];
+    let mut last_frame = 0.0;
+
+    clock::run(manager, |elapsed| {
+        let delta = elapsed - last_frame;
+        last_frame = elapsed;
+
+        for zone in &mut energy {
+            *zone = (*zone - delta / decay_seconds).max(0.0);
+        }
+
+        while let Ok(event) = events.try_recv() {
+            if let KeyEvent::Press(key) = event {
+                if let Some(zone) = key_zones::zone_of(key) {
+                    energy[zone] = (energy[zone] + settings.press_intensity).min(1.0);
+                }
+            }
+        }
+
+        let mut frame = [0; 12];
+        for (i, zone) in frame.chunks_exact_mut(3).enumerate() {
+            let rgb = color::lerp(base[i], press, settings.curve.apply(energy[i]));
+            zone.copy_from_slice(&rgb.map(|channel| channel.round() as u8));
+        }
+
+        frame
+    });
+}
@@ -1,172 +1,90 @@
-use std::{
-    collections::HashSet,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+use std::{collections::HashSet, sync::atomic::Ordering};
+
+use crate::{
+    input::{self, Key, KeyEvent},
+    manager::{
+        effects::{
+            canvas::Canvas,
+            clock::{self, FrameClock},
+            color,
+        },
+        key_zones,
+        profile::Profile,
+        Inner,
     },
-    thread,
-    time::{Duration, Instant},
 };
 
-use device_query::{DeviceEvents, DeviceEventsHandler, Keycode};
+/// How long it takes a ripple to travel one zone at speed 1
+const STEP_SECONDS: f32 = 0.2;
+/// How far from its front a ripple is still visible
+const RIPPLE_WIDTH: f32 = 0.25;
 
-use crate::manager::{
-    profile::Profile,
-    {effects::zones::KEY_ZONES, Inner},
-};
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum RippleMove {
-    Center,
-    Left,
-    Right,
-    Off,
+struct Ripple {
+    origin: f32,
+    started: f32,
 }
 
 pub fn play(manager: &mut Inner, p: &Profile) {
-    // Welcome to the definition of i-don't-know-what-im-doing
-    let stop_signals = manager.stop_signals.clone();
-
-    let kill_thread = Arc::new(AtomicBool::new(false));
-    let exit_thread = kill_thread.clone();
-
-    enum Event {
-        KeyPress(Keycode),
-        KeyRelease(Keycode),
-    }
+    let events = input::subscribe();
 
-    let (tx, rx) = crossbeam_channel::unbounded::<Event>();
+    let colors = color::zones(&p.rgb_array());
+    let step = STEP_SECONDS / f32::from(p.speed);
 
-    thread::spawn(move || {
-        // Do this in order to avoid having to store the event handler struct somewhere,
-        // since it saves no data and serves only as a fancy function proxy for interacting with the real event loop
-        // This keeps the effect self contained, and other effects should probably use the same pattern
-        let event_handler = DeviceEventsHandler::new(Duration::from_millis(10)).unwrap_or(DeviceEventsHandler {});
+    let mut zone_pressed: [HashSet<Key>; 4] = [HashSet::new(), HashSet::new(), HashSet::new(), HashSet::new()];
+    // Held keys keep sending out ripples, one every step
+    let mut zone_last_ripple: [f32; 4] = [f32::NEG_INFINITY; 4];
+    let mut ripples: Vec<Ripple> = Vec::new();
 
-        // tx_clone.send(Event::KeyPress(Keycode::Meta)).unwrap();
-        let tx_clone = tx.clone();
-
-        let press_guard = event_handler.on_key_down(move |key| {
-            stop_signals.keyboard_stop_signal.store(true, Ordering::SeqCst);
-
-            let _ = tx_clone.send(Event::KeyPress(*key));
-        });
-
-        let release_guard = event_handler.on_key_up(move |key| {
-            let _ = tx.send(Event::KeyRelease(*key));
-        });
+    let mut canvas = Canvas::default();
+    let mut clock = FrameClock::new(clock::FPS);
 
+    'outer: while !manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
         loop {
-            if exit_thread.load(Ordering::SeqCst) {
-                drop(press_guard);
-                drop(release_guard);
-                break;
-            }
-
-            thread::sleep(Duration::from_millis(5));
-        }
-    });
-
-    let mut zone_pressed: [HashSet<Keycode>; 4] = [HashSet::new(), HashSet::new(), HashSet::new(), HashSet::new()];
-    let mut zone_state: [RippleMove; 4] = [RippleMove::Off, RippleMove::Off, RippleMove::Off, RippleMove::Off];
-
-    let mut last_step_time = Instant::now();
-
-    while !manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
-        match rx.try_recv() {
-            Ok(event) => match event {
-                Event::KeyPress(key) => {
-                    for (i, zone) in KEY_ZONES.iter().enumerate() {
-                        if zone.contains(&key) {
-                            zone_pressed[i].insert(key);
-                        }
+            match events.try_recv() {
+                Ok(KeyEvent::Press(key)) => {
+                    if let Some(zone) = key_zones::zone_of(key) {
+                        zone_pressed[zone].insert(key);
                     }
-
-                    manager.stop_signals.keyboard_stop_signal.store(false, Ordering::SeqCst);
                 }
-                Event::KeyRelease(key) => {
-                    for (i, zone) in KEY_ZONES.iter().enumerate() {
-                        if zone.contains(&key) {
-                            zone_pressed[i].remove(&key);
-                        }
+                Ok(KeyEvent::Release(key)) => {
+                    if let Some(zone) = key_zones::zone_of(key) {
+                        zone_pressed[zone].remove(&key);
                     }
                 }
-            },
-            Err(err) => {
-                if err == crossbeam_channel::TryRecvError::Disconnected {
-                    break;
-                }
+                Err(crossbeam_channel::TryRecvError::Empty) => break,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => break 'outer,
             }
         }
 
-        zone_state = advance_zone_state(zone_state, &mut last_step_time, &p.speed);
+        let now = clock.elapsed();
 
         for (i, pressed) in zone_pressed.iter().enumerate() {
-            if !pressed.is_empty() {
-                zone_state[i] = RippleMove::Center;
-            }
-        }
-
-        let rgb_array = p.rgb_array();
-        let mut final_arr: [u8; 12] = [0; 12];
-
-        for (i, ripple_move) in zone_state.iter().enumerate() {
-            if ripple_move != &RippleMove::Off {
-                final_arr[(i * 3)..((i * 3) + 3)].copy_from_slice(&rgb_array[(i * 3)..((i * 3) + 3)]);
+            if !pressed.is_empty() && now - zone_last_ripple[i] >= step {
+                zone_last_ripple[i] = now;
+                ripples.push(Ripple {
+                    origin: (i as f32 + 0.5) / 4.0,
+                    started: now,
+                });
             }
         }
 
-        manager.keyboard.transition_colors_to(&final_arr, 20, 0).unwrap();
-        thread::sleep(Duration::from_millis(50));
-    }
-
-    kill_thread.store(true, Ordering::SeqCst);
-}
-
-fn advance_zone_state(zone_state: [RippleMove; 4], last_step_time: &mut Instant, speed: &u8) -> [RippleMove; 4] {
-    let now = Instant::now();
-
-    if now - *last_step_time > Duration::from_millis((200 / *speed) as u64) {
-        let mut new_state: [RippleMove; 4] = [RippleMove::Off, RippleMove::Off, RippleMove::Off, RippleMove::Off];
-
-        *last_step_time = now;
+        // A ripple moves one zone (a quarter of the keyboard) per step
+        let radius = |ripple: &Ripple| (now - ripple.started) / step / 4.0;
+        ripples.retain(|ripple| radius(ripple) < 1.0 + RIPPLE_WIDTH);
 
-        // Process moves first, then add centers
-        for (i, zone_move) in zone_state.iter().enumerate() {
-            match zone_move {
-                RippleMove::Left => {
-                    if i != 0 {
-                        if let Some(left) = new_state.get_mut(i - 1) {
-                            *left = RippleMove::Left;
-                        }
-                    }
-                }
+        canvas.paint(|position| {
+            let zone = ((position * 4.0) as usize).min(3);
 
-                RippleMove::Right => {
-                    if let Some(right) = new_state.get_mut(i + 1) {
-                        *right = RippleMove::Right;
-                    }
-                }
-                _ => {}
+            let mut intensity: f32 = if zone_pressed[zone].is_empty() { 0.0 } else { 1.0 };
+            for ripple in &ripples {
+                let from_front = ((position - ripple.origin).abs() - radius(ripple)).abs();
+                intensity = intensity.max(1.0 - from_front / RIPPLE_WIDTH);
             }
-        }
-
-        for (i, ripple_move) in zone_state.iter().enumerate() {
-            if matches!(ripple_move, RippleMove::Center) {
-                if i != 0 {
-                    if let Some(left) = new_state.get_mut(i - 1) {
-                        *left = RippleMove::Left;
-                    }
-                }
 
-                if let Some(right) = new_state.get_mut(i + 1) {
-                    *right = RippleMove::Right;
-                }
-            }
-        }
+            colors[zone].map(|channel| channel * intensity.clamp(0.0, 1.0))
+        });
 
-        new_state
-    } else {
-        zone_state
+        manager.keyboard.set_canvas(&canvas).unwrap();
+        clock.wait();
     }
 }
@@ -1,55 +1,89 @@
-use std::{sync::atomic::Ordering, thread, time::Duration};
-
 use crate::{
     enums::{Direction, SwipeMode},
-    manager::{profile::Profile, Inner},
+    manager::{palette::Palette, profile::Profile, Inner},
+};
+
+use super::{
+    canvas::{self, Canvas},
+    clock, color,
 };
 
-const STEPS: u8 = 150;
+/// How fast the colors scroll in the `Change` mode at speed 1
+const CHANGE_ZONES_PER_SECOND: f32 = 0.6;
+/// How fast each color is swept in the `Fill` mode at speed 1
+const FILL_ZONES_PER_SECOND: f32 = 3.0;
+/// Width of the soft edge at the front of a fill
+const FILL_EDGE: f32 = 0.25;
 
 pub fn play(manager: &mut Inner, profile: &Profile, mode: SwipeMode, clean_with_black: bool) {
-    let mut change_rgb_array = profile.rgb_array();
-    let fill_rgb_array = profile.rgb_array();
-    // Placed here so we don't reset it every loop
-    let mut used_colors_array: [u8; 12] = [0; 12];
-
-    let steps = STEPS / profile.speed;
-
-    while !manager.stop_signals.manager_stop_signal.load(Ordering::SeqCst) {
-        match mode {
-            SwipeMode::Change => {
-                match profile.direction {
-                    Direction::Left => change_rgb_array.rotate_right(3),
-                    Direction::Right => change_rgb_array.rotate_left(3),
-                }
-                manager.keyboard.transition_colors_to(&change_rgb_array, steps, 10).unwrap();
-            }
-            SwipeMode::Fill => {
-                // A little hacky to avoid type mismatch errors, but you gotta do what you gotta do
-                let range: Vec<usize> = match profile.direction {
-                    Direction::Left => (0..4).collect(),
-                    Direction::Right => (0..4).rev().collect(),
+    let palette = Palette::from_zones(&profile.rgb_array());
+    play_palette(manager, &palette, profile.speed, profile.direction, mode, clean_with_black);
+}
+
+/// Swipe through the stops of a palette rather than the colors of the zones
+pub fn play_palette(manager: &mut Inner, palette: &Palette, speed: u8, direction: Direction, mode: SwipeMode, clean_with_black: bool) {
+    let palette = palette.sorted();
+    let speed = f32::from(speed);
+
+    match mode {
+        SwipeMode::Change => clock::run_canvas(manager, |canvas, elapsed| {
+            draw_change(canvas, &palette, direction, elapsed * speed * CHANGE_ZONES_PER_SECOND);
+        }),
+        SwipeMode::Fill => {
+            let colors = palette.colors();
+
+            let mut sweeps = Vec::new();
+            for i in 0..colors.len() {
+                // Fills start from the side the swipe is coming from
+                let color = match direction {
+                    Direction::Left => colors[i],
+                    Direction::Right => colors[colors.len() - 1 - i],
                 };
 
-                for i in range.clone() {
-                    for j in range.clone() {
-                        used_colors_array[j * 3] = fill_rgb_array[i * 3];
-                        used_colors_array[j * 3 + 1] = fill_rgb_array[i * 3 + 1];
-                        used_colors_array[j * 3 + 2] = fill_rgb_array[i * 3 + 2];
-                        manager.keyboard.transition_colors_to(&used_colors_array, steps, 1).unwrap();
-                    }
-                    if clean_with_black {
-                        for j in range.clone() {
-                            used_colors_array[j * 3] = 0;
-                            used_colors_array[j * 3 + 1] = 0;
-                            used_colors_array[j * 3 + 2] = 0;
-                            manager.keyboard.transition_colors_to(&used_colors_array, steps, 1).unwrap();
-                        }
-                    }
+                sweeps.push(color);
+                if clean_with_black {
+                    sweeps.push([0.0; 3]);
                 }
             }
-        }
 
-        thread::sleep(Duration::from_millis(20));
+            if sweeps.is_empty() {
+                return;
+            }
+
+            let sweep_duration = canvas::ZONES as f32 / (speed * FILL_ZONES_PER_SECOND);
+
+            clock::run_canvas(manager, |canvas, elapsed| {
+                draw_fill(canvas, &sweeps, direction, elapsed / sweep_duration);
+            });
+        }
     }
 }
+
+/// Scroll a looping gradient across the canvas, `offset` being how many zones it has moved so far
+///
+/// Expects the palette to be [`sorted`](Palette::sorted).
+pub fn draw_change(canvas: &mut Canvas, palette: &Palette, direction: Direction, offset: f32) {
+    // Left moves the colors towards the right side of the keyboard, matching the older zone based implementation
+    let shift = match direction {
+        Direction::Left => offset,
+        Direction::Right => -offset,
+    } / canvas::ZONES as f32;
+
+    canvas.paint(|position| palette.sample_wrapped(position - shift));
+}
+
+/// Sweep each color over the previous one, `progress` being the amount of sweeps done so far
+pub fn draw_fill(canvas: &mut Canvas, sweeps: &[[f32; 3]], direction: Direction, progress: f32) {
+    let current = progress as usize % sweeps.len();
+    let previous = (current + sweeps.len() - 1) % sweeps.len();
+    let front = progress.fract() * (1.0 + FILL_EDGE);
+
+    canvas.paint(|position| {
+        let distance = match direction {
+            Direction::Left => position,
+            Direction::Right => 1.0 - position,
+        };
+
+        color::lerp(sweeps[previous], sweeps[current], (front - distance) / FILL_EDGE)
+    });
+}
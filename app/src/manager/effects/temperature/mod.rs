@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use crate::manager::{
+    palette::Palette,
+    profile::{Profile, TemperatureSettings},
+    Inner,
+};
+
+use super::clock;
+
+use sensor::TemperatureSource;
+
+pub mod sensor;
+
+/// How often the sensors are read
+const READ_INTERVAL: Duration = Duration::from_millis(500);
+/// There is no point in redrawing a slowly changing temperature as often as other effects
+const FPS: u32 = 20;
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let settings = &profile.temperature;
+
+    let zones = if settings.per_zone { 4 } else { 1 };
+
+    let mut sensors: Vec<Box<dyn TemperatureSource>> = Vec::new();
+    for i in 0..zones {
+        let name = settings.sensors.get(i).map_or("", String::as_str);
+
+        let Some(sensor) = sensor::open(name) else {
+            eprintln!("Could not find the temperature sensor {name:?}");
+            return;
+        };
+
+        sensors.push(sensor);
+    }
+
+    let mut thermometer = Thermometer::new(settings, sensors);
+
+    clock::run_at(manager, FPS, |_| thermometer.frame());
+}
+
+/// Turns sensor readings into colors, one sensor per zone or a single one for the whole keyboard
+pub struct Thermometer {
+    sensors: Vec<Box<dyn TemperatureSource>>,
+    readings: Vec<Option<f32>>,
+    shown: Vec<Option<f32>>,
+    last_read: Option<Instant>,
+    gradient: Palette,
+    min: f32,
+    range: f32,
+    /// Per frame, so that the amount left after a second matches the setting
+    smoothing: f32,
+}
+
+impl Thermometer {
+    pub fn new(settings: &TemperatureSettings, sensors: Vec<Box<dyn TemperatureSource>>) -> Self {
+        Self {
+            readings: vec![None; sensors.len()],
+            shown: vec![None; sensors.len()],
+            sensors,
+            last_read: None,
+            gradient: settings.gradient.sorted(),
+            min: settings.min,
+            range: (settings.max - settings.min).max(1.0),
+            smoothing: settings.smoothing.clamp(0.0, 0.99).powf(1.0 / FPS as f32),
+        }
+    }
+
+    /// Read the sensors if it's time to and render the next frame, zones without a reading yet being left off
+    pub fn frame(&mut self) -> [u8; 12] {
+        if self.last_read.is_none_or(|last_read| last_read.elapsed() >= READ_INTERVAL) {
+            for (reading, sensor) in self.readings.iter_mut().zip(&mut self.sensors) {
+                // Keep the last value around if a read fails
+                *reading = sensor.read().or(*reading);
+            }
+            self.last_read = Some(Instant::now());
+        }
+
+        for (shown, reading) in self.shown.iter_mut().zip(&self.readings) {
+            *shown = match (*shown, *reading) {
+                (Some(shown), Some(reading)) => Some(shown.mul_add(self.smoothing, reading * (1.0 - self.smoothing))),
+                (_, reading) => reading,
+            };
+        }
+
+        let mut frame = [0; 12];
+        if self.shown.is_empty() {
+            return frame;
+        }
+
+        for (i, zone) in frame.chunks_exact_mut(3).enumerate() {
+            if let Some(temperature) = self.shown[i % self.shown.len()] {
+                let rgb = self.gradient.sample((temperature - self.min) / self.range);
+                zone.copy_from_slice(&rgb.map(|channel| channel.round() as u8));
+            }
+        }
+
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    use crate::manager::profile::TemperatureSettings;
+
+    use super::{sensor::TemperatureSource, Thermometer};
+
+    /// Hands out the readings it was given, then fails
+    struct Scripted(Rc<RefCell<VecDeque<Option<f32>>>>);
+
+    impl TemperatureSource for Scripted {
+        fn read(&mut self) -> Option<f32> {
+            self.0.borrow_mut().pop_front().flatten()
+        }
+    }
+
+    fn settings() -> TemperatureSettings {
+        TemperatureSettings {
+            smoothing: 0.0,
+            ..TemperatureSettings::default()
+        }
+    }
+
+    fn rgb(settings: &TemperatureSettings, temperature: f32) -> [u8; 3] {
+        let gradient = settings.gradient.sorted();
+        gradient.sample((temperature - settings.min) / (settings.max - settings.min)).map(|channel| channel.round() as u8)
+    }
+
+    #[test]
+    fn single_sensor_colors_every_zone() {
+        let settings = settings();
+        let readings = Rc::new(RefCell::new(VecDeque::from([Some(settings.max)])));
+        let mut thermometer = Thermometer::new(&settings, vec![Box::new(Scripted(readings))]);
+
+        let frame = thermometer.frame();
+        for zone in frame.chunks_exact(3) {
+            assert_eq!(zone, rgb(&settings, settings.max));
+        }
+    }
+
+    #[test]
+    fn zones_wait_for_a_reading_and_keep_the_last_one() {
+        let settings = settings();
+        let first = Rc::new(RefCell::new(VecDeque::from([Some(settings.min)])));
+        let second = Rc::new(RefCell::new(VecDeque::from([None])));
+        let sensors: Vec<Box<dyn TemperatureSource>> = vec![Box::new(Scripted(first.clone())), Box::new(Scripted(second))];
+        let mut thermometer = Thermometer::new(&settings, sensors);
+
+        let frame = thermometer.frame();
+        assert_eq!(frame[0..3], rgb(&settings, settings.min));
+        // The second sensor hasn't read anything yet, so its zones stay off
+        assert_eq!(frame[3..6], [0; 3]);
+        assert_eq!(frame[6..9], rgb(&settings, settings.min));
+
+        // A failed read leaves the last temperature showing
+        thermometer.last_read = None;
+        assert_eq!(thermometer.frame()[0..3], rgb(&settings, settings.min));
+        assert!(first.borrow().is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use sysinfo::Components;
+
+/// Labels tried in order when no sensor was picked, covering the CPU package on AMD and Intel
+const AUTO_LABELS: [&str; 4] = ["Tctl", "Package id 0", "k10temp", "coretemp"];
+
+/// Anything that can report a temperature, in degrees Celsius
+pub trait TemperatureSource {
+    fn read(&mut self) -> Option<f32>;
+}
+
+/// A sensor from the list `sysinfo` finds
+pub struct ComponentSensor {
+    components: Components,
+    index: usize,
+}
+
+impl ComponentSensor {
+    pub fn new(label: &str) -> Option<Self> {
+        let components = Components::new_with_refreshed_list();
+        let index = components.iter().position(|component| component.label() == label)?;
+
+        Some(Self { components, index })
+    }
+}
+
+impl TemperatureSource for ComponentSensor {
+    fn read(&mut self) -> Option<f32> {
+        let component = self.components.list_mut().get_mut(self.index)?;
+        component.refresh();
+        component.temperature()
+    }
+}
+
+/// A `temp*_input` file under `/sys/class/hwmon`, or a hwmon directory to read `temp1_input` from
+pub struct HwmonSensor {
+    path: PathBuf,
+}
+
+impl HwmonSensor {
+    pub fn new(path: &Path) -> Self {
+        let path = if path.is_dir() { path.join("temp1_input") } else { path.to_path_buf() };
+        Self { path }
+    }
+}
+
+impl TemperatureSource for HwmonSensor {
+    fn read(&mut self) -> Option<f32> {
+        // Reported in millidegrees
+        let millidegrees: f32 = fs::read_to_string(&self.path).ok()?.trim().parse().ok()?;
+        Some(millidegrees / 1000.0)
+    }
+}
+
+/// Open a sensor by its label, a hwmon path, or pick one automatically if `name` is empty
+pub fn open(name: &str) -> Option<Box<dyn TemperatureSource>> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        let labels = available();
+        let label = AUTO_LABELS.iter().find_map(|auto| labels.iter().find(|label| label.contains(auto)))?;
+
+        return ComponentSensor::new(label).map(|sensor| Box::new(sensor) as Box<dyn TemperatureSource>);
+    }
+
+    if name.starts_with('/') {
+        return Some(Box::new(HwmonSensor::new(Path::new(name))));
+    }
+
+    ComponentSensor::new(name).map(|sensor| Box::new(sensor) as Box<dyn TemperatureSource>)
+}
+
+/// The labels of every sensor found on the system, looked up once
+pub fn available() -> &'static [String] {
+    static LABELS: OnceLock<Vec<String>> = OnceLock::new();
+
+    LABELS.get_or_init(|| Components::new_with_refreshed_list().iter().map(|component| component.label().to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::{HwmonSensor, TemperatureSource};
+
+    #[test]
+    fn reads_hwmon_millidegrees() {
+        let dir = env::temp_dir().join(format!("legion-kb-rgb-hwmon-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("temp1_input"), "54000\n").unwrap();
+        fs::write(dir.join("temp2_input"), "61500\n").unwrap();
+
+        // A directory falls back to its first sensor
+        assert_eq!(HwmonSensor::new(&dir).read(), Some(54.0));
+        assert_eq!(HwmonSensor::new(&dir.join("temp2_input")).read(), Some(61.5));
+        assert_eq!(HwmonSensor::new(&dir.join("temp3_input")).read(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+use std::f32::consts::PI;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::manager::{profile::Profile, Inner};
+
+use super::clock;
+
+/// Chances to light up a star per second at speed 1
+const TICKS_PER_SECOND: f32 = 8.0;
+/// Chance of a star lighting up on every tick
+const SPAWN_CHANCE: f64 = 0.5;
+/// How long a star lasts at speed 1, in seconds
+const LIFETIME: std::ops::Range<f32> = 0.8..2.0;
+/// How far a star's glow reaches, relative to the width of the keyboard
+const STAR_RADIUS: f32 = 0.08;
+
+struct Star {
+    position: f32,
+    rgb: [f32; 3],
+    born: f32,
+    lifetime: f32,
+}
+
+pub fn play(manager: &mut Inner, profile: &Profile) {
+    let mut rng = StdRng::seed_from_u64(profile.procedural.seed);
+    let speed = f32::from(profile.speed);
+    let tick_rate = TICKS_PER_SECOND * speed;
+
+    let mut colors = profile.palette.colors();
+    if colors.is_empty() {
+        colors.push([255.0; 3]);
+    }
+
+    let mut stars: Vec<Star> = Vec::new();
+    let mut ticks = 0;
+
+    clock::run_canvas(manager, |canvas, elapsed| {
+        let due = (elapsed * tick_rate) as u64;
+        while ticks < due {
+            if rng.random_bool(SPAWN_CHANCE) {
+                stars.push(Star {
+                    position: rng.random(),
+                    rgb: colors[rng.random_range(0..colors.len())],
+                    born: ticks as f32 / tick_rate,
+                    lifetime: rng.random_range(LIFETIME) / speed,
+                });
+            }
+            ticks += 1;
+        }
+
+        stars.retain(|star| elapsed - star.born < star.lifetime);
+
+        canvas.paint(|position| {
+            let mut rgb = [0.0; 3];
+
+            for star in &stars {
+                let glow = 1.0 - (position - star.position).abs() / STAR_RADIUS;
+                if glow <= 0.0 {
+                    continue;
+                }
+
+                // Fade in and back out over the lifetime of the star
+                let age = ((elapsed - star.born) / star.lifetime).clamp(0.0, 1.0);
+                let intensity = (age * PI).sin().powi(2) * glow;
+
+                for (channel, value) in rgb.iter_mut().zip(star.rgb) {
+                    *channel = value.mul_add(intensity, *channel).min(255.0);
+                }
+            }
+
+            rgb
+        });
+    });
+}
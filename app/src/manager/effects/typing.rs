@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+
+use crossbeam_channel::Receiver;
+
+use crate::{
+    enums::MeterStyle,
+    input::{self, Key, KeyEvent, Keycode},
+    manager::{histogram, key_zones, profile::Profile, Inner},
+};
+
+use super::{clock, gradient};
+
+/// How often the histogram is written to disk while recording
+const FLUSH_INTERVAL_SECONDS: f32 = 60.0;
+/// How much of a change in typing speed is still left to show after a second
+const SMOOTHING: f32 = 0.2;
+/// Presses a zone needs before the heatmap shows it at full heat, so that a handful of keys don't light it up
+const HEAT_FLOOR: f32 = 5.0;
+/// Characters in a word, as typing tests count them
+const WORD_LENGTH: f32 = 5.0;
+
+/// Modifiers and editing keys that don't produce anything on their own
+const IGNORED_KEYS: [Keycode; 12] = [
+    Keycode::LShift,
+    Keycode::RShift,
+    Keycode::LControl,
+    Keycode::RControl,
+    Keycode::LAlt,
+    Keycode::RAlt,
+    Keycode::LMeta,
+    Keycode::RMeta,
+    Keycode::CapsLock,
+    Keycode::Backspace,
+    Keycode::Delete,
+    Keycode::Escape,
+];
+
+/// Keeps count of the keys pressed while a typing effect runs, writing the histogram out now and then
+struct Recorder {
+    events: Receiver<KeyEvent>,
+    record: bool,
+    last_flush: f32,
+}
+
+impl Recorder {
+    fn new(profile: &Profile) -> Self {
+        Self {
+            events: input::subscribe(),
+            record: profile.typing.record_histogram,
+            last_flush: 0.0,
+        }
+    }
+
+    /// The keys pressed since the last call
+    fn presses(&mut self, elapsed: f32) -> Vec<Key> {
+        let mut presses = Vec::new();
+
+        while let Ok(event) = self.events.try_recv() {
+            if let KeyEvent::Press(key) = event {
+                if self.record {
+                    histogram::record(key);
+                }
+                presses.push(key);
+            }
+        }
+
+        if self.record && elapsed - self.last_flush >= FLUSH_INTERVAL_SECONDS {
+            histogram::flush();
+            self.last_flush = elapsed;
+        }
+
+        presses
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.record {
+            histogram::flush();
+        }
+    }
+}
+
+/// Rolling words per minute, shown on a gauge
+pub fn play_speed(manager: &mut Inner, profile: &Profile, style: MeterStyle) {
+    let mut recorder = Recorder::new(profile);
+
+    let settings = &profile.typing;
+    let gradient = settings.gradient.sorted();
+    let direction = profile.direction;
+    let window = settings.wpm_window.max(1.0);
+    let smoothing = SMOOTHING.powf(1.0 / clock::FPS as f32);
+
+    let mut presses: VecDeque<f32> = VecDeque::new();
+    let mut shown: f32 = 0.0;
+
+    clock::run_canvas(manager, |canvas, elapsed| {
+        let new = recorder
+            .presses(elapsed)
+            .into_iter()
+            .filter(|key| !matches!(key, Key::Code(code) if IGNORED_KEYS.contains(code)))
+            .count();
+        presses.extend(std::iter::repeat_n(elapsed, new));
+
+        while presses.front().is_some_and(|&pressed| elapsed - pressed > window) {
+            presses.pop_front();
+        }
+
+        // Don't underestimate the speed before a whole window has gone by
+        let measured = window.min(elapsed).max(1.0);
+        let wpm = presses.len() as f32 / WORD_LENGTH / (measured / 60.0);
+        let level = (wpm / settings.max_wpm.max(1.0)).clamp(0.0, 1.0);
+
+        shown = shown.mul_add(smoothing, level * (1.0 - smoothing));
+
+        match style {
+            MeterStyle::Bar => gradient::draw_meter(canvas, &gradient, direction, shown),
+            MeterStyle::Gauge => canvas.paint(|_| gradient.sample(shown)),
+        }
+    });
+}
+
+/// Zones warming up with the keys pressed in them, and cooling down over time
+pub fn play_heatmap(manager: &mut Inner, profile: &Profile) {
+    let mut recorder = Recorder::new(profile);
+
+    let settings = &profile.typing;
+    let gradient = settings.gradient.sorted();
+    let cool_down = settings.cool_down.max(1.0);
+
+    let mut heat = [0.0_f32; 4];
+    let mut last_frame = 0.0;
+
+    clock::run(manager, |elapsed| {
+        let cooling = (-(elapsed - last_frame) / cool_down).exp();
+        last_frame = elapsed;
+
+        for zone in &mut heat {
+            *zone *= cooling;
+        }
+
+        for key in recorder.presses(elapsed) {
+            if let Some(zone) = key_zones::zone_of(key) {
+                heat[zone] += 1.0;
+            }
+        }
+
+        let hottest = heat.iter().copied().fold(HEAT_FLOOR, f32::max);
+
+        let mut frame = [0; 12];
+        for (zone, heat) in frame.chunks_exact_mut(3).zip(heat) {
+            zone.copy_from_slice(&gradient.sample(heat / hottest).map(|channel| channel.round() as u8));
+        }
+
+        frame
+    });
+}
@@ -0,0 +1,107 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{input::Key, persist::Settings, util::StorageTrait};
+
+use super::key_zones;
+
+const FILE_NAME: &str = "keystrokes.json";
+
+/// How many times each key was pressed, kept on disk across sessions
+///
+/// Only the counts are stored, never the order keys were pressed in or anything else about what was typed.
+#[derive(Default, Serialize, Deserialize)]
+struct KeyHistogram {
+    counts: BTreeMap<String, u64>,
+}
+
+impl StorageTrait<'_> for KeyHistogram {}
+
+/// The counts as last read from disk, and the presses since then
+///
+/// Other instances may be writing to the same file, so presses are added to whatever is on disk by then instead of overwriting it.
+#[derive(Default)]
+struct Tally {
+    saved: KeyHistogram,
+    pending: BTreeMap<String, u64>,
+}
+
+static HISTOGRAM: Mutex<Option<Tally>> = Mutex::new(None);
+
+/// Work on the histogram, which is read from disk the first time it is needed
+fn with_tally<R>(f: impl FnOnce(&mut Tally) -> R) -> R {
+    let mut tally = HISTOGRAM.lock().unwrap();
+    f(tally.get_or_insert_with(|| Tally {
+        saved: load(),
+        pending: BTreeMap::new(),
+    }))
+}
+
+fn load() -> KeyHistogram {
+    KeyHistogram::load(&Settings::sibling(FILE_NAME)).unwrap_or_default()
+}
+
+pub fn record(key: Key) {
+    with_tally(|tally| *tally.pending.entry(key.to_string()).or_default() += 1);
+}
+
+/// Add the presses since the last time to the counts on disk
+pub fn flush() {
+    with_tally(|tally| {
+        if tally.pending.is_empty() {
+            return;
+        }
+
+        // What this instance last saw is better than nothing, should the file be caught halfway through being written
+        let mut merged = KeyHistogram::load(&Settings::sibling(FILE_NAME)).unwrap_or_else(|_| KeyHistogram { counts: tally.saved.counts.clone() });
+        for (key, count) in &tally.pending {
+            *merged.counts.entry(key.clone()).or_default() += count;
+        }
+
+        match merged.save(&Settings::sibling(FILE_NAME)) {
+            Ok(()) => {
+                tally.saved = merged;
+                tally.pending.clear();
+            }
+            // Kept for the next time around
+            Err(err) => eprintln!("{err:?}"),
+        }
+    });
+}
+
+pub fn clear() {
+    with_tally(|tally| {
+        *tally = Tally::default();
+
+        if let Err(err) = tally.saved.save(&Settings::sibling(FILE_NAME)) {
+            eprintln!("{err:?}");
+        }
+    });
+}
+
+/// Every key that was pressed and how many times, the most pressed first
+pub fn counts() -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = with_tally(|tally| {
+        let mut counts = tally.saved.counts.clone();
+        for (key, count) in &tally.pending {
+            *counts.entry(key.clone()).or_default() += count;
+        }
+        counts.into_iter().collect()
+    });
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+}
+
+/// How many presses landed in each zone
+pub fn zone_totals() -> [u64; 4] {
+    let mut totals = [0; 4];
+
+    for (key, count) in counts() {
+        if let Some(zone) = key.parse::<Key>().ok().and_then(key_zones::zone_of) {
+            totals[zone] += count;
+        }
+    }
+
+    totals
+}
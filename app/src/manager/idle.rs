@@ -0,0 +1,110 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use serde::{Deserialize, Serialize};
+
+use crate::input::{self, KeyEvent};
+
+use super::{output::Dimmer, power_supply::PowerSupply};
+
+/// How often the timeouts and the power source are checked while no key is pressed
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the lights take to dim once the keyboard goes idle
+const DIM_DURATION: Duration = Duration::from_secs(2);
+
+/// Dims or turns off the lights after a while without key presses, whatever effect is running
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct IdleSettings {
+    pub enabled: bool,
+    pub timeout_secs: u64,
+    /// A separate timeout used while running on battery
+    pub battery_timeout_secs: Option<u64>,
+    /// How bright the lights are kept while idle, in percent, `0` turning them off
+    pub level: u8,
+    /// How long the lights take to come back on a key press
+    pub restore_ms: u64,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 120,
+            battery_timeout_secs: None,
+            level: 0,
+            restore_ms: 300,
+        }
+    }
+}
+
+impl IdleSettings {
+    pub const MAX_TIMEOUT_SECS: u64 = 1800;
+    pub const MAX_RESTORE_MS: u64 = 3000;
+
+    fn timeout(&self, supply: &PowerSupply) -> Duration {
+        let secs = match self.battery_timeout_secs {
+            Some(secs) if supply.on_ac() == Some(false) => secs,
+            _ => self.timeout_secs,
+        };
+
+        Duration::from_secs(secs)
+    }
+
+    fn restore_duration(&self) -> Duration {
+        Duration::from_millis(self.restore_ms.min(Self::MAX_RESTORE_MS))
+    }
+}
+
+/// Dim the output once there's been no input for as long as the settings say, until the output is gone
+pub fn watch(dimmer: Dimmer, settings: Arc<Mutex<IdleSettings>>) {
+    thread::spawn(move || {
+        let supply = PowerSupply::default();
+        // Only listen to the keyboard while it's needed
+        let mut events: Option<Receiver<KeyEvent>> = None;
+        let mut last_input = Instant::now();
+        let mut idle = false;
+
+        loop {
+            let settings = *settings.lock().unwrap();
+
+            if !settings.enabled {
+                events = None;
+                if idle && !dimmer.fade_to(1.0, settings.restore_duration()) {
+                    break;
+                }
+                idle = false;
+
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+
+            let receiver = events.get_or_insert_with(|| {
+                last_input = Instant::now();
+                input::subscribe()
+            });
+
+            let keep_going = match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(_) => {
+                    last_input = Instant::now();
+                    let was_idle = std::mem::replace(&mut idle, false);
+                    !was_idle || dimmer.fade_to(1.0, settings.restore_duration())
+                }
+                Err(RecvTimeoutError::Timeout) if !idle && last_input.elapsed() >= settings.timeout(&supply) => {
+                    idle = true;
+                    dimmer.fade_to(f32::from(settings.level.min(100)) / 100.0, DIM_DURATION)
+                }
+                Err(RecvTimeoutError::Timeout) => true,
+                Err(RecvTimeoutError::Disconnected) => false,
+            };
+
+            if !keep_going {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{LazyLock, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::Key;
+
+/// Which keys sit in which zone on each kind of keyboard, named after the keys in those spots on a US layout
+///
+/// ISO keyboards have an extra key next to the left shift, named IntlBackslash, while BackSlash is the key left of Enter.
+const MODELS: [(&str, &str); 4] = [
+    ("numpad", include_str!("../../res/zones/models/numpad.json")),
+    ("no-numpad", include_str!("../../res/zones/models/no-numpad.json")),
+    ("iso-numpad", include_str!("../../res/zones/models/iso-numpad.json")),
+    ("iso-no-numpad", include_str!("../../res/zones/models/iso-no-numpad.json")),
+];
+
+/// Where the keys that are reported by what they type rather than by where they are end up, such as on Windows
+const LAYOUTS: [(&str, &str); 4] = [
+    ("position", include_str!("../../res/zones/layouts/position.json")),
+    ("azerty", include_str!("../../res/zones/layouts/azerty.json")),
+    ("qwertz", include_str!("../../res/zones/layouts/qwertz.json")),
+    ("uk", include_str!("../../res/zones/layouts/uk.json")),
+];
+
+#[derive(Deserialize)]
+pub struct Model {
+    pub name: String,
+    zones: [Vec<String>; 4],
+}
+
+#[derive(Deserialize)]
+pub struct Layout {
+    pub name: String,
+    /// Keys as reported, and the US key in the same spot
+    #[serde(default)]
+    positions: HashMap<String, String>,
+}
+
+static PARSED_MODELS: LazyLock<Vec<(&str, Model)>> = LazyLock::new(|| parse(&MODELS));
+static PARSED_LAYOUTS: LazyLock<Vec<(&str, Layout)>> = LazyLock::new(|| parse(&LAYOUTS));
+
+fn parse<T: for<'de> Deserialize<'de>>(files: &[(&'static str, &str)]) -> Vec<(&'static str, T)> {
+    files
+        .iter()
+        .map(|(id, contents)| (*id, serde_json::from_str(contents).unwrap_or_else(|err| panic!("Bundled zone file {id} is invalid: {err}"))))
+        .collect()
+}
+
+/// The bundled models, by id
+pub fn models() -> &'static [(&'static str, Model)] {
+    &PARSED_MODELS
+}
+
+/// The bundled layouts, by id
+pub fn layouts() -> &'static [(&'static str, Layout)] {
+    &PARSED_LAYOUTS
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyZoneSettings {
+    pub model: String,
+    pub layout: String,
+    /// Keys moved to another zone by the user, taking precedence over the model and layout
+    pub overrides: BTreeMap<String, u8>,
+}
+
+impl Default for KeyZoneSettings {
+    fn default() -> Self {
+        Self {
+            model: MODELS[0].0.to_string(),
+            layout: LAYOUTS[0].0.to_string(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl KeyZoneSettings {
+    pub fn assign(&mut self, key: Key, zone: usize) {
+        self.overrides.insert(key.to_string(), zone.min(3) as u8);
+    }
+}
+
+static ACTIVE: LazyLock<RwLock<HashMap<Key, usize>>> = LazyLock::new(|| RwLock::new(build(&KeyZoneSettings::default())));
+
+/// Use the given model, layout and overrides from now on
+pub fn configure(settings: &KeyZoneSettings) {
+    *ACTIVE.write().unwrap() = build(settings);
+}
+
+/// The zone a key sits in, if it is in any
+pub fn zone_of(key: Key) -> Option<usize> {
+    ACTIVE.read().unwrap().get(&key).copied()
+}
+
+fn build(settings: &KeyZoneSettings) -> HashMap<Key, usize> {
+    // Unknown ids fall back to the first entry, which is also the default
+    let model = models().iter().find(|(id, _)| *id == settings.model).unwrap_or(&models()[0]);
+    let layout = layouts().iter().find(|(id, _)| *id == settings.layout).unwrap_or(&layouts()[0]);
+
+    let mut by_position = HashMap::new();
+    for (zone, keys) in model.1.zones.iter().enumerate() {
+        for key in keys.iter().filter_map(|name| name.parse::<Key>().ok()) {
+            by_position.insert(key, zone);
+        }
+    }
+
+    let mut zones = by_position.clone();
+    for (reported, position) in &layout.1.positions {
+        let (Ok(reported), Ok(position)) = (reported.parse::<Key>(), position.parse::<Key>()) else {
+            continue;
+        };
+
+        match by_position.get(&position) {
+            Some(zone) => zones.insert(reported, *zone),
+            None => zones.remove(&reported),
+        };
+    }
+
+    for (key, zone) in &settings.overrides {
+        if let Ok(key) = key.parse::<Key>() {
+            zones.insert(key, usize::from(*zone).min(3));
+        }
+    }
+
+    zones
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use device_query::Keycode;
+
+    use super::{build, layouts, models, KeyZoneSettings};
+    use crate::input::Key;
+
+    fn settings(model: &str, layout: &str) -> KeyZoneSettings {
+        KeyZoneSettings {
+            model: model.to_string(),
+            layout: layout.to_string(),
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn bundled_files_only_name_known_keys() {
+        for (id, model) in models() {
+            for name in model.zones.iter().flatten() {
+                assert!(name.parse::<Key>().is_ok(), "{id} names unknown key {name}");
+            }
+        }
+
+        for (id, layout) in layouts() {
+            for name in layout.positions.iter().flat_map(|(reported, position)| [reported, position]) {
+                assert!(name.parse::<Key>().is_ok(), "{id} names unknown key {name}");
+            }
+        }
+    }
+
+    #[test]
+    fn keys_are_placed_by_model() {
+        let zones = build(&settings("numpad", "position"));
+        assert_eq!(zones.get(&Key::Code(Keycode::Escape)), Some(&0));
+        assert_eq!(zones.get(&Key::Code(Keycode::Space)), Some(&1));
+        assert_eq!(zones.get(&Key::Code(Keycode::Enter)), Some(&2));
+        assert_eq!(zones.get(&Key::Code(Keycode::Numpad5)), Some(&3));
+        assert_eq!(zones.get(&Key::IntlBackslash), None);
+
+        let zones = build(&settings("no-numpad", "position"));
+        assert_eq!(zones.get(&Key::Code(Keycode::Numpad5)), None);
+    }
+
+    #[test]
+    fn iso_backslash_keys_sit_in_their_own_zones() {
+        for model in ["iso-numpad", "iso-no-numpad"] {
+            let zones = build(&settings(model, "position"));
+            assert_eq!(zones.get(&Key::IntlBackslash), zones.get(&Key::Code(Keycode::LShift)), "{model}");
+            assert_eq!(zones.get(&Key::Code(Keycode::BackSlash)), zones.get(&Key::Code(Keycode::Enter)), "{model}");
+            assert_ne!(zones.get(&Key::IntlBackslash), zones.get(&Key::Code(Keycode::BackSlash)), "{model}");
+        }
+    }
+
+    #[test]
+    fn layouts_move_keys_to_where_they_are() {
+        let zones = build(&settings("numpad", "azerty"));
+        assert_eq!(zones.get(&Key::Code(Keycode::A)), Some(&0));
+        assert_eq!(zones.get(&Key::Code(Keycode::M)), Some(&2));
+        assert_eq!(zones.get(&Key::Code(Keycode::Semicolon)), Some(&1));
+
+        let zones = build(&settings("iso-numpad", "uk"));
+        assert_eq!(zones.get(&Key::Code(Keycode::BackSlash)), Some(&0));
+        assert_eq!(zones.get(&Key::Code(Keycode::Apostrophe)), Some(&2));
+
+        // A key whose spot isn't on the model isn't anywhere either
+        let zones = build(&settings("numpad", "uk"));
+        assert_eq!(zones.get(&Key::Code(Keycode::BackSlash)), None);
+    }
+
+    #[test]
+    fn overrides_win_over_model_and_layout() {
+        let mut settings = settings("numpad", "azerty");
+        settings.assign(Key::Code(Keycode::A), 3);
+        settings.assign(Key::IntlBackslash, 9);
+        settings.overrides.insert("NotAKey".to_string(), 1);
+
+        let zones = build(&settings);
+        assert_eq!(zones.get(&Key::Code(Keycode::A)), Some(&3));
+        assert_eq!(zones.get(&Key::IntlBackslash), Some(&3));
+        assert_eq!(zones.len(), build(&self::settings("numpad", "azerty")).len() + 1);
+    }
+
+    #[test]
+    fn unknown_ids_fall_back_to_the_defaults() {
+        assert_eq!(build(&settings("missing", "missing")), build(&KeyZoneSettings::default()));
+    }
+}
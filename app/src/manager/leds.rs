@@ -0,0 +1,49 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::enums::LockKey;
+
+/// Where the kernel exposes the LEDs of input devices, mirroring their evdev LED state
+pub const SYSFS_PATH: &str = "/sys/class/leds";
+
+/// Reads a `leds` class directory, which can be pointed somewhere other than sysfs to fake one
+#[derive(Clone, Debug)]
+pub struct Leds {
+    root: PathBuf,
+}
+
+impl Default for Leds {
+    fn default() -> Self {
+        Self::new(SYSFS_PATH)
+    }
+}
+
+impl Leds {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Whether the LED of a lock key is lit on any keyboard, or `None` if no keyboard has one
+    pub fn is_lit(&self, key: LockKey) -> Option<bool> {
+        let suffix = format!("::{}", key.led_name());
+
+        let leds: Vec<PathBuf> = fs::read_dir(&self.root)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(&suffix))
+            .map(|entry| entry.path())
+            .collect();
+
+        if leds.is_empty() {
+            return None;
+        }
+
+        Some(leds.iter().any(|path| brightness(path).is_some_and(|brightness| brightness > 0)))
+    }
+}
+
+fn brightness(led: &Path) -> Option<u32> {
+    fs::read_to_string(led.join("brightness")).ok()?.trim().parse().ok()
+}
@@ -0,0 +1,251 @@
+//! Follows suspend, resume, screen locking and the lid through systemd-logind
+//!
+//! Setting `LEGION_KEYBOARD_LOGIND_BUS=session` talks to a stand-in on the session bus instead, which only has to
+//! own `org.freedesktop.login1` and implement the few members used here.
+
+use std::{
+    env,
+    sync::{atomic::Ordering, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::Sender;
+use error_stack::{Result, ResultExt};
+use thiserror::Error;
+use zbus::{
+    blocking::Connection,
+    proxy,
+    zvariant::{OwnedFd, OwnedObjectPath},
+    CacheProperties,
+};
+
+use crate::enums::Message;
+
+use super::{
+    output::Dimmer,
+    session::{SessionEvent, SessionSettings},
+    StopSignals,
+};
+
+const BUS_VAR: &str = "LEGION_KEYBOARD_LOGIND_BUS";
+/// logind doesn't announce changes to the lid, so it has to be asked every now and then
+const LID_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Time given to the compositor to turn the lights off before letting the system suspend
+const SLEEP_DELAY: Duration = Duration::from_millis(100);
+
+#[proxy(interface = "org.freedesktop.login1.Manager", default_service = "org.freedesktop.login1", default_path = "/org/freedesktop/login1")]
+trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[zbus(name = "GetSessionByPID")]
+    fn get_session_by_pid(&self, pid: u32) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn lid_closed(&self) -> zbus::Result<bool>;
+}
+
+#[proxy(interface = "org.freedesktop.login1.Session", default_service = "org.freedesktop.login1")]
+trait Session {
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn unlock(&self) -> zbus::Result<()>;
+}
+
+#[derive(Debug, Error)]
+pub enum LogindError {
+    #[error("Could not connect to systemd-logind")]
+    Connect,
+    #[error("Could not subscribe to systemd-logind signals")]
+    Subscribe,
+}
+
+/// What the lights have to follow, as both suspending and the lid can turn them off
+#[derive(Default)]
+struct State {
+    asleep: bool,
+    lid_closed: bool,
+    /// Holding this delays suspending until the lights are off
+    inhibitor: Option<OwnedFd>,
+}
+
+impl State {
+    /// How bright the lights should be, `0.0` if either suspending or the lid says they should be off
+    fn level(&self, settings: &SessionSettings) -> f32 {
+        if (self.asleep && settings.off_on_sleep) || (self.lid_closed && settings.off_on_lid_closed) {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Where the watcher sends what it sees
+#[derive(Clone)]
+struct Handler {
+    dimmer: Dimmer,
+    settings: Arc<Mutex<SessionSettings>>,
+    state: Arc<Mutex<State>>,
+    manager: ManagerProxyBlocking<'static>,
+    tx: Sender<Message>,
+    stop_signals: StopSignals,
+}
+
+impl Handler {
+    /// Hand an event to the effect thread, interrupting whatever is playing
+    fn forward(&self, event: SessionEvent) {
+        self.stop_signals.store_true();
+        let _ = self.tx.send(Message::Session { event });
+    }
+
+    /// Change what the lights follow, dimming them to match
+    ///
+    /// Returns whether they're being turned off for suspending, in which case the inhibitor is let go once they are.
+    fn update(&self, change: impl FnOnce(&mut State)) -> bool {
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+
+        self.dimmer.fade_to(state.level(&settings), Duration::ZERO);
+
+        // Only delay suspending while there's something to do before it. When already suspending, it's let go of once the lights are off
+        if !state.asleep {
+            if !settings.off_on_sleep || self.stop_signals.exit_signal.load(Ordering::SeqCst) {
+                state.inhibitor = None;
+            } else if state.inhibitor.is_none() {
+                state.inhibitor = take_inhibitor(&self.manager);
+            }
+        }
+
+        state.asleep && settings.off_on_sleep
+    }
+}
+
+/// Start following logind in the background
+pub fn watch(dimmer: Dimmer, settings: Arc<Mutex<SessionSettings>>, tx: Sender<Message>, stop_signals: StopSignals) -> Result<(), LogindError> {
+    let connection = if env::var(BUS_VAR).is_ok_and(|bus| bus == "session") {
+        Connection::session()
+    } else {
+        Connection::system()
+    }
+    .change_context(LogindError::Connect)?;
+
+    let manager = ManagerProxyBlocking::new(&connection).change_context(LogindError::Connect)?;
+    let handler = Handler {
+        dimmer,
+        settings,
+        state: Arc::new(Mutex::new(State::default())),
+        manager: manager.clone(),
+        tx,
+        stop_signals,
+    };
+
+    let sleep_signals = manager.receive_prepare_for_sleep().change_context(LogindError::Subscribe)?;
+    let sleep_handler = handler.clone();
+    thread::spawn(move || {
+        for signal in sleep_signals {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+
+            if args.start {
+                if sleep_handler.update(|state| state.asleep = true) {
+                    thread::sleep(SLEEP_DELAY);
+                }
+                sleep_handler.state.lock().unwrap().inhibitor = None;
+            } else {
+                sleep_handler.update(|state| state.asleep = false);
+                sleep_handler.forward(SessionEvent::Resume);
+            }
+        }
+    });
+
+    // Without a session, such as when running as a system service, there is nothing to lock
+    match manager.get_session_by_pid(std::process::id()) {
+        Ok(path) => watch_locks(&connection, path, &handler)?,
+        Err(err) => eprintln!("Not following screen locks, as there is no login session: {err}"),
+    }
+
+    let lid_manager = ManagerProxyBlocking::builder(&connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .change_context(LogindError::Connect)?;
+    // Also picks up changes to the settings, taking or letting go of the inhibitor as they ask
+    thread::spawn(move || {
+        while !handler.stop_signals.exit_signal.load(Ordering::SeqCst) {
+            let lid_closed = lid_manager.lid_closed();
+            handler.update(|state| {
+                if let Ok(lid_closed) = lid_closed {
+                    state.lid_closed = lid_closed;
+                }
+            });
+
+            thread::sleep(LID_POLL_INTERVAL);
+        }
+
+        handler.state.lock().unwrap().inhibitor = None;
+    });
+
+    Ok(())
+}
+
+fn watch_locks(connection: &Connection, path: OwnedObjectPath, handler: &Handler) -> Result<(), LogindError> {
+    let session = SessionProxyBlocking::builder(connection)
+        .path(path)
+        .and_then(|builder| builder.build())
+        .change_context(LogindError::Connect)?;
+
+    let locks = session.receive_lock().change_context(LogindError::Subscribe)?;
+    let lock_handler = handler.clone();
+    thread::spawn(move || {
+        for _ in locks {
+            lock_handler.forward(SessionEvent::Lock);
+        }
+    });
+
+    let unlocks = session.receive_unlock().change_context(LogindError::Subscribe)?;
+    let unlock_handler = handler.clone();
+    thread::spawn(move || {
+        for _ in unlocks {
+            unlock_handler.forward(SessionEvent::Unlock);
+        }
+    });
+
+    Ok(())
+}
+
+fn take_inhibitor(manager: &ManagerProxyBlocking<'static>) -> Option<OwnedFd> {
+    manager
+        .inhibit("sleep", env!("CARGO_PKG_NAME"), "Turn the keyboard lights off", "delay")
+        .inspect_err(|err| eprintln!("Could not delay suspending: {err}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::State;
+    use crate::manager::session::SessionSettings;
+
+    #[test]
+    fn lights_go_off_for_whichever_setting_applies() {
+        let state = |asleep, lid_closed| State { asleep, lid_closed, inhibitor: None };
+        let settings = |off_on_sleep, off_on_lid_closed| SessionSettings {
+            off_on_sleep,
+            off_on_lid_closed,
+            ..SessionSettings::default()
+        };
+
+        assert_eq!(state(false, false).level(&settings(true, true)), 1.0);
+        assert_eq!(state(true, false).level(&settings(true, false)), 0.0);
+        assert_eq!(state(true, false).level(&settings(false, true)), 1.0);
+        assert_eq!(state(false, true).level(&settings(false, true)), 0.0);
+        assert_eq!(state(false, true).level(&settings(true, false)), 1.0);
+        // Both at once, with the lid closed while suspending
+        assert_eq!(state(true, true).level(&settings(true, true)), 0.0);
+    }
+}
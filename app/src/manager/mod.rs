@@ -1,27 +1,65 @@
 use crate::enums::{Direction, Effects, Message};
 
 use crossbeam_channel::{Receiver, Sender};
-use effects::{ambient, christmas, disco, fade, lightning, ripple, swipe, temperature};
+use effects::{
+    ambient, audio,
+    battery::{self, BatteryOverlay},
+    canvas::Canvas,
+    christmas, comet, disco, fade, fire, firmware, gradient, lightning, load,
+    lock_keys::LockKeysOverlay,
+    plasma, rainbow, reactive, ripple, swipe, temperature, twinkle, typing,
+};
 use error_stack::{Result, ResultExt};
-use legion_rgb_driver::{BaseEffects, Keyboard, SPEED_RANGE};
+use idle::IdleSettings;
+use leds::Leds;
+use legion_rgb_driver::{BaseEffects, SPEED_RANGE};
+use output::{DimLayer, Dimmer, Output};
+use overlay::{Overlay, OverlayKind};
+use power::{PowerSettings, PowerState};
+use power_supply::PowerSupply;
 use profile::Profile;
 use rand::{rng, rngs::ThreadRng};
+use session::{SessionEvent, SessionSettings};
 use single_instance::SingleInstance;
 use std::{
     sync::atomic::{AtomicBool, Ordering},
     thread,
     time::Duration,
 };
-use std::{sync::Arc, thread::JoinHandle};
+use std::{
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
 use thiserror::Error;
+use transition::Transition;
 
 use self::custom_effect::{CustomEffect, EffectType};
 
 pub mod custom_effect;
 mod effects;
+mod histogram;
+pub mod idle;
+pub mod key_zones;
+mod leds;
+#[cfg(target_os = "linux")]
+mod logind;
+mod output;
+mod overlay;
+pub mod palette;
+pub mod power;
+mod power_supply;
 pub mod profile;
+pub mod session;
+pub mod transition;
 
-pub use effects::show_effect_ui;
+pub use effects::{
+    default_ui::{show_battery_warning, show_lock_key_overlay},
+    show_effect_ui,
+};
+pub use output::Preview;
+
+/// How long the lights take to turn off or back on when asked to
+const SWITCH_DURATION: Duration = Duration::from_millis(300);
 
 #[derive(Debug, Error, PartialEq)]
 #[error("Could not create keyboard manager")]
@@ -37,14 +75,28 @@ pub struct EffectManager {
     pub tx: Sender<Message>,
     inner_handle: Option<JoinHandle<()>>,
     stop_signals: StopSignals,
+    transition: Arc<Mutex<Transition>>,
+    idle: Arc<Mutex<IdleSettings>>,
+    session: Arc<Mutex<SessionSettings>>,
+    power: Arc<Mutex<PowerSettings>>,
+    switch: Dimmer,
+    preview: Preview,
 }
 
 /// Controls the keyboard lighting logic
 struct Inner {
-    keyboard: Keyboard,
+    keyboard: Output,
     rx: Receiver<Message>,
     stop_signals: StopSignals,
     last_profile: Profile,
+    /// What was shown before switching to the lock screen profile
+    before_lock: Detour,
+    transition: Arc<Mutex<Transition>>,
+    session: Arc<Mutex<SessionSettings>>,
+    /// What was shown before a power rule switched profiles
+    before_power: Detour,
+    power: Arc<Mutex<PowerSettings>>,
+    power_state: Arc<Mutex<PowerState>>,
     // Can't drop this else it stops "reserving" whatever underlying implementation identifier it uses
     #[allow(dead_code)]
     single_instance: SingleInstance,
@@ -54,6 +106,8 @@ struct Inner {
 pub enum OperationMode {
     Cli,
     Gui,
+    /// Running in the background without a window, taking requests from other instances
+    Daemon,
 }
 
 impl EffectManager {
@@ -61,6 +115,7 @@ impl EffectManager {
         let stop_signals = StopSignals {
             manager_stop_signal: Arc::new(AtomicBool::new(false)),
             keyboard_stop_signal: Arc::new(AtomicBool::new(false)),
+            exit_signal: Arc::new(AtomicBool::new(false)),
         };
 
         // Use the crate's name as the identifier, should be unique enough
@@ -78,44 +133,62 @@ impl EffectManager {
 
         let (tx, rx) = crossbeam_channel::unbounded::<Message>();
 
+        let transition = Arc::new(Mutex::new(Transition::default()));
+
+        let output = Output::new(keyboard, stop_signals.keyboard_stop_signal.clone());
+        let preview = output.preview();
+        let switch = output.dimmer(DimLayer::Switch);
+
+        let idle = Arc::new(Mutex::new(IdleSettings::default()));
+        idle::watch(output.dimmer(DimLayer::Idle), idle.clone());
+
+        let session = Arc::new(Mutex::new(SessionSettings::default()));
+        #[cfg(target_os = "linux")]
+        if matches!(operation_mode, OperationMode::Gui | OperationMode::Daemon) {
+            if let Err(err) = logind::watch(output.dimmer(DimLayer::Session), session.clone(), tx.clone(), stop_signals.clone()) {
+                eprintln!("{err:?}");
+            }
+        }
+
+        let power = Arc::new(Mutex::new(PowerSettings::default()));
+        let power_state = Arc::new(Mutex::new(PowerState::default()));
+        if matches!(operation_mode, OperationMode::Gui | OperationMode::Daemon) {
+            power::watch(output.dimmer(DimLayer::Power), power.clone(), power_state.clone(), tx.clone(), stop_signals.clone());
+        }
+
         let mut inner = Inner {
-            keyboard,
+            keyboard: output,
             rx,
             stop_signals: stop_signals.clone(),
             last_profile: Profile::default(),
+            before_lock: Detour::default(),
+            transition: transition.clone(),
+            session: session.clone(),
+            before_power: Detour::default(),
+            power: power.clone(),
+            power_state,
             single_instance,
         };
 
-        macro_rules! effect_thread_loop {
-            ($e: expr) => {
-                thread::spawn(move || loop {
-                    match $e {
-                        Some(message) => match message {
-                            Message::Profile { profile } => {
-                                inner.set_profile(profile);
-                            }
-                            Message::CustomEffect { effect } => {
-                                inner.custom_effect(&effect);
-                            }
-                            Message::Exit => break,
-                        },
-                        None => {
-                            thread::sleep(Duration::from_millis(20));
-                        }
-                    }
-                })
-            };
-        }
-
-        let inner_handle = match operation_mode {
-            OperationMode::Cli => effect_thread_loop!(inner.rx.try_recv().ok()),
-            OperationMode::Gui => effect_thread_loop!(inner.rx.try_iter().last()),
-        };
+        let inner_handle = thread::spawn(move || loop {
+            let messages: Vec<Message> = inner.rx.try_iter().collect();
+            if messages.is_empty() {
+                thread::sleep(Duration::from_millis(20));
+            } else if !inner.handle(messages) {
+                break;
+            }
+        });
 
         let manager = Self {
             tx,
             inner_handle: Some(inner_handle),
             stop_signals,
+            transition,
+            idle,
+            session,
+            power,
+            switch,
+            preview,
         };
 
         Ok(manager)
@@ -123,7 +196,7 @@ impl EffectManager {
 
     pub fn set_profile(&mut self, profile: Profile) {
         self.stop_signals.store_true();
-        self.tx.try_send(Message::Profile { profile }).unwrap();
+        self.tx.try_send(Message::Profile { profile: Box::new(profile) }).unwrap();
     }
 
     pub fn custom_effect(&self, effect: CustomEffect) {
@@ -131,8 +204,53 @@ impl EffectManager {
         self.tx.send(Message::CustomEffect { effect }).unwrap();
     }
 
-    pub fn shutdown(mut self) {
+    /// Leave the current frame on the keyboard, until a profile or custom effect is set again
+    pub fn pause(&self) {
         self.stop_signals.store_true();
+    }
+
+    /// Turn the lights off or back on, whatever is running
+    pub fn set_lights(&self, on: bool) {
+        self.switch.fade_to(if on { 1.0 } else { 0.0 }, SWITCH_DURATION);
+    }
+
+    /// Change how the lights blend into the next profile or custom effect
+    pub fn set_transition(&self, transition: Transition) {
+        *self.transition.lock().unwrap() = transition;
+    }
+
+    /// Change when and how much the lights dim while the keyboard isn't used
+    pub fn set_idle(&self, idle: IdleSettings) {
+        *self.idle.lock().unwrap() = idle;
+    }
+
+    /// Change what happens on suspend, lid and screen lock events
+    pub fn set_session(&self, session: SessionSettings) {
+        *self.session.lock().unwrap() = session;
+    }
+
+    /// Change what happens when running on battery
+    pub fn set_power(&self, power: PowerSettings) {
+        *self.power.lock().unwrap() = power;
+    }
+
+    /// What the keyboard is currently showing
+    pub fn preview(&self) -> [u8; 12] {
+        self.preview.frame()
+    }
+
+    /// The high resolution canvas behind the current frame, for effects that draw one
+    pub fn preview_canvas(&self) -> Option<Canvas> {
+        self.preview.canvas()
+    }
+
+    /// A view of what the keyboard is showing that can be kept around elsewhere
+    pub fn previewer(&self) -> Preview {
+        self.preview.clone()
+    }
+
+    pub fn shutdown(mut self) {
+        self.stop_signals.exit();
         self.tx.send(Message::Exit).unwrap();
         if let Some(handle) = self.inner_handle.take() {
             handle.join().unwrap();
@@ -140,13 +258,93 @@ impl EffectManager {
     }
 }
 
+/// The messages up to the first `Exit`, and whether there was none
+///
+/// What was sent before it still has to be shown, since the command line sends its one change and exits straight away.
+fn until_exit(mut messages: Vec<Message>) -> (Vec<Message>, bool) {
+    match messages.iter().position(|message| matches!(message, Message::Exit)) {
+        Some(exit) => {
+            messages.truncate(exit);
+            (messages, false)
+        }
+        None => (messages, true),
+    }
+}
+
+/// What the effect thread ends up showing after going through the messages that piled up
+enum Next {
+    Profile(Box<Profile>),
+    CustomEffect(CustomEffect),
+}
+
+/// A profile shown for a while instead of the one that was picked, like the lock screen one
+#[derive(Default)]
+struct Detour {
+    /// What to go back to, if away from it
+    before: Option<Profile>,
+}
+
+impl Detour {
+    /// The profile to switch to, if there is one, remembering what was shown unless already away from it
+    fn leave(&mut self, profile: Option<Profile>, shown: &Profile) -> Option<Profile> {
+        if profile.is_some() {
+            self.before.get_or_insert_with(|| shown.clone());
+        }
+        profile
+    }
+
+    /// The profile to show once the detour is over, going back to what was shown before unless told otherwise
+    fn back(&mut self, profile: Option<Profile>) -> Option<Profile> {
+        let before = self.before.take();
+        profile.or(before)
+    }
+}
+
 impl Inner {
+    /// Go through everything that was sent since the last effect started, returning whether to keep running
+    ///
+    /// Only the last profile or custom effect gets shown, but session and power changes along the way all have to be
+    /// taken into account, since they remember what to go back to afterwards.
+    fn handle(&mut self, messages: Vec<Message>) -> bool {
+        let (messages, keep_running) = until_exit(messages);
+        let mut next = None;
+
+        for message in messages {
+            let profile = match message {
+                Message::Profile { profile } => Some(*profile),
+                Message::CustomEffect { effect } => {
+                    next = Some(Next::CustomEffect(effect));
+                    None
+                }
+                Message::Session { event } => self.session_event(event),
+                Message::Power => Some(self.power_changed()),
+                // Already cut off by `until_exit`
+                Message::Exit => None,
+            };
+
+            if let Some(profile) = profile {
+                self.last_profile = profile.clone();
+                next = Some(Next::Profile(Box::new(profile)));
+            }
+        }
+
+        match next {
+            Some(Next::Profile(profile)) => self.set_profile(*profile),
+            Some(Next::CustomEffect(effect)) => self.custom_effect(&effect),
+            None => {}
+        }
+
+        keep_running
+    }
+
     fn set_profile(&mut self, mut profile: Profile) {
         self.last_profile = profile.clone();
         self.stop_signals.store_false();
         let mut rng = rng();
 
-        if profile.effect.is_built_in() {
+        self.begin_transition();
+
+        if profile.is_hardware_effect() {
             let clamped_speed = self.clamp_speed(profile.speed);
             self.keyboard.set_speed(clamped_speed).unwrap();
         } else {
@@ -155,11 +353,65 @@ impl Inner {
         }
 
         self.keyboard.set_brightness(profile.brightness as u8 + 1).unwrap();
+        self.set_overlays(&profile);
 
         self.apply_effect(&mut profile, &mut rng);
         self.stop_signals.store_false();
     }
 
+    /// The profile to show after a session event, if it calls for one
+    fn session_event(&mut self, event: SessionEvent) -> Option<Profile> {
+        let settings = self.session.lock().unwrap().clone();
+
+        match event {
+            SessionEvent::Resume => {
+                // The firmware tends to come back with its own idea of what to show
+                self.keyboard.resync();
+                Some(self.last_profile.clone())
+            }
+            SessionEvent::Lock => self.before_lock.leave(settings.lock_profile, &self.last_profile),
+            SessionEvent::Unlock => self.before_lock.back(settings.unlock_profile),
+        }
+    }
+
+    /// The profile to show for the power source now in use
+    fn power_changed(&mut self) -> Profile {
+        let settings = self.power.lock().unwrap().clone();
+        let state = *self.power_state.lock().unwrap();
+
+        let profile = match settings.rule(state) {
+            Some(rule) => self.before_power.leave(rule.profile.clone(), &self.last_profile),
+            None => self.before_power.back(settings.ac_profile),
+        };
+
+        // Even when keeping the same profile, the ambient light may have to stop or start capturing
+        profile.unwrap_or_else(|| self.last_profile.clone())
+    }
+
+    /// Whether the power rule that applies asks to leave the screen alone
+    fn ambient_paused(&self) -> bool {
+        let state = *self.power_state.lock().unwrap();
+        self.power.lock().unwrap().rule(state).is_some_and(|rule| rule.pause_ambient)
+    }
+
+    fn begin_transition(&self) {
+        let transition = *self.transition.lock().unwrap();
+        self.keyboard.begin_transition(transition);
+    }
+
+    /// Replace the overlays with the ones the profile asks for
+    fn set_overlays(&self, profile: &Profile) {
+        // The battery effect already shows the warning by itself
+        let battery = (profile.battery.overlay && profile.effect != Effects::Battery).then(|| Box::new(BatteryOverlay::new(&profile.battery, PowerSupply::default())) as Box<dyn Overlay>);
+        self.keyboard.set_overlay(OverlayKind::Battery, battery);
+
+        let lock_keys = profile
+            .lock_keys
+            .any_enabled()
+            .then(|| Box::new(LockKeysOverlay::new(profile.lock_keys, Leds::default())) as Box<dyn Overlay>);
+        self.keyboard.set_overlay(OverlayKind::LockKeys, lock_keys);
+    }
+
     fn clamp_speed(&self, speed: u8) -> u8 {
         speed.clamp(SPEED_RANGE.min().unwrap(), SPEED_RANGE.max().unwrap())
     }
@@ -170,42 +422,60 @@ impl Inner {
                 self.keyboard.set_colors_to(&profile.rgb_array()).unwrap();
                 self.keyboard.set_effect(BaseEffects::Static).unwrap();
             }
-            Effects::Breath => {
-                self.keyboard.set_colors_to(&profile.rgb_array()).unwrap();
-                self.keyboard.set_effect(BaseEffects::Breath).unwrap();
-            }
-            Effects::Smooth => {
-                self.keyboard.set_effect(BaseEffects::Smooth).unwrap();
-            }
-            Effects::Wave => {
-                let effect = match profile.direction {
-                    Direction::Left => BaseEffects::LeftWave,
-                    Direction::Right => BaseEffects::RightWave,
+            Effects::Breath | Effects::Smooth | Effects::Wave => {
+                let effect = match profile.effect {
+                    Effects::Breath => BaseEffects::Breath,
+                    Effects::Smooth => BaseEffects::Smooth,
+                    _ => match profile.direction {
+                        Direction::Left => BaseEffects::LeftWave,
+                        Direction::Right => BaseEffects::RightWave,
+                    },
                 };
-                self.keyboard.set_effect(effect).unwrap();
+
+                if profile.is_hardware_effect() {
+                    self.keyboard.set_colors_to(&profile.rgb_array()).unwrap();
+                    self.keyboard.set_effect(effect).unwrap();
+                } else {
+                    firmware::play(self, profile, effect);
+                }
             }
             Effects::Lightning => lightning::play(self, profile, rng),
+            Effects::AmbientLight { .. } if self.ambient_paused() => {
+                self.keyboard.set_colors_to(&profile.rgb_array()).unwrap();
+            }
             Effects::AmbientLight { mut fps, mut saturation_boost } => {
                 fps = fps.clamp(1, 60);
                 saturation_boost = saturation_boost.clamp(0.0, 1.0);
                 ambient::play(self, fps, saturation_boost);
             }
-            Effects::SmoothWave { mode, clean_with_black } => {
-                profile.rgb_zones = profile::arr_to_zones([255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 0, 255]);
-                swipe::play(self, profile, mode, clean_with_black);
-            }
+            Effects::AudioVisualizer { mode } => audio::play(self, profile, mode),
+            Effects::SmoothWave { mode, clean_with_black } => swipe::play_palette(self, &profile.palette, profile.speed, profile.direction, mode, clean_with_black),
             Effects::Swipe { mode, clean_with_black } => swipe::play(self, profile, mode, clean_with_black),
             Effects::Disco => disco::play(self, profile, rng),
             Effects::Christmas => christmas::play(self, rng),
             Effects::Fade => fade::play(self, profile),
-            Effects::Temperature => temperature::play(self),
+            Effects::Temperature => temperature::play(self, profile),
+            Effects::LoadMeter { metric, style } => load::play(self, profile, metric, style),
+            Effects::Battery => battery::play(self, profile),
             Effects::Ripple => ripple::play(self, profile),
+            Effects::Reactive => reactive::play(self, profile),
+            Effects::TypingSpeed { style } => typing::play_speed(self, profile, style),
+            Effects::Heatmap => typing::play_heatmap(self, profile),
+            Effects::Gradient { mode } => gradient::play(self, profile, mode),
+            Effects::Fire => fire::play(self, profile),
+            Effects::Twinkle => twinkle::play(self, profile),
+            Effects::Comet => comet::play(self, profile),
+            Effects::Plasma => plasma::play(self, profile),
+            Effects::Rainbow => rainbow::play(self, profile),
         }
     }
 
     fn custom_effect(&mut self, custom_effect: &CustomEffect) {
         self.stop_signals.store_false();
 
+        self.begin_transition();
+        self.keyboard.set_effect(BaseEffects::Static).unwrap();
+
         loop {
             for step in &custom_effect.effect_steps {
                 self.keyboard.set_brightness(step.brightness).unwrap();
@@ -231,6 +501,7 @@ impl Inner {
 
 impl Drop for EffectManager {
     fn drop(&mut self) {
+        self.stop_signals.exit();
         let _ = self.tx.send(Message::Exit);
     }
 }
@@ -239,6 +510,8 @@ impl Drop for EffectManager {
 pub struct StopSignals {
     pub manager_stop_signal: Arc<AtomicBool>,
     pub keyboard_stop_signal: Arc<AtomicBool>,
+    /// Only ever set once, for the threads watching the system to stop when the manager does
+    pub exit_signal: Arc<AtomicBool>,
 }
 
 impl StopSignals {
@@ -250,4 +523,58 @@ impl StopSignals {
         self.keyboard_stop_signal.store(false, Ordering::SeqCst);
         self.manager_stop_signal.store(false, Ordering::SeqCst);
     }
+    pub fn exit(&self) {
+        self.exit_signal.store(true, Ordering::SeqCst);
+        self.store_true();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{profile::Profile, until_exit, Detour};
+    use crate::enums::Message;
+
+    fn named(name: &str) -> Profile {
+        Profile {
+            name: Some(name.to_string()),
+            ..Profile::default()
+        }
+    }
+
+    #[test]
+    fn changes_sent_along_with_exit_are_still_shown() {
+        let profile = |name| Message::Profile { profile: Box::new(named(name)) };
+
+        let (messages, keep_running) = until_exit(vec![profile("cli"), Message::Exit, profile("too late")]);
+        assert!(!keep_running);
+        assert!(matches!(messages.as_slice(), [Message::Profile { profile }] if *profile.name.as_ref().unwrap() == "cli"));
+
+        let (messages, keep_running) = until_exit(vec![profile("gui"), Message::Power]);
+        assert!(keep_running);
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[test]
+    fn detour_goes_back_to_what_was_shown_first() {
+        let mut lock = Detour::default();
+
+        assert_eq!(lock.leave(Some(named("lock")), &named("picked")), Some(named("lock")));
+        // Locking again while locked must not forget what to go back to
+        assert_eq!(lock.leave(Some(named("lock")), &named("lock")), Some(named("lock")));
+        assert_eq!(lock.back(None), Some(named("picked")));
+        assert_eq!(lock.back(None), None);
+    }
+
+    #[test]
+    fn detour_without_a_profile_stays_put() {
+        let mut power = Detour::default();
+
+        assert_eq!(power.leave(None, &named("picked")), None);
+        assert_eq!(power.back(None), None);
+
+        assert_eq!(power.leave(Some(named("battery")), &named("picked")), Some(named("battery")));
+        assert_eq!(power.back(Some(named("ac"))), Some(named("ac")));
+        // Going back to a chosen profile still ends the detour
+        assert_eq!(power.back(None), None);
+    }
 }
@@ -0,0 +1,521 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use legion_rgb_driver::{
+    error::{RangeError, RangeErrorKind, Result},
+    BaseEffects, Keyboard, BRIGHTNESS_RANGE, SPEED_RANGE, ZONE_RANGE,
+};
+
+use super::{
+    effects::{canvas::Canvas, firmware},
+    overlay::{Overlay, OverlayKind},
+    transition::{self, Transition},
+};
+
+/// Minimum time between two writes to the keyboard
+const FRAME_INTERVAL: Duration = Duration::from_millis(10);
+/// How often overlays get redrawn, which is plenty for the slow pulses they show
+const OVERLAY_INTERVAL: Duration = Duration::from_millis(33);
+/// How long to wait before trying again after the keyboard couldn't be written to
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The state the effects ask for, which the compositor turns into what is actually sent to the keyboard
+struct Shared {
+    effect: BaseEffects,
+    speed: u8,
+    brightness: u8,
+    /// The last frame requested by the running effect
+    frame: [u8; 12],
+    /// The canvas the last frame was filtered down from, if the effect drew one
+    canvas: Option<Canvas>,
+    /// The last frame that was sent to the keyboard
+    shown: [u8; 12],
+    /// When the firmware effect currently on the keyboard was applied
+    applied_at: Instant,
+    crossfade: Option<Crossfade>,
+    overlays: BTreeMap<OverlayKind, Box<dyn Overlay>>,
+    /// How much each of the dimmers is keeping, all of which get multiplied together
+    dimming: BTreeMap<DimLayer, Dimming>,
+    /// Whether the last frame that was sent had overlays drawn over it or was dimmed
+    composed: bool,
+    /// Set when the keyboard may no longer be showing what was last sent, such as after a suspend
+    stale: bool,
+    exit: bool,
+}
+
+impl Shared {
+    /// The closest approximation of what is on the keyboard right now
+    fn preview(&self) -> [u8; 12] {
+        if self.crossfade.is_none() && !self.composed && firmware::is_animated(self.effect) {
+            firmware::render(self.effect, self.speed, &self.frame, self.applied_at.elapsed().as_secs_f32())
+        } else {
+            self.shown
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Crossfade {
+    from: [u8; 12],
+    transition: Transition,
+    /// Set once the new effect renders its first frame
+    started: Option<Instant>,
+}
+
+impl Crossfade {
+    /// Returns the blended frame, or `None` if the crossfade is over
+    fn sample(&self, to: &[u8; 12]) -> Option<[u8; 12]> {
+        let Some(started) = self.started else {
+            return Some(self.from);
+        };
+
+        let progress = started.elapsed().as_secs_f32() / self.transition.duration().as_secs_f32();
+
+        if progress >= 1.0 {
+            None
+        } else {
+            Some(transition::blend(&self.from, to, self.transition.easing.apply(progress)))
+        }
+    }
+}
+
+/// How much of the effect's brightness is kept, changing gradually
+#[derive(Clone, Copy)]
+struct Dimming {
+    from: f32,
+    to: f32,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Dimming {
+    fn level(&self) -> f32 {
+        let progress = if self.duration.is_zero() {
+            1.0
+        } else {
+            (self.started.elapsed().as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+        };
+
+        (self.to - self.from).mul_add(progress, self.from)
+    }
+
+    fn is_changing(&self) -> bool {
+        self.started.elapsed() < self.duration
+    }
+}
+
+impl Default for Dimming {
+    fn default() -> Self {
+        Self {
+            from: 1.0,
+            to: 1.0,
+            started: Instant::now(),
+            duration: Duration::ZERO,
+        }
+    }
+}
+
+/// What dims the output, each keeping its own level so they don't undo each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DimLayer {
+    Idle,
+    Session,
+    Power,
+    /// Turning the lights off by hand
+    Switch,
+}
+
+/// Dims whatever is shown without the effects knowing about it
+#[derive(Clone)]
+pub struct Dimmer {
+    layer: DimLayer,
+    shared: Weak<Mutex<Shared>>,
+    wake_tx: Sender<()>,
+}
+
+impl Dimmer {
+    /// Go from the current level to `level`, `1.0` showing the effect as is and `0.0` turning the lights off
+    ///
+    /// Returns `false` if the output is gone.
+    pub fn fade_to(&self, level: f32, duration: Duration) -> bool {
+        let Some(shared) = self.shared.upgrade() else {
+            return false;
+        };
+
+        let mut shared = shared.lock().unwrap();
+        let dimming = shared.dimming.entry(self.layer).or_default();
+        *dimming = Dimming {
+            from: dimming.level(),
+            to: level.clamp(0.0, 1.0),
+            started: Instant::now(),
+            duration,
+        };
+        let _ = self.wake_tx.try_send(());
+
+        true
+    }
+}
+
+/// A read only view of what the keyboard is showing
+#[derive(Clone)]
+pub struct Preview {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Preview {
+    pub fn frame(&self) -> [u8; 12] {
+        self.shared.lock().unwrap().preview()
+    }
+
+    /// The full resolution version of the frame, if there is one
+    pub fn canvas(&self) -> Option<Canvas> {
+        let shared = self.shared.lock().unwrap();
+
+        if shared.crossfade.is_some() || shared.composed || shared.effect != BaseEffects::Static {
+            None
+        } else {
+            shared.canvas.clone()
+        }
+    }
+}
+
+/// Sits between the effects and the keyboard, mirroring the driver's API
+///
+/// Effects write frames here, and a separate thread composes them into what ends up on the keyboard.
+/// This allows blending the output of two effects without them knowing about each other.
+pub struct Output {
+    shared: Arc<Mutex<Shared>>,
+    wake_tx: Sender<()>,
+    stop_signal: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Output {
+    pub fn new(keyboard: Keyboard, stop_signal: Arc<AtomicBool>) -> Self {
+        let shared = Arc::new(Mutex::new(Shared {
+            effect: BaseEffects::Static,
+            speed: 1,
+            brightness: 1,
+            frame: [0; 12],
+            canvas: None,
+            shown: [0; 12],
+            applied_at: Instant::now(),
+            crossfade: None,
+            overlays: BTreeMap::new(),
+            dimming: BTreeMap::new(),
+            composed: false,
+            stale: false,
+            exit: false,
+        }));
+
+        let (wake_tx, wake_rx) = crossbeam_channel::bounded::<()>(1);
+
+        let shared_c = shared.clone();
+        let handle = thread::spawn(move || compositor(keyboard, &shared_c, &wake_rx));
+
+        Self {
+            shared,
+            wake_tx,
+            stop_signal,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn preview(&self) -> Preview {
+        Preview { shared: self.shared.clone() }
+    }
+
+    pub fn dimmer(&self, layer: DimLayer) -> Dimmer {
+        Dimmer {
+            layer,
+            shared: Arc::downgrade(&self.shared),
+            wake_tx: self.wake_tx.clone(),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Shared)) {
+        f(&mut self.shared.lock().unwrap());
+        let _ = self.wake_tx.try_send(());
+    }
+
+    fn set_frame(&self, frame: [u8; 12]) {
+        self.set_frame_from(frame, None);
+    }
+
+    fn set_frame_from(&self, frame: [u8; 12], canvas: Option<&Canvas>) {
+        self.update(|shared| {
+            shared.frame = frame;
+
+            match (shared.canvas.as_mut(), canvas) {
+                (Some(stored), Some(canvas)) => stored.clone_from(canvas),
+                (_, canvas) => shared.canvas = canvas.cloned(),
+            }
+
+            if let Some(crossfade) = shared.crossfade.as_mut() {
+                crossfade.started.get_or_insert_with(Instant::now);
+            }
+        });
+    }
+
+    /// Blend from whatever is currently shown into the next frames that are rendered
+    pub fn begin_transition(&self, transition: Transition) {
+        self.update(|shared| {
+            shared.crossfade = if transition.is_instant() {
+                None
+            } else {
+                Some(Crossfade {
+                    from: shared.preview(),
+                    transition,
+                    started: None,
+                })
+            };
+        });
+    }
+
+    pub fn frame(&self) -> [u8; 12] {
+        self.shared.lock().unwrap().frame
+    }
+
+    /// Send the whole state to the keyboard again, even if nothing changed since the last write
+    pub fn resync(&self) {
+        self.update(|shared| shared.stale = true);
+    }
+
+    /// Draw something over every effect until it is replaced or removed
+    pub fn set_overlay(&self, kind: OverlayKind, overlay: Option<Box<dyn Overlay>>) {
+        let replaced = {
+            let mut shared = self.shared.lock().unwrap();
+            match overlay {
+                Some(overlay) => shared.overlays.insert(kind, overlay),
+                None => shared.overlays.remove(&kind),
+            }
+        };
+        let _ = self.wake_tx.try_send(());
+
+        // Stopping what it samples with may take a moment, which the compositor shouldn't have to wait on
+        drop(replaced);
+    }
+
+    pub fn set_effect(&mut self, effect: BaseEffects) -> Result<()> {
+        self.update(|shared| {
+            shared.effect = effect;
+
+            // There is no way of telling what a hardware animation looks like, so don't try blending into it
+            if matches!(effect, BaseEffects::Smooth | BaseEffects::LeftWave | BaseEffects::RightWave) {
+                shared.crossfade = None;
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn set_speed(&mut self, speed: u8) -> Result<()> {
+        if !SPEED_RANGE.contains(&speed) {
+            return Err(RangeError { kind: RangeErrorKind::Speed }.into());
+        }
+
+        self.update(|shared| shared.speed = speed);
+
+        Ok(())
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) -> Result<()> {
+        if !BRIGHTNESS_RANGE.contains(&brightness) {
+            return Err(RangeError { kind: RangeErrorKind::Brightness }.into());
+        }
+
+        self.update(|shared| shared.brightness = brightness);
+
+        Ok(())
+    }
+
+    pub fn set_zone_by_index(&mut self, zone_index: u8, new_values: [u8; 3]) -> Result<()> {
+        if !ZONE_RANGE.contains(&zone_index) {
+            return Err(RangeError { kind: RangeErrorKind::Zone }.into());
+        }
+
+        let mut frame = self.frame();
+        let start = usize::from(zone_index) * 3;
+        frame[start..start + 3].copy_from_slice(&new_values);
+        self.set_frame(frame);
+
+        Ok(())
+    }
+
+    pub fn set_colors_to(&mut self, new_values: &[u8; 12]) -> Result<()> {
+        self.set_frame(*new_values);
+
+        Ok(())
+    }
+
+    /// Show a canvas, filtered down to the four zones
+    pub fn set_canvas(&mut self, canvas: &Canvas) -> Result<()> {
+        self.set_frame_from(canvas.downsample(), Some(canvas));
+
+        Ok(())
+    }
+
+    pub fn solid_set_colors_to(&mut self, new_values: [u8; 3]) -> Result<()> {
+        let mut frame = [0; 12];
+        for zone in frame.chunks_exact_mut(3) {
+            zone.copy_from_slice(&new_values);
+        }
+        self.set_frame(frame);
+
+        Ok(())
+    }
+
+    pub fn transition_colors_to(&mut self, target_colors: &[u8; 12], steps: u8, delay_between_steps: u64) -> Result<()> {
+        if self.stop_signal.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let start = self.frame();
+
+        for step_num in 1..=steps {
+            if self.stop_signal.load(Ordering::SeqCst) {
+                break;
+            }
+
+            self.set_frame(transition::blend(&start, target_colors, f32::from(step_num) / f32::from(steps)));
+
+            // Every step used to be a write to the keyboard, which took about a millisecond on its own
+            thread::sleep(Duration::from_millis(delay_between_steps.max(1)));
+        }
+
+        self.set_colors_to(target_colors)
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        // Go straight to where any crossfade or dimming was headed, as there's no telling how long those take
+        self.update(|shared| {
+            shared.crossfade = None;
+            for dimming in shared.dimming.values_mut() {
+                *dimming = Dimming {
+                    from: dimming.to,
+                    duration: Duration::ZERO,
+                    ..*dimming
+                };
+            }
+            shared.exit = true;
+        });
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn compositor(mut keyboard: Keyboard, shared: &Mutex<Shared>, wake_rx: &Receiver<()>) {
+    let mut applied: Option<(BaseEffects, u8, u8, [u8; 12])> = None;
+    let mut last_write = Instant::now();
+    // Whether the last write failed, which is only reported once until one works again
+    let mut failing = false;
+
+    loop {
+        let (state, fading, composing, exit) = {
+            let mut guard = shared.lock().unwrap();
+            let shared = &mut *guard;
+
+            if std::mem::take(&mut shared.stale) {
+                applied = None;
+            }
+
+            let blended = shared.crossfade.and_then(|crossfade| crossfade.sample(&shared.frame));
+            if blended.is_none() {
+                shared.crossfade = None;
+            }
+
+            let mut frame = blended.unwrap_or(shared.frame);
+            // Firmware effects only take over once the crossfade is done
+            let mut effect = if blended.is_some() { BaseEffects::Static } else { shared.effect };
+
+            let level: f32 = shared.dimming.values().map(Dimming::level).product();
+            let dimmed = level < 1.0;
+
+            let mut drawn = false;
+            if !shared.overlays.is_empty() || dimmed {
+                // Overlays and dimming go over what the effect looks like, so a firmware animation has to be emulated meanwhile
+                let mut composed = if firmware::is_animated(effect) {
+                    firmware::render(effect, shared.speed, &frame, shared.applied_at.elapsed().as_secs_f32())
+                } else {
+                    frame
+                };
+
+                for overlay in shared.overlays.values_mut() {
+                    drawn |= overlay.draw(&mut composed);
+                }
+
+                if dimmed {
+                    composed = composed.map(|channel| (f32::from(channel) * level).round() as u8);
+                }
+
+                if drawn || dimmed {
+                    frame = composed;
+                    effect = BaseEffects::Static;
+                }
+            }
+
+            shared.shown = frame;
+            shared.composed = drawn || dimmed;
+            if applied.is_none_or(|(applied_effect, ..)| applied_effect != effect) {
+                shared.applied_at = Instant::now();
+            }
+
+            let fading = shared.crossfade.is_some_and(|crossfade| crossfade.started.is_some()) || shared.dimming.values().any(Dimming::is_changing);
+
+            ((effect, shared.speed, shared.brightness, frame), fading, !shared.overlays.is_empty() || dimmed, shared.exit)
+        };
+
+        if applied != Some(state) {
+            let (effect, speed, brightness, frame) = state;
+            match keyboard.set_state(effect, speed, brightness, frame) {
+                Ok(()) => {
+                    applied = Some(state);
+                    failing = false;
+                }
+                // Most likely the keyboard being busy for a moment, so keep going and try again
+                Err(err) => {
+                    if !failing {
+                        eprintln!("Could not write to the keyboard: {err:?}");
+                    }
+                    failing = true;
+                }
+            }
+            last_write = Instant::now();
+        }
+
+        if exit && !fading {
+            break;
+        }
+
+        let wait = if failing {
+            wake_rx.recv_timeout(RETRY_INTERVAL)
+        } else if fading {
+            wake_rx.recv_timeout(FRAME_INTERVAL)
+        } else if composing {
+            wake_rx.recv_timeout(OVERLAY_INTERVAL)
+        } else {
+            wake_rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+
+        if wait == Err(RecvTimeoutError::Disconnected) {
+            break;
+        }
+
+        let elapsed = last_write.elapsed();
+        if elapsed < FRAME_INTERVAL {
+            thread::sleep(FRAME_INTERVAL - elapsed);
+        }
+    }
+}
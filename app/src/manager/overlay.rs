@@ -0,0 +1,73 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam_channel::{RecvTimeoutError, Sender};
+
+/// Something the compositor draws over whatever the running effect shows
+pub trait Overlay: Send {
+    /// Draw over the frame, returning `false` if there is nothing to show right now
+    ///
+    /// This is called with the compositor's state locked, so anything that has to be read from the system should come from a [`Sampler`].
+    fn draw(&mut self, frame: &mut [u8; 12]) -> bool;
+}
+
+/// What an overlay is for, only one of each kind being shown at a time
+///
+/// Overlays are drawn in the order the kinds are declared in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OverlayKind {
+    Battery,
+    LockKeys,
+}
+
+/// Reads something on its own thread every now and then, keeping the last value around for overlays to draw from
+pub struct Sampler<T> {
+    value: Arc<Mutex<T>>,
+    /// Dropped to stop the thread
+    stop_tx: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Clone + Send + 'static> Sampler<T> {
+    /// Start reading right away, `initial` standing in until the first read is done
+    pub fn spawn(interval: Duration, initial: T, mut read: impl FnMut(&T) -> T + Send + 'static) -> Self {
+        let value = Arc::new(Mutex::new(initial));
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(0);
+
+        let value_c = value.clone();
+        let handle = thread::spawn(move || loop {
+            let last = value_c.lock().unwrap().clone();
+            // Not read with the value locked, as that's what drawing waits on
+            let new = read(&last);
+            *value_c.lock().unwrap() = new;
+
+            if stop_rx.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                break;
+            }
+        });
+
+        Self {
+            value,
+            stop_tx: Some(stop_tx),
+            handle: Some(handle),
+        }
+    }
+
+    /// The last value that was read
+    pub fn get(&self) -> T {
+        self.value.lock().unwrap().clone()
+    }
+}
+
+impl<T> Drop for Sampler<T> {
+    fn drop(&mut self) {
+        self.stop_tx = None;
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
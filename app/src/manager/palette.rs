@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+
+use super::effects::color;
+
+/// A color placed somewhere along a gradient, `0.0` being its start and `1.0` its end
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ColorStop {
+    pub position: f32,
+    pub rgb: [u8; 3],
+}
+
+/// An arbitrary amount of color stops making up a gradient
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Palette {
+    pub stops: Vec<ColorStop>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        // What SmoothWave used to always show
+        Self::from_colors(&[[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 0, 255]])
+    }
+}
+
+impl Palette {
+    pub const MAX_STOPS: usize = 16;
+
+    /// Green through yellow to red, for anything going from fine to alarming
+    pub fn green_to_red() -> Self {
+        Self {
+            stops: vec![
+                ColorStop { position: 0.0, rgb: [0, 255, 0] },
+                ColorStop { position: 0.5, rgb: [255, 255, 0] },
+                ColorStop { position: 1.0, rgb: [255, 0, 0] },
+            ],
+        }
+    }
+
+    /// Spread the colors evenly, each one sitting where the center of a zone would be if there was one per color
+    pub fn from_colors(colors: &[[u8; 3]]) -> Self {
+        let len = colors.len() as f32;
+
+        Self {
+            stops: colors
+                .iter()
+                .enumerate()
+                .map(|(i, rgb)| ColorStop {
+                    position: (i as f32 + 0.5) / len,
+                    rgb: *rgb,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn from_zones(frame: &[u8; 12]) -> Self {
+        let colors: Vec<[u8; 3]> = frame.chunks_exact(3).map(|rgb| rgb.try_into().unwrap()).collect();
+        Self::from_colors(&colors)
+    }
+
+    /// The same palette with its stops clamped to the gradient and ordered by position, which sampling relies on
+    pub fn sorted(&self) -> Self {
+        let mut stops: Vec<ColorStop> = self
+            .stops
+            .iter()
+            .take(Self::MAX_STOPS)
+            .map(|stop| ColorStop {
+                position: stop.position.clamp(0.0, 1.0),
+                rgb: stop.rgb,
+            })
+            .collect();
+
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        Self { stops }
+    }
+
+    /// The colors of the stops, from the start of the gradient to its end
+    pub fn colors(&self) -> Vec<[f32; 3]> {
+        self.stops.iter().map(|stop| stop.rgb.map(f32::from)).collect()
+    }
+
+    /// The same palette with the hue of every stop shifted by `degrees`
+    pub fn rotate_hue(&self, degrees: f32) -> Self {
+        Self {
+            stops: self
+                .stops
+                .iter()
+                .map(|stop| ColorStop {
+                    position: stop.position,
+                    rgb: color::rotate_hue(stop.rgb, degrees),
+                })
+                .collect(),
+        }
+    }
+
+    /// The same palette running from its end to its start
+    pub fn reversed(&self) -> Self {
+        Self {
+            stops: self
+                .stops
+                .iter()
+                .rev()
+                .map(|stop| ColorStop {
+                    position: 1.0 - stop.position,
+                    rgb: stop.rgb,
+                })
+                .collect(),
+        }
+    }
+
+    /// The color at `position`, the first and last stops extending to the ends of the gradient
+    ///
+    /// Expects the palette to be [`sorted`](Self::sorted).
+    pub fn sample(&self, position: f32) -> [f32; 3] {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return [0.0; 3];
+        };
+
+        if position <= first.position {
+            return first.rgb.map(f32::from);
+        }
+
+        if position >= last.position {
+            return last.rgb.map(f32::from);
+        }
+
+        self.between(position)
+    }
+
+    /// The color at `position`, treating the gradient as a loop where the last stop blends back into the first one
+    ///
+    /// Expects the palette to be [`sorted`](Self::sorted).
+    pub fn sample_wrapped(&self, position: f32) -> [f32; 3] {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return [0.0; 3];
+        };
+
+        let position = position.rem_euclid(1.0);
+
+        if position < first.position || position >= last.position {
+            let gap = first.position + 1.0 - last.position;
+            let distance = (position - last.position).rem_euclid(1.0);
+            let t = if gap > 0.0 { distance / gap } else { 0.0 };
+
+            return color::lerp(last.rgb.map(f32::from), first.rgb.map(f32::from), t);
+        }
+
+        self.between(position)
+    }
+
+    fn between(&self, position: f32) -> [f32; 3] {
+        let next = self.stops.partition_point(|stop| stop.position <= position).min(self.stops.len() - 1);
+        let (from, to) = (self.stops[next.saturating_sub(1)], self.stops[next]);
+
+        let gap = to.position - from.position;
+        let t = if gap > 0.0 { (position - from.position) / gap } else { 1.0 };
+
+        color::lerp(from.rgb.map(f32::from), to.rgb.map(f32::from), t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorStop, Palette};
+
+    fn stop(position: f32, rgb: [u8; 3]) -> ColorStop {
+        ColorStop { position, rgb }
+    }
+
+    #[test]
+    fn colors_are_spread_over_the_zone_centers() {
+        let palette = Palette::from_zones(&[1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4]);
+
+        assert_eq!(palette.stops, vec![stop(0.125, [1; 3]), stop(0.375, [2; 3]), stop(0.625, [3; 3]), stop(0.875, [4; 3])]);
+    }
+
+    #[test]
+    fn sorting_clamps_orders_and_limits_the_stops() {
+        let palette = Palette {
+            stops: vec![stop(1.5, [3; 3]), stop(-1.0, [1; 3]), stop(0.5, [2; 3])],
+        };
+        assert_eq!(palette.sorted().stops, vec![stop(0.0, [1; 3]), stop(0.5, [2; 3]), stop(1.0, [3; 3])]);
+
+        let many = Palette::from_colors(&[[0; 3]; Palette::MAX_STOPS + 4]);
+        assert_eq!(many.sorted().stops.len(), Palette::MAX_STOPS);
+    }
+
+    #[test]
+    fn samples_between_stops_are_interpolated() {
+        let palette = Palette::green_to_red();
+
+        assert_eq!(palette.sample(0.0), [0.0, 255.0, 0.0]);
+        assert_eq!(palette.sample(0.25), [127.5, 255.0, 0.0]);
+        assert_eq!(palette.sample(0.5), [255.0, 255.0, 0.0]);
+        assert_eq!(palette.sample(0.75), [255.0, 127.5, 0.0]);
+        assert_eq!(palette.sample(1.0), [255.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn the_outer_stops_extend_to_the_ends() {
+        let palette = Palette {
+            stops: vec![stop(0.25, [255, 0, 0]), stop(0.75, [0, 0, 255])],
+        };
+
+        assert_eq!(palette.sample(0.0), [255.0, 0.0, 0.0]);
+        assert_eq!(palette.sample(0.5), [127.5, 0.0, 127.5]);
+        assert_eq!(palette.sample(1.0), [0.0, 0.0, 255.0]);
+        assert_eq!(Palette { stops: Vec::new() }.sample(0.5), [0.0; 3]);
+    }
+
+    #[test]
+    fn wrapped_samples_blend_the_last_stop_back_into_the_first() {
+        let palette = Palette {
+            stops: vec![stop(0.25, [255, 0, 0]), stop(0.75, [0, 0, 255])],
+        };
+
+        assert_eq!(palette.sample_wrapped(0.0), [127.5, 0.0, 127.5]);
+        assert_eq!(palette.sample_wrapped(1.0), [127.5, 0.0, 127.5]);
+        assert_eq!(palette.sample_wrapped(-0.5), palette.sample_wrapped(0.5));
+        assert_eq!(palette.sample_wrapped(0.875), [63.75, 0.0, 191.25]);
+
+        // A single stop is all there is to show
+        let single = Palette { stops: vec![stop(0.5, [9; 3])] };
+        assert_eq!(single.sample_wrapped(0.1), [9.0; 3]);
+    }
+
+    #[test]
+    fn reversing_mirrors_the_gradient() {
+        let palette = Palette::green_to_red();
+        let reversed = palette.reversed();
+
+        for position in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert_eq!(reversed.sample(position), palette.sample(1.0 - position));
+        }
+    }
+}
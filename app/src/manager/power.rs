@@ -0,0 +1,194 @@
+use std::{
+    sync::{atomic::Ordering, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
+
+use crate::enums::Message;
+
+use super::{
+    output::Dimmer,
+    power_supply::{ChargeStatus, PowerSupply},
+    profile::Profile,
+    StopSignals,
+};
+
+/// How often the power source is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How long the lights take to reach a new brightness cap
+const LIMIT_DURATION: Duration = Duration::from_secs(1);
+
+/// What to do while running on battery, and once it runs low
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct PowerSettings {
+    pub enabled: bool,
+    pub battery: PowerRule,
+    pub saver: PowerRule,
+    /// Battery percentage at or under which the saver rule applies instead
+    pub saver_threshold: u8,
+    /// Shown once a charger is plugged in, instead of going back to what was shown before
+    pub ac_profile: Option<Profile>,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            battery: PowerRule::default(),
+            saver: PowerRule {
+                brightness: 50,
+                pause_ambient: true,
+                ..PowerRule::default()
+            },
+            saver_threshold: 20,
+            ac_profile: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct PowerRule {
+    /// Switched to when the rule starts applying, keeping the current profile if there is none
+    pub profile: Option<Profile>,
+    /// The most of the effect's brightness that is kept, in percent
+    pub brightness: u8,
+    /// Show the profile's colors instead of capturing the screen for the ambient light effect
+    pub pause_ambient: bool,
+}
+
+impl Default for PowerRule {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            brightness: 100,
+            pause_ambient: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PowerState {
+    #[default]
+    Ac,
+    Battery,
+    /// On battery, which is at or under the saver threshold
+    Saver,
+}
+
+impl PowerState {
+    fn read(supply: &PowerSupply, saver_threshold: u8) -> Self {
+        let battery = supply.battery();
+        // Without a charger to ask, a discharging battery is the next best hint
+        let on_ac = supply.on_ac().unwrap_or_else(|| battery.is_none_or(|battery| battery.status != ChargeStatus::Discharging));
+
+        if on_ac {
+            Self::Ac
+        } else if battery.is_some_and(|battery| battery.capacity <= saver_threshold) {
+            Self::Saver
+        } else {
+            Self::Battery
+        }
+    }
+}
+
+impl PowerSettings {
+    pub const MIN_BRIGHTNESS: u8 = 10;
+
+    /// The rule that applies in a given state, if any
+    pub fn rule(&self, state: PowerState) -> Option<&PowerRule> {
+        match state {
+            PowerState::Ac => None,
+            PowerState::Battery => Some(&self.battery),
+            PowerState::Saver => Some(&self.saver),
+        }
+    }
+
+    /// The brightness cap in a given state, in percent
+    fn limit(&self, state: PowerState) -> u8 {
+        self.rule(state).map_or(100, |rule| rule.brightness.clamp(Self::MIN_BRIGHTNESS, 100))
+    }
+}
+
+/// Follow the power source, capping the brightness and telling the effect thread whenever it changes, until the manager or the output is gone
+pub fn watch(dimmer: Dimmer, settings: Arc<Mutex<PowerSettings>>, state: Arc<Mutex<PowerState>>, tx: Sender<Message>, stop_signals: StopSignals) {
+    thread::spawn(move || {
+        let supply = PowerSupply::default();
+        let mut limit = 100;
+
+        while !stop_signals.exit_signal.load(Ordering::SeqCst) {
+            let settings = settings.lock().unwrap().clone();
+
+            let current = if settings.enabled { PowerState::read(&supply, settings.saver_threshold) } else { PowerState::Ac };
+
+            let new_limit = settings.limit(current);
+            if new_limit != limit {
+                if !dimmer.fade_to(f32::from(new_limit) / 100.0, LIMIT_DURATION) {
+                    break;
+                }
+                limit = new_limit;
+            }
+
+            let previous = std::mem::replace(&mut *state.lock().unwrap(), current);
+            if previous != current {
+                stop_signals.store_true();
+                if tx.send(Message::Power).is_err() {
+                    break;
+                }
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PowerRule, PowerSettings, PowerState};
+    use crate::manager::power_supply::fake::FakeSupplies;
+
+    #[test]
+    fn reads_the_power_source() {
+        let supplies = FakeSupplies::new("state");
+        // A desktop, or nothing to read
+        assert_eq!(PowerState::read(&supplies.supply(), 20), PowerState::Ac);
+
+        supplies.set("BAT0", &[("type", "Battery"), ("capacity", "21"), ("status", "Discharging")]);
+        assert_eq!(PowerState::read(&supplies.supply(), 20), PowerState::Battery);
+
+        supplies.set("BAT0", &[("capacity", "20")]);
+        assert_eq!(PowerState::read(&supplies.supply(), 20), PowerState::Saver);
+
+        // The charger is believed over the battery, which may not have caught up yet
+        supplies.set("ADP0", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(PowerState::read(&supplies.supply(), 20), PowerState::Ac);
+
+        supplies.set("ADP0", &[("online", "0")]);
+        supplies.set("BAT0", &[("status", "Not charging")]);
+        assert_eq!(PowerState::read(&supplies.supply(), 20), PowerState::Saver);
+    }
+
+    #[test]
+    fn limits_stay_in_range() {
+        let settings = PowerSettings {
+            battery: PowerRule {
+                brightness: 0,
+                ..PowerRule::default()
+            },
+            saver: PowerRule {
+                brightness: 150,
+                ..PowerRule::default()
+            },
+            ..PowerSettings::default()
+        };
+
+        assert_eq!(settings.limit(PowerState::Ac), 100);
+        assert_eq!(settings.limit(PowerState::Battery), PowerSettings::MIN_BRIGHTNESS);
+        assert_eq!(settings.limit(PowerState::Saver), 100);
+        assert_eq!(PowerSettings::default().limit(PowerState::Saver), 50);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Where the kernel exposes batteries and chargers
+pub const SYSFS_PATH: &str = "/sys/class/power_supply";
+/// Points every reader at a fake tree instead, laid out like the one in sysfs
+const ROOT_VAR: &str = "LEGION_KEYBOARD_POWER_SUPPLY";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChargeStatus {
+    Charging,
+    Discharging,
+    Full,
+    NotCharging,
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatteryState {
+    /// Percentage of charge left
+    pub capacity: u8,
+    pub status: ChargeStatus,
+}
+
+/// Reads a `power_supply` class directory, which can be pointed somewhere other than sysfs to fake one
+#[derive(Clone, Debug)]
+pub struct PowerSupply {
+    root: PathBuf,
+}
+
+impl Default for PowerSupply {
+    fn default() -> Self {
+        Self::new(env::var_os(ROOT_VAR).map_or_else(|| SYSFS_PATH.into(), PathBuf::from))
+    }
+}
+
+impl PowerSupply {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The supplies of a given type, such as `Battery` or `Mains`
+    fn supplies(&self, kind: &str) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+
+        let mut supplies: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| read(path, "type").is_some_and(|value| value == kind))
+            .collect();
+        supplies.sort();
+        supplies
+    }
+
+    /// The state of the first battery found
+    pub fn battery(&self) -> Option<BatteryState> {
+        let path = self.supplies("Battery").into_iter().next()?;
+
+        let capacity = read(&path, "capacity")?.parse::<u8>().ok()?.min(100);
+        let status = match read(&path, "status").as_deref() {
+            Some("Charging") => ChargeStatus::Charging,
+            Some("Discharging") => ChargeStatus::Discharging,
+            Some("Full") => ChargeStatus::Full,
+            Some("Not charging") => ChargeStatus::NotCharging,
+            _ => ChargeStatus::Unknown,
+        };
+
+        Some(BatteryState { capacity, status })
+    }
+
+    /// Whether a charger is plugged in, or `None` if there is no charger to ask
+    pub fn on_ac(&self) -> Option<bool> {
+        let mains = self.supplies("Mains");
+
+        if mains.is_empty() {
+            None
+        } else {
+            Some(mains.iter().any(|path| read(path, "online").is_some_and(|value| value == "1")))
+        }
+    }
+}
+
+fn read(supply: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(supply.join(attribute)).ok().map(|value| value.trim().to_string())
+}
+
+/// A `power_supply` tree in a temporary directory, removed once dropped
+#[cfg(test)]
+pub mod fake {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::PowerSupply;
+
+    pub struct FakeSupplies {
+        root: PathBuf,
+    }
+
+    impl FakeSupplies {
+        pub fn new(name: &str) -> Self {
+            let root = env::temp_dir().join(format!("legion-kb-rgb-power-{name}-{}", process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            Self { root }
+        }
+
+        pub fn supply(&self) -> PowerSupply {
+            PowerSupply::new(&self.root)
+        }
+
+        /// Add or change a supply, with its attributes as they'd read in sysfs
+        pub fn set(&self, name: &str, attributes: &[(&str, &str)]) {
+            let path = self.root.join(name);
+            fs::create_dir_all(&path).unwrap();
+            for (attribute, value) in attributes {
+                fs::write(path.join(attribute), format!("{value}\n")).unwrap();
+            }
+        }
+    }
+
+    impl Drop for FakeSupplies {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fake::FakeSupplies, BatteryState, ChargeStatus};
+
+    #[test]
+    fn reads_the_first_battery() {
+        let supplies = FakeSupplies::new("battery");
+        assert_eq!(supplies.supply().battery(), None);
+
+        supplies.set("ADP0", &[("type", "Mains"), ("online", "0")]);
+        supplies.set("BAT1", &[("type", "Battery"), ("capacity", "90"), ("status", "Full")]);
+        supplies.set("BAT0", &[("type", "Battery"), ("capacity", "42"), ("status", "Not charging")]);
+
+        assert_eq!(
+            supplies.supply().battery(),
+            Some(BatteryState {
+                capacity: 42,
+                status: ChargeStatus::NotCharging
+            })
+        );
+
+        // Out of range and unknown values are made the most of
+        supplies.set("BAT0", &[("capacity", "120"), ("status", "Something else")]);
+        assert_eq!(
+            supplies.supply().battery(),
+            Some(BatteryState {
+                capacity: 100,
+                status: ChargeStatus::Unknown
+            })
+        );
+    }
+
+    #[test]
+    fn follows_the_chargers() {
+        let supplies = FakeSupplies::new("mains");
+        assert_eq!(supplies.supply().on_ac(), None);
+
+        supplies.set("ADP0", &[("type", "Mains"), ("online", "0")]);
+        supplies.set("USB0", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(supplies.supply().on_ac(), Some(false));
+
+        supplies.set("USB0", &[("online", "1")]);
+        assert_eq!(supplies.supply().on_ac(), Some(true));
+    }
+}
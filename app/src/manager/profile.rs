@@ -1,10 +1,12 @@
 use std::{convert::TryInto, path::Path};
 
 use crate::{
-    enums::{Brightness, Direction, Effects},
+    enums::{AudioSource, Brightness, DecayCurve, Direction, Effects, LockKey, RenderMode},
     util::StorageTrait,
 };
 
+use super::palette::{ColorStop, Palette};
+
 use error_stack::{Result, ResultExt};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -26,6 +28,254 @@ impl Default for KeyboardZone {
 
 type Zones = [KeyboardZone; 4];
 
+/// How each of the effects built into the keyboard should be rendered
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+pub struct RenderModes {
+    pub breath: RenderMode,
+    pub smooth: RenderMode,
+    pub wave: RenderMode,
+}
+
+/// Settings for the procedurally generated effects
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProceduralSettings {
+    /// The same seed always plays out the same way
+    pub seed: u64,
+    /// How saturated the colors of the rainbow are, from `0.0` to `1.0`
+    pub saturation: f32,
+}
+
+impl Default for ProceduralSettings {
+    fn default() -> Self {
+        Self { seed: 0, saturation: 1.0 }
+    }
+}
+
+/// Settings for the audio visualizer
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub source: AudioSource,
+    /// The file or pipe to read from, when using either as the source
+    pub path: String,
+    /// How much the sound gets amplified before being shown
+    pub sensitivity: f32,
+    /// How slowly the lights follow the sound, from `0.0` (not at all) to just under `1.0`
+    pub smoothing: f32,
+    /// Sample rate of the raw PCM read from a pipe
+    pub pipe_sample_rate: u32,
+    /// Channels in the raw PCM read from a pipe
+    pub pipe_channels: u16,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            source: AudioSource::default(),
+            path: String::new(),
+            sensitivity: 1.0,
+            smoothing: 0.6,
+            pipe_sample_rate: 44100,
+            pipe_channels: 2,
+        }
+    }
+}
+
+/// Settings for the temperature effect
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct TemperatureSettings {
+    /// A sensor label or hwmon path for each zone, empty to pick the CPU automatically
+    ///
+    /// Only the first one is used unless `per_zone` is set, missing ones being picked automatically.
+    pub sensors: Vec<String>,
+    pub per_zone: bool,
+    /// Temperature shown with the start of the gradient, in degrees Celsius
+    pub min: f32,
+    /// Temperature shown with the end of the gradient, in degrees Celsius
+    pub max: f32,
+    pub gradient: Palette,
+    /// How much of a change in temperature is still left to show after a second, from `0.0` to just under `1.0`
+    pub smoothing: f32,
+}
+
+impl Default for TemperatureSettings {
+    fn default() -> Self {
+        Self {
+            sensors: vec![String::new()],
+            per_zone: false,
+            min: 40.0,
+            max: 95.0,
+            gradient: Palette::green_to_red(),
+            smoothing: 0.5,
+        }
+    }
+}
+
+/// Settings for the load meter
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct LoadSettings {
+    /// Throughput shown as a full meter for the disk and network metrics, in megabytes per second
+    pub full_scale: f32,
+    pub gradient: Palette,
+}
+
+impl Default for LoadSettings {
+    fn default() -> Self {
+        Self {
+            full_scale: 100.0,
+            gradient: Palette::green_to_red(),
+        }
+    }
+}
+
+/// Settings for the reactive effect, which fades from the press color back to the zone colors
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct ReactiveSettings {
+    pub press_color: [u8; 3],
+    /// How long a zone takes to fade back after a full intensity press, in milliseconds
+    pub decay_ms: u32,
+    pub curve: DecayCurve,
+    /// How much a single press lights up its zone, from `0.0` to `1.0`, quick presses adding up to full intensity
+    pub press_intensity: f32,
+}
+
+impl Default for ReactiveSettings {
+    fn default() -> Self {
+        Self {
+            press_color: [255, 255, 255],
+            decay_ms: 600,
+            curve: DecayCurve::default(),
+            press_intensity: 0.6,
+        }
+    }
+}
+
+/// Settings for the typing speed and heatmap effects
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct TypingSettings {
+    /// Words per minute shown as a full gauge
+    pub max_wpm: f32,
+    /// How far back the typing speed is measured, in seconds
+    pub wpm_window: f32,
+    /// How long the heatmap takes to cool down to about a third, in seconds
+    pub cool_down: f32,
+    pub gradient: Palette,
+    /// Keep a count of how many times each key gets pressed on disk, without anything about what was typed
+    pub record_histogram: bool,
+}
+
+impl Default for TypingSettings {
+    fn default() -> Self {
+        Self {
+            max_wpm: 100.0,
+            wpm_window: 10.0,
+            cool_down: 30.0,
+            gradient: Palette {
+                stops: vec![
+                    ColorStop { position: 0.0, rgb: [0, 0, 255] },
+                    ColorStop { position: 0.33, rgb: [0, 255, 0] },
+                    ColorStop { position: 0.66, rgb: [255, 255, 0] },
+                    ColorStop { position: 1.0, rgb: [255, 0, 0] },
+                ],
+            },
+            record_histogram: false,
+        }
+    }
+}
+
+/// Settings for the battery effect and the low battery warning
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct BatterySettings {
+    /// Charge at or below which the warning is shown while discharging, in percent
+    pub warning_level: u8,
+    pub warning_color: [u8; 3],
+    pub charging_color: [u8; 3],
+    /// Colors the bar from an empty battery to a full one
+    pub gradient: Palette,
+    /// Pulse the warning over any other effect when the battery runs low
+    pub overlay: bool,
+}
+
+impl Default for BatterySettings {
+    fn default() -> Self {
+        Self {
+            warning_level: 20,
+            warning_color: [255, 0, 0],
+            charging_color: [0, 255, 0],
+            gradient: Palette::green_to_red().reversed(),
+            overlay: false,
+        }
+    }
+}
+
+/// A zone lighting up in some color while a lock key is on
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LockIndicator {
+    pub enabled: bool,
+    /// From `0` to `3`
+    pub zone: u8,
+    pub rgb: [u8; 3],
+}
+
+/// Settings for the overlay standing in for the lock key LEDs some models lack
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct LockKeySettings {
+    pub caps_lock: LockIndicator,
+    pub num_lock: LockIndicator,
+    pub scroll_lock: LockIndicator,
+}
+
+impl Default for LockKeySettings {
+    fn default() -> Self {
+        Self {
+            caps_lock: LockIndicator {
+                enabled: false,
+                zone: 0,
+                rgb: [255, 255, 255],
+            },
+            num_lock: LockIndicator {
+                enabled: false,
+                zone: 3,
+                rgb: [0, 128, 255],
+            },
+            scroll_lock: LockIndicator {
+                enabled: false,
+                zone: 2,
+                rgb: [255, 128, 0],
+            },
+        }
+    }
+}
+
+impl LockKeySettings {
+    pub fn indicator(&self, key: LockKey) -> &LockIndicator {
+        match key {
+            LockKey::Caps => &self.caps_lock,
+            LockKey::Num => &self.num_lock,
+            LockKey::Scroll => &self.scroll_lock,
+        }
+    }
+
+    pub fn indicator_mut(&mut self, key: LockKey) -> &mut LockIndicator {
+        match key {
+            LockKey::Caps => &mut self.caps_lock,
+            LockKey::Num => &mut self.num_lock,
+            LockKey::Scroll => &mut self.scroll_lock,
+        }
+    }
+
+    pub fn any_enabled(&self) -> bool {
+        self.caps_lock.enabled || self.num_lock.enabled || self.scroll_lock.enabled
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Profile {
     pub name: Option<String>,
@@ -34,6 +284,27 @@ pub struct Profile {
     pub direction: Direction,
     pub speed: u8,
     pub brightness: Brightness,
+    #[serde(default)]
+    pub render_modes: RenderModes,
+    /// The color stops used by the gradient effects
+    #[serde(default)]
+    pub palette: Palette,
+    #[serde(default)]
+    pub procedural: ProceduralSettings,
+    #[serde(default)]
+    pub audio: AudioSettings,
+    #[serde(default)]
+    pub temperature: TemperatureSettings,
+    #[serde(default)]
+    pub load: LoadSettings,
+    #[serde(default)]
+    pub reactive: ReactiveSettings,
+    #[serde(default)]
+    pub typing: TypingSettings,
+    #[serde(default)]
+    pub battery: BatterySettings,
+    #[serde(default)]
+    pub lock_keys: LockKeySettings,
 }
 
 impl Default for Profile {
@@ -45,6 +316,16 @@ impl Default for Profile {
             direction: Direction::default(),
             speed: 1,
             brightness: Brightness::default(),
+            render_modes: RenderModes::default(),
+            palette: Palette::default(),
+            procedural: ProceduralSettings::default(),
+            audio: AudioSettings::default(),
+            temperature: TemperatureSettings::default(),
+            load: LoadSettings::default(),
+            reactive: ReactiveSettings::default(),
+            typing: TypingSettings::default(),
+            battery: BatterySettings::default(),
+            lock_keys: LockKeySettings::default(),
         }
     }
 }
@@ -69,6 +350,28 @@ impl Profile {
         self.save(path).change_context(SaveProfileError)
     }
 
+    /// The render mode of the current effect, if it can be picked
+    pub fn render_mode_mut(&mut self) -> Option<&mut RenderMode> {
+        match self.effect {
+            Effects::Breath => Some(&mut self.render_modes.breath),
+            Effects::Smooth => Some(&mut self.render_modes.smooth),
+            Effects::Wave => Some(&mut self.render_modes.wave),
+            _ => None,
+        }
+    }
+
+    /// Whether the current effect is left for the keyboard to run
+    pub fn is_hardware_effect(&self) -> bool {
+        let render_mode = match self.effect {
+            Effects::Breath => self.render_modes.breath,
+            Effects::Smooth => self.render_modes.smooth,
+            Effects::Wave => self.render_modes.wave,
+            _ => RenderMode::Hardware,
+        };
+
+        self.effect.is_built_in() && render_mode == RenderMode::Hardware
+    }
+
     pub fn rgb_array(&self) -> [u8; 12] {
         self.rgb_zones.map(|zone| if zone.enabled { zone.rgb } else { [0; 3] }).concat().try_into().unwrap()
     }
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use super::profile::Profile;
+
+/// What to do when the laptop suspends, the session is locked or the lid is closed
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct SessionSettings {
+    pub off_on_sleep: bool,
+    pub off_on_lid_closed: bool,
+    /// Shown while the session is locked
+    pub lock_profile: Option<Profile>,
+    /// Shown once the session is unlocked, instead of going back to what was shown before locking
+    pub unlock_profile: Option<Profile>,
+}
+
+/// Something that happened to the session which the effect thread has to act on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionEvent {
+    Resume,
+    Lock,
+    Unlock,
+}
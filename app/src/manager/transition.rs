@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumIter, IntoStaticStr};
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, EnumIter, IntoStaticStr, Default)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map a linear progress value in the `0.0..=1.0` range onto the easing curve
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2.0 - t),
+            Self::EaseInOut => t * t * 2.0f32.mul_add(-t, 3.0),
+        }
+    }
+}
+
+/// How the lights blend from the last shown frame into a newly selected profile or effect
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub duration_ms: u64,
+    pub easing: Easing,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            duration_ms: 400,
+            easing: Easing::default(),
+        }
+    }
+}
+
+impl Transition {
+    pub const MAX_DURATION_MS: u64 = 3000;
+
+    pub fn duration(self) -> Duration {
+        Duration::from_millis(self.duration_ms.min(Self::MAX_DURATION_MS))
+    }
+
+    pub fn is_instant(self) -> bool {
+        self.duration().is_zero()
+    }
+}
+
+/// Linearly interpolate between two frames
+pub fn blend(from: &[u8; 12], to: &[u8; 12], t: f32) -> [u8; 12] {
+    let mut out = [0; 12];
+
+    for (i, val) in out.iter_mut().enumerate() {
+        *val = (f32::from(to[i]) - f32::from(from[i])).mul_add(t, f32::from(from[i])).round() as u8;
+    }
+
+    out
+}
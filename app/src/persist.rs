@@ -5,7 +5,13 @@ use std::{
     path::PathBuf,
 };
 
-use crate::manager::{custom_effect::CustomEffect, profile::Profile};
+use crate::{
+    focus::FocusSettings,
+    hotkeys::HotkeySettings,
+    ipc::{ApiSettings, MqttSettings},
+    manager::{custom_effect::CustomEffect, idle::IdleSettings, key_zones::KeyZoneSettings, power::PowerSettings, profile::Profile, session::SessionSettings, transition::Transition},
+    schedule::ScheduleSettings,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Default)]
@@ -15,13 +21,29 @@ pub struct Settings {
     // Up to 0.19.5
     #[serde(alias = "ui_state")]
     pub current_profile: Profile,
+    #[serde(default)]
+    pub transition: Transition,
+    #[serde(default)]
+    pub key_zones: KeyZoneSettings,
+    #[serde(default)]
+    pub idle: IdleSettings,
+    #[serde(default)]
+    pub session: SessionSettings,
+    #[serde(default)]
+    pub power: PowerSettings,
+    #[serde(default)]
+    pub hotkeys: HotkeySettings,
+    #[serde(default)]
+    pub focus: FocusSettings,
+    #[serde(default)]
+    pub schedule: ScheduleSettings,
+    #[serde(default)]
+    pub api: ApiSettings,
+    #[serde(default)]
+    pub mqtt: MqttSettings,
 }
 
 impl Settings {
-    pub fn new(profiles: Vec<Profile>, effects: Vec<CustomEffect>, current_profile: Profile) -> Self {
-        Self { profiles, effects, current_profile }
-    }
-
     /// Load the settings from the configured path or generate default ones if an error occurs
     pub fn load() -> Self {
         let mut persist: Self = Self::default();
@@ -42,6 +64,11 @@ impl Settings {
         file.write_all(stringified_json.as_bytes()).unwrap();
     }
 
+    /// A file kept in the same directory as the settings
+    pub fn sibling(file_name: &str) -> PathBuf {
+        Self::get_location().with_file_name(file_name)
+    }
+
     fn get_location() -> PathBuf {
         let default = PathBuf::from("./settings.json");
 
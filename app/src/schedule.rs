@@ -0,0 +1,280 @@
+//! Switching saved profiles at set times of the day, or around sunrise and sunset
+//!
+//! Only the moments an entry starts matter, so a profile picked by hand stays until the next one comes around.
+
+use std::{f64::consts::PI, time::Duration};
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone};
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumIter, IntoStaticStr};
+
+use crate::manager::profile::Profile;
+
+/// How often the schedule should be checked for entries that started
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(20);
+/// How far back a missed entry is still applied, such as when the program starts
+const LOOKBACK_DAYS: i64 = 7;
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    pub enabled: bool,
+    pub entries: Vec<ScheduleEntry>,
+    /// Where sunrise and sunset are worked out for, in degrees
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct ScheduleEntry {
+    /// The days the entry applies on, starting on Monday
+    pub days: [bool; 7],
+    pub trigger: Trigger,
+    /// The name of the saved profile to switch to, turning the lights off if there is none
+    pub profile: Option<String>,
+}
+
+impl Default for ScheduleEntry {
+    fn default() -> Self {
+        Self {
+            days: [true; 7],
+            trigger: Trigger::default(),
+            profile: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, EnumIter, IntoStaticStr)]
+pub enum Trigger {
+    Time {
+        hour: u8,
+        minute: u8,
+    },
+    /// Minutes before (when negative) or after sunrise
+    Sunrise {
+        offset_minutes: i16,
+    },
+    Sunset {
+        offset_minutes: i16,
+    },
+}
+
+/// What an entry does when it starts
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Profile(Box<Profile>),
+    LightsOff,
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self::Time { hour: 9, minute: 0 }
+    }
+}
+
+impl ScheduleEntry {
+    /// When the entry starts on a given day, in local time
+    fn start_on(&self, date: NaiveDate, latitude: f64, longitude: f64) -> Option<NaiveDateTime> {
+        if !self.days[date.weekday().num_days_from_monday() as usize] {
+            return None;
+        }
+
+        match self.trigger {
+            Trigger::Time { hour, minute } => date.and_hms_opt(u32::from(hour), u32::from(minute), 0),
+            Trigger::Sunrise { offset_minutes } => sun_event(date, latitude, longitude, true).map(|time| time + TimeDelta::minutes(i64::from(offset_minutes))),
+            Trigger::Sunset { offset_minutes } => sun_event(date, latitude, longitude, false).map(|time| time + TimeDelta::minutes(i64::from(offset_minutes))),
+        }
+    }
+
+    /// What the entry does, or `None` if the profile it switches to is no longer saved
+    pub fn target(&self, profiles: &[Profile]) -> Option<Target> {
+        match &self.profile {
+            Some(name) => profiles
+                .iter()
+                .find(|profile| profile.name.as_ref() == Some(name))
+                .map(|profile| Target::Profile(Box::new(profile.clone()))),
+            None => Some(Target::LightsOff),
+        }
+    }
+}
+
+impl ScheduleSettings {
+    /// Every entry start within the days around `from` and `to`, in order
+    fn starts_between(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<(NaiveDateTime, &ScheduleEntry)> {
+        let mut starts: Vec<(NaiveDateTime, &ScheduleEntry)> = from
+            .date()
+            .iter_days()
+            .take_while(|date| *date <= to.date())
+            .flat_map(|date| self.entries.iter().filter_map(move |entry| Some((entry.start_on(date, self.latitude, self.longitude)?, entry))))
+            .filter(|(start, _)| *start > from && *start <= to)
+            .collect();
+
+        starts.sort_by_key(|(start, _)| *start);
+        starts
+    }
+
+    /// The entry that started last in `(since, now]`, if any did
+    pub fn started(&self, since: NaiveDateTime, now: NaiveDateTime) -> Option<&ScheduleEntry> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.starts_between(since, now).pop().map(|(_, entry)| entry)
+    }
+
+    /// The entry that was started last, looking back up to a week
+    pub fn current(&self, now: NaiveDateTime) -> Option<&ScheduleEntry> {
+        self.started(now - TimeDelta::days(LOOKBACK_DAYS), now)
+    }
+
+    /// When the next entry starts, looking ahead up to a week
+    pub fn next(&self, now: NaiveDateTime) -> Option<(NaiveDateTime, &ScheduleEntry)> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.starts_between(now, now + TimeDelta::days(LOOKBACK_DAYS)).into_iter().next()
+    }
+}
+
+pub fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+/// Sunrise or sunset on a day in local time, following NOAA's approximation, or `None` during polar days and nights
+fn sun_event(date: NaiveDate, latitude: f64, longitude: f64, rising: bool) -> Option<NaiveDateTime> {
+    let gamma = 2.0 * PI / 365.0 * f64::from(date.ordinal() - 1);
+
+    let equation_of_time = 229.18 * (0.000_075 + 0.001_868 * gamma.cos() - 0.032_077 * gamma.sin() - 0.014_615 * (2.0 * gamma).cos() - 0.040_849 * (2.0 * gamma).sin());
+    let declination = 0.006_918 - 0.399_912 * gamma.cos() + 0.070_257 * gamma.sin() - 0.006_758 * (2.0 * gamma).cos() + 0.000_907 * (2.0 * gamma).sin() - 0.002_697 * (3.0 * gamma).cos()
+        + 0.001_48 * (3.0 * gamma).sin();
+
+    let latitude = latitude.to_radians();
+    // The sun's center being a bit below the horizon accounts for refraction and the size of the disk
+    let cos_hour_angle = 90.833_f64.to_radians().cos() / (latitude.cos() * declination.cos()) - latitude.tan() * declination.tan();
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let utc_minutes = if rising {
+        720.0 - 4.0 * (longitude + hour_angle) - equation_of_time
+    } else {
+        720.0 - 4.0 * (longitude - hour_angle) - equation_of_time
+    };
+
+    // Converted with the offset in effect at that moment, which on the days around a DST change isn't today's
+    let utc = date.and_hms_opt(0, 0, 0)? + TimeDelta::minutes(utc_minutes.round() as i64);
+    Some(Local.from_utc_datetime(&utc).naive_local())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, Timelike};
+
+    use super::{sun_event, ScheduleEntry, ScheduleSettings, Trigger};
+
+    /// In 2026 the clocks in Berlin go forward on Sunday the 29th of March
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn entry(days: [bool; 7], trigger: Trigger, profile: &str) -> ScheduleEntry {
+        ScheduleEntry {
+            days,
+            trigger,
+            profile: Some(profile.to_string()),
+        }
+    }
+
+    fn settings() -> ScheduleSettings {
+        let weekdays = [true, true, true, true, true, false, false];
+
+        ScheduleSettings {
+            enabled: true,
+            entries: vec![
+                entry(weekdays, Trigger::Time { hour: 21, minute: 0 }, "evening"),
+                entry([true; 7], Trigger::Time { hour: 9, minute: 0 }, "morning"),
+                // Doesn't exist on the day the clocks go forward, but still has to come up
+                entry([false, false, false, false, false, false, true], Trigger::Time { hour: 2, minute: 30 }, "night"),
+            ],
+            latitude: 52.52,
+            longitude: 13.405,
+        }
+    }
+
+    fn names<'a>(starts: impl IntoIterator<Item = (NaiveDateTime, &'a ScheduleEntry)>) -> Vec<(NaiveDateTime, &'a str)> {
+        starts.into_iter().map(|(start, entry)| (start, entry.profile.as_deref().unwrap())).collect()
+    }
+
+    #[test]
+    fn starts_in_order_across_days() {
+        let settings = settings();
+
+        // Friday morning to Monday morning, leaving out the start right at the beginning
+        assert_eq!(
+            names(settings.starts_between(at(27, 9, 0), at(30, 9, 0))),
+            vec![
+                (at(27, 21, 0), "evening"),
+                (at(28, 9, 0), "morning"),
+                (at(29, 2, 30), "night"),
+                (at(29, 9, 0), "morning"),
+                (at(30, 9, 0), "morning"),
+            ]
+        );
+    }
+
+    #[test]
+    fn started_picks_the_latest_start() {
+        let mut settings = settings();
+
+        assert_eq!(settings.started(at(27, 9, 0), at(27, 20, 59)), None);
+        assert_eq!(settings.started(at(27, 9, 0), at(28, 8, 0)).and_then(|entry| entry.profile.as_deref()), Some("evening"));
+        assert_eq!(settings.started(at(28, 9, 0), at(29, 3, 0)).and_then(|entry| entry.profile.as_deref()), Some("night"));
+        assert_eq!(settings.current(at(29, 3, 0)).and_then(|entry| entry.profile.as_deref()), Some("night"));
+
+        settings.enabled = false;
+        assert_eq!(settings.started(at(27, 9, 0), at(28, 8, 0)), None);
+    }
+
+    #[test]
+    fn next_looks_ahead_over_the_weekend() {
+        let mut settings = settings();
+
+        assert_eq!(names(settings.next(at(28, 10, 0))), vec![(at(29, 2, 30), "night")]);
+        assert_eq!(names(settings.next(at(29, 9, 0))), vec![(at(30, 9, 0), "morning")]);
+
+        settings.enabled = false;
+        assert_eq!(settings.next(at(28, 10, 0)), None);
+
+        settings.enabled = true;
+        settings.entries.iter_mut().for_each(|entry| entry.days = [false; 7]);
+        assert_eq!(settings.next(at(28, 10, 0)), None);
+    }
+
+    #[test]
+    fn sunset_follows_the_clocks_going_forward() {
+        // Only this test looks at the local time zone
+        std::env::set_var("TZ", "CET-1CEST,M3.5.0,M10.5.0/3");
+        let settings = settings();
+
+        let before = sun_event(at(28, 0, 0).date(), settings.latitude, settings.longitude, false).unwrap();
+        let after = sun_event(at(30, 0, 0).date(), settings.latitude, settings.longitude, false).unwrap();
+        assert_eq!(before.hour(), 18);
+        assert_eq!(after.hour(), 19);
+
+        // An hour from the clocks, and a couple of minutes a day from spring
+        let later = (after.time() - before.time()).num_minutes();
+        assert!((62..=67).contains(&later), "{later} minutes later");
+
+        let sunset = entry([true; 7], Trigger::Sunset { offset_minutes: -30 }, "dusk");
+        assert_eq!(
+            sunset.start_on(at(30, 0, 0).date(), settings.latitude, settings.longitude),
+            Some(after - chrono::TimeDelta::minutes(30))
+        );
+
+        // Nothing to work out during the polar night
+        assert_eq!(sun_event(NaiveDate::from_ymd_opt(2026, 12, 21).unwrap(), 78.22, 15.65, true), None);
+    }
+}
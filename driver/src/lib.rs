@@ -29,6 +29,7 @@ pub const SPEED_RANGE: std::ops::RangeInclusive<u8> = 1..=4;
 pub const BRIGHTNESS_RANGE: std::ops::RangeInclusive<u8> = 1..=2;
 pub const ZONE_RANGE: std::ops::RangeInclusive<u8> = 0..=3;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BaseEffects {
     Static,
     Breath,
@@ -92,7 +93,7 @@ impl Keyboard {
     pub fn refresh(&mut self) -> Result<()> {
         let payload = self.build_payload()?;
 
-        self.keyboard_hid.send_feature_report(&payload).unwrap();
+        self.keyboard_hid.send_feature_report(&payload)?;
 
         Ok(())
     }
@@ -126,6 +127,26 @@ impl Keyboard {
         Ok(())
     }
 
+    /// Set every parameter at once, sending a single payload to the keyboard
+    pub fn set_state(&mut self, effect: BaseEffects, speed: u8, brightness: u8, rgb_values: [u8; 12]) -> Result<()> {
+        if !SPEED_RANGE.contains(&speed) {
+            return Err(RangeError { kind: RangeErrorKind::Speed }.into());
+        }
+        if !BRIGHTNESS_RANGE.contains(&brightness) {
+            return Err(RangeError { kind: RangeErrorKind::Brightness }.into());
+        }
+
+        self.current_state = LightingState {
+            effect_type: effect,
+            speed,
+            brightness,
+            rgb_values,
+        };
+        self.refresh()?;
+
+        Ok(())
+    }
+
     pub fn set_zone_by_index(&mut self, zone_index: u8, new_values: [u8; 3]) -> Result<()> {
         if !ZONE_RANGE.contains(&zone_index) {
             return Err(RangeError { kind: RangeErrorKind::Zone }.into());
@@ -63,6 +63,9 @@
             expat
             openssl
 
+            # Audio visualizer
+            alsa-lib
+
             # Tray icon
             pango
             gtk3